@@ -0,0 +1,73 @@
+//! Compares choosing cells with and without the candidate heap of [Solver].
+//!
+//! Run with `cargo bench --bench candidates`.
+
+use std::{collections::HashMap, time::Instant};
+
+use wave_function_collapse::*;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum LandCoastSea {
+    Land,
+    Coast,
+    Sea,
+}
+
+struct GridTest {
+    layout: Grid<LandCoastSea>,
+}
+
+impl GridTest {
+    fn new(x: usize, y: usize) -> Self {
+        let mut layout = Grid::new(x, y);
+        let mut possibilities = HashMap::new();
+        possibilities.insert(LandCoastSea::Coast, 5);
+        possibilities.insert(LandCoastSea::Sea, 100);
+        possibilities.insert(LandCoastSea::Land, 100);
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl Wavefunction for GridTest {
+    type V = LandCoastSea;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        value: Self::V,
+    ) {
+        match value {
+            LandCoastSea::Land => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Sea);
+            }
+            LandCoastSea::Coast => {}
+            LandCoastSea::Sea => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Land)
+            }
+        }
+    }
+}
+
+fn bench_greedy(name: &str, x: usize, y: usize, cache: bool) {
+    let mut solver = Solver::new(GridTest::new(x, y));
+    solver.set_candidate_cache(cache);
+    let start = Instant::now();
+    solver.solve_greedy();
+    println!("{}: {:?}", name, start.elapsed());
+}
+
+fn main() {
+    // A full solve clones the layout for every collapse, which dominates at
+    // this size, so the greedy solve is used to time the cell selection.
+    bench_greedy("greedy 200x200 cached", 200, 200, true);
+    bench_greedy("greedy 200x200 scan", 200, 200, false);
+}
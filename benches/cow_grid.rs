@@ -0,0 +1,71 @@
+//! Compares solving a hard sudoku on a [Grid] and on a [CowGrid].
+//!
+//! Run with `cargo bench --bench cow_grid`.
+
+use std::{collections::HashMap, time::Instant};
+
+use wave_function_collapse::*;
+
+const HARD: [(usize, usize, usize); 26] = [
+    (1, 0, 3), (3, 0, 8), (5, 0, 2), (8, 0, 5),
+    (0, 1, 6), (5, 1, 9),
+    (2, 2, 8), (3, 2, 5), (7, 2, 4), (8, 2, 3),
+    (2, 3, 7), (8, 3, 1),
+    (2, 4, 9), (6, 4, 4),
+    (0, 5, 5), (6, 5, 7),
+    (0, 6, 9), (1, 6, 6), (5, 6, 1), (6, 6, 3),
+    (3, 7, 6), (8, 7, 8),
+    (0, 8, 2), (3, 8, 9), (5, 8, 3), (7, 8, 7),
+];
+
+struct Sudoku<L> {
+    layout: L,
+}
+
+impl<L: Layout<usize, Coordinate = Coord2D>> Sudoku<L> {
+    fn new(mut layout: L) -> Self {
+        let possibilities: HashMap<_, _> = (1..=9).map(|value| (value, 1)).collect();
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl<L: Layout<usize, Coordinate = Coord2D>> Wavefunction for Sudoku<L> {
+    type V = usize;
+    type L = L;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(&mut self, layout: &mut Self::L, coord: Coord2D, value: Self::V) {
+        let (box_x, box_y) = (coord.x() / 3 * 3, coord.y() / 3 * 3);
+        let mut peers = Vec::new();
+        for index in 0..9 {
+            peers.push(Coord2D::new(index, coord.y()));
+            peers.push(Coord2D::new(coord.x(), index));
+            peers.push(Coord2D::new(box_x + index % 3, box_y + index / 3));
+        }
+        layout.remove_cells_possibility(peers, &value);
+    }
+}
+
+fn bench_hard<L: Layout<usize, Coordinate = Coord2D>>(name: &str, layout: L) {
+    let start = Instant::now();
+    let mut backtracks = 0;
+    for seed in 0..20 {
+        let mut solver = Solver::new(Sudoku::new(layout.clone()));
+        solver.set_seed(seed);
+        for (x, y, value) in HARD {
+            solver.collapse_initial(Coord2D::new(x, y), value);
+        }
+        solver.solve().expect("hard sudoku should be solvable");
+        backtracks += solver.get_backtrack_count();
+    }
+    println!("{}: {:?} for 20 solves, {} backtracks", name, start.elapsed(), backtracks);
+}
+
+fn main() {
+    bench_hard("sudoku_hard Grid", Grid::new(9, 9));
+    bench_hard("sudoku_hard CowGrid", CowGrid::new(9, 9));
+}
@@ -0,0 +1,120 @@
+//! Compares solving a hard sudoku with and without layout checkpoints, see
+//! [Solver::set_journaling], and measures how solving a large layout scales
+//! with the candidate heap, which is checkpointed along with the layout.
+//!
+//! Run with `cargo bench --bench journal`.
+
+use std::{collections::HashMap, time::Instant};
+
+use wave_function_collapse::*;
+
+const HARD: [(usize, usize, usize); 26] = [
+    (1, 0, 3), (3, 0, 8), (5, 0, 2), (8, 0, 5),
+    (0, 1, 6), (5, 1, 9),
+    (2, 2, 8), (3, 2, 5), (7, 2, 4), (8, 2, 3),
+    (2, 3, 7), (8, 3, 1),
+    (2, 4, 9), (6, 4, 4),
+    (0, 5, 5), (6, 5, 7),
+    (0, 6, 9), (1, 6, 6), (5, 6, 1), (6, 6, 3),
+    (3, 7, 6), (8, 7, 8),
+    (0, 8, 2), (3, 8, 9), (5, 8, 3), (7, 8, 7),
+];
+
+struct Sudoku<L> {
+    layout: L,
+}
+
+impl<L: Layout<usize, Coordinate = Coord2D>> Sudoku<L> {
+    fn new(mut layout: L) -> Self {
+        let possibilities: HashMap<_, _> = (1..=9).map(|value| (value, 1)).collect();
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl<L: Layout<usize, Coordinate = Coord2D>> Wavefunction for Sudoku<L> {
+    type V = usize;
+    type L = L;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(&mut self, layout: &mut Self::L, coord: Coord2D, value: Self::V) {
+        let (box_x, box_y) = (coord.x() / 3 * 3, coord.y() / 3 * 3);
+        let mut peers = Vec::new();
+        for index in 0..9 {
+            peers.push(Coord2D::new(index, coord.y()));
+            peers.push(Coord2D::new(coord.x(), index));
+            peers.push(Coord2D::new(box_x + index % 3, box_y + index / 3));
+        }
+        layout.remove_cells_possibility(peers, &value);
+    }
+}
+
+fn bench_hard(name: &str, journaling: bool) {
+    let start = Instant::now();
+    let mut backtracks = 0;
+    for seed in 0..20 {
+        let mut solver = Solver::new(Sudoku::new(Grid::new(9, 9)));
+        solver.set_seed(seed);
+        solver.set_journaling(journaling);
+        for (x, y, value) in HARD {
+            solver.collapse_initial(Coord2D::new(x, y), value);
+        }
+        solver.solve().expect("hard sudoku should be solvable");
+        backtracks += solver.get_backtrack_count();
+    }
+    println!("{}: {:?} for 20 solves, {} backtracks", name, start.elapsed(), backtracks);
+}
+
+// Neighbors may not share one of four colors
+struct Coloring {
+    layout: Grid<usize>,
+}
+
+impl Coloring {
+    fn new(size: usize) -> Self {
+        let mut layout = Grid::new(size, size);
+        let possibilities: HashMap<_, _> = (0..4).map(|value| (value, 1)).collect();
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl Wavefunction for Coloring {
+    type V = usize;
+    type L = Grid<usize>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(&mut self, layout: &mut Self::L, coord: Coord2D, value: Self::V) {
+        let neighbors = coord.neighbor_directions4().into_iter().map(|(neighbor, _)| neighbor).collect();
+        layout.remove_cells_possibility(neighbors, &value);
+    }
+}
+
+fn bench_coloring(name: &str, size: usize, candidate_cache: bool) {
+    let start = Instant::now();
+    let mut backtracks = 0;
+    for seed in 0..5 {
+        let mut solver = Solver::new(Coloring::new(size));
+        solver.set_seed(seed);
+        solver.set_candidate_cache(candidate_cache);
+        solver.solve().expect("four colors should always be solvable");
+        backtracks += solver.get_backtrack_count();
+    }
+    println!("{}: {:?} for 5 solves, {} backtracks", name, start.elapsed(), backtracks);
+}
+
+fn main() {
+    bench_hard("sudoku_hard checkpoints", true);
+    bench_hard("sudoku_hard clones", false);
+
+    bench_coloring("coloring 30x30 heap", 30, true);
+    bench_coloring("coloring 30x30 scans", 30, false);
+    bench_coloring("coloring 60x60 heap", 60, true);
+    bench_coloring("coloring 60x60 scans", 60, false);
+}
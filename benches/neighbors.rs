@@ -0,0 +1,154 @@
+//! Compares solving with and without the neighbor cache of [Grid].
+//!
+//! Run with `cargo bench --bench neighbors`.
+
+use std::{collections::HashMap, time::Instant};
+
+use wave_function_collapse::*;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum LandCoastSea {
+    Land,
+    Coast,
+    Sea,
+}
+
+struct GridTest {
+    layout: Grid<LandCoastSea>,
+}
+
+impl GridTest {
+    fn new(layout: Grid<LandCoastSea>) -> Self {
+        let mut layout = layout;
+        let mut possibilities = HashMap::new();
+        possibilities.insert(LandCoastSea::Coast, 5);
+        possibilities.insert(LandCoastSea::Sea, 100);
+        possibilities.insert(LandCoastSea::Land, 100);
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl Wavefunction for GridTest {
+    type V = LandCoastSea;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        value: Self::V,
+    ) {
+        match value {
+            LandCoastSea::Land => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Sea);
+            }
+            LandCoastSea::Coast => {}
+            LandCoastSea::Sea => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Land)
+            }
+        }
+    }
+}
+
+fn bench_solve(name: &str, layout: Grid<LandCoastSea>, runs: u32) {
+    let mut solver = Solver::new(GridTest::new(layout));
+    let start = Instant::now();
+    for _ in 0..runs {
+        solver.solve_greedy();
+    }
+    println!("{}: {:?} per solve", name, start.elapsed() / runs);
+}
+
+fn bench_neighbors(name: &str, layout: Grid<LandCoastSea>, runs: u32) {
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..runs {
+        for y in 0..layout.y() {
+            for x in 0..layout.x() {
+                total += layout.neighbors(Coord2D::new(x, y)).len();
+            }
+        }
+    }
+    println!("{}: {:?} per pass ({} neighbors)", name, start.elapsed() / runs, total);
+}
+
+fn bench_cached_neighbors(name: &str, layout: Grid<LandCoastSea>, runs: u32) {
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..runs {
+        for y in 0..layout.y() {
+            for x in 0..layout.x() {
+                total += layout.cached_neighbors(Coord2D::new(x, y)).unwrap().len();
+            }
+        }
+    }
+    println!("{}: {:?} per pass ({} neighbors)", name, start.elapsed() / runs, total);
+}
+
+fn bench_neighbors_ref(name: &str, layout: Grid<LandCoastSea>, runs: u32) {
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..runs {
+        for y in 0..layout.y() {
+            for x in 0..layout.x() {
+                total += layout.neighbors_ref(Coord2D::new(x, y)).len();
+            }
+        }
+    }
+    println!("{}: {:?} per pass ({} neighbors)", name, start.elapsed() / runs, total);
+}
+
+fn bench_rows(name: &str, layout: Grid<LandCoastSea>, runs: u32) {
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..runs {
+        for y in 0..layout.y() {
+            total += layout.row_ref(y).len();
+        }
+        for x in 0..layout.x() {
+            total += layout.col_ref(x).len();
+        }
+    }
+    println!("{}: {:?} per pass ({} coordinates)", name, start.elapsed() / runs, total);
+}
+
+fn main() {
+    bench_solve("solve 50x30", Grid::new(50, 30), 20);
+    bench_solve(
+        "solve 50x30 cached",
+        Grid::new(50, 30).with_cached_neighbors(),
+        20,
+    );
+
+    bench_neighbors("neighbors 200x200", Grid::new(200, 200), 20);
+    bench_neighbors(
+        "neighbors 200x200 cached",
+        Grid::new(200, 200).with_cached_neighbors(),
+        20,
+    );
+    bench_cached_neighbors(
+        "cached_neighbors 200x200",
+        Grid::new(200, 200).with_cached_neighbors(),
+        20,
+    );
+    bench_neighbors_ref("neighbors_ref 200x200", Grid::new(200, 200), 20);
+    bench_neighbors_ref(
+        "neighbors_ref 200x200 cached",
+        Grid::new(200, 200).with_cached_neighbors(),
+        20,
+    );
+
+    bench_rows("rows and columns 200x200", Grid::new(200, 200), 200);
+    bench_rows(
+        "rows and columns 200x200 cached",
+        Grid::new(200, 200).with_cached_neighbors(),
+        200,
+    );
+}
@@ -0,0 +1,204 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    hash::Hash,
+};
+
+use rand::Rng;
+
+use crate::weighted_iterator::stable_hash;
+
+/// A priority queue of the cells that the solver may collapse next, ordered
+/// by their selection score.
+///
+/// Rather than being removed, entries for cells that changed are left in the
+/// heap and skipped when popped. Each cell has a version that is increased on
+/// every update, and only the entry with the current version is live.
+///
+/// Ties between equal scores are broken by a random order of the cells, which
+/// is chosen when the heap is created. It does not depend on the order in
+/// which cells are updated, so a seeded solve stays reproducible even when a
+/// wavefunction changes cells in the iteration order of a HashMap.
+///
+/// Like a layout, the heap can be checkpointed and restored, so that the
+/// solver can undo the updates of a failed branch without copying the heap.
+/// While there are checkpoints, every change is logged, and entries that a
+/// restore could make live again are kept in the heap.
+#[derive(Clone)]
+pub(crate) struct CandidateHeap<C: Clone + Eq + Hash> {
+    heap: BinaryHeap<Entry<C>>,
+    versions: HashMap<C, u64>,
+    next_version: u64,
+    salt: u64,
+    /// The length of the heap below which it is not compacted.
+    compact_at: usize,
+    /// The changes made since the first checkpoint, in order.
+    changes: Vec<Change<C>>,
+    /// The length of `changes` at each checkpoint.
+    checkpoints: Vec<usize>,
+}
+
+// A change to the heap that a restore undoes.
+#[derive(Clone)]
+enum Change<C> {
+    /// The version of the cell before it was changed.
+    Version(C, Option<u64>),
+    /// An entry that was popped from the heap.
+    Popped(Entry<C>),
+    /// The salt before the heap was reset.
+    Salt(u64),
+}
+
+impl<C: Clone + Eq + Hash> CandidateHeap<C> {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            versions: HashMap::new(),
+            next_version: 0,
+            salt: rng.gen(),
+            compact_at: 0,
+            changes: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Removes every candidate, and chooses a new order for breaking ties, as
+    /// if the heap was created anew.
+    pub fn reset(&mut self, rng: &mut impl Rng) {
+        let salt = std::mem::replace(&mut self.salt, rng.gen());
+        if self.checkpoints.is_empty() {
+            self.heap.clear();
+            self.versions.clear();
+            return;
+        }
+        self.changes.push(Change::Salt(salt));
+        for (coord, version) in self.versions.drain() {
+            self.changes.push(Change::Version(coord, Some(version)));
+        }
+    }
+
+    /// Saves the state of the heap, so that it can be returned to with
+    /// [CandidateHeap::restore].
+    pub fn checkpoint(&mut self) {
+        self.checkpoints.push(self.changes.len());
+    }
+
+    /// Returns the heap to the state of the most recent checkpoint, and
+    /// removes that checkpoint.
+    pub fn restore(&mut self) {
+        let Some(start) = self.checkpoints.pop() else {
+            return;
+        };
+        for change in self.changes.drain(start..).rev() {
+            match change {
+                Change::Version(coord, Some(version)) => {
+                    self.versions.insert(coord, version);
+                }
+                Change::Version(coord, None) => {
+                    self.versions.remove(&coord);
+                }
+                Change::Popped(entry) => self.heap.push(entry),
+                Change::Salt(salt) => self.salt = salt,
+            }
+        }
+    }
+
+    // Sets the version of the cell, logging the previous one
+    fn set_version(&mut self, coord: C, version: Option<u64>) {
+        let previous = match version {
+            Some(version) => self.versions.insert(coord.clone(), version),
+            None => self.versions.remove(&coord),
+        };
+        if !self.checkpoints.is_empty() && previous != version {
+            self.changes.push(Change::Version(coord, previous));
+        }
+    }
+
+    /// Sets the score of each cell, where a score of None means the cell is no
+    /// longer a candidate.
+    pub fn update(&mut self, scores: Vec<(C, Option<f64>)>) {
+        for (coord, score) in scores {
+            match score {
+                Some(score) => {
+                    let version = self.next_version;
+                    self.next_version += 1;
+                    self.set_version(coord.clone(), Some(version));
+                    self.heap.push(Entry {
+                        score,
+                        tiebreak: stable_hash(&(self.salt, &coord)),
+                        version,
+                        coord,
+                    });
+                }
+                None => self.set_version(coord, None),
+            }
+        }
+
+        // Drop the stale entries once they outnumber the live ones, keeping
+        // those that were live at a checkpoint
+        if self.heap.len() > (2 * self.versions.len() + 16).max(self.compact_at) {
+            let versions = &self.versions;
+            let saved: HashSet<u64> = self
+                .changes
+                .iter()
+                .filter_map(|change| match change {
+                    Change::Version(_, version) => *version,
+                    _ => None,
+                })
+                .collect();
+            self.heap.retain(|entry| {
+                versions.get(&entry.coord) == Some(&entry.version) || saved.contains(&entry.version)
+            });
+            self.compact_at = 2 * self.heap.len();
+        }
+    }
+
+    /// Removes and returns the candidate with the lowest score.
+    pub fn pop(&mut self) -> Option<C> {
+        while let Some(entry) = self.heap.pop() {
+            let live = self.versions.get(&entry.coord) == Some(&entry.version);
+            let coord = entry.coord.clone();
+            if !self.checkpoints.is_empty() {
+                self.changes.push(Change::Popped(entry));
+            }
+            if live {
+                self.set_version(coord.clone(), None);
+                return Some(coord);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone)]
+struct Entry<C> {
+    score: f64,
+    tiebreak: u64,
+    version: u64,
+    coord: C,
+}
+
+impl<C> PartialEq for Entry<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<C> Eq for Entry<C> {}
+
+impl<C> PartialOrd for Entry<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<C> Ord for Entry<C> {
+    // Reversed, so that the max heap yields the lowest score first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .total_cmp(&self.score)
+            .then(other.tiebreak.cmp(&self.tiebreak))
+            .then(other.version.cmp(&self.version))
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     cmp::{max, min},
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
     hash::Hash,
 };
@@ -49,12 +49,17 @@ pub enum Function {
     A,
     /// The the weight from the merged weights.
     B,
-    /// Multiply the weights together (Unimplimented).
+    /// The product of the two weights, saturating at usize::MAX.
     Multiply,
+    /// Multiply the weights as probabilities, see
+    /// [Cell::merge_cell_possibilities].
+    MultiplyNormalized,
     /// The sum of the two weights.
     Add,
     /// The difference of the two weights.
     Subtract,
+    /// The mean of the two weights, rounded down.
+    Average,
 }
 
 /// Trait that indicates a type can be used as the value in a [Cell].
@@ -85,13 +90,13 @@ pub enum Cell<V: CellValue> {
 }
 
 impl<V: CellValue> Cell<V> {
+    /// The total weight that [Cell::set_float_weights] distributes between
+    /// the possibilities.
+    pub const FLOAT_WEIGHT_SCALE: usize = 1 << 20;
+
     /// Returns true if the Cell is collapsed.
     pub fn is_collapsed(&self) -> bool {
-        if let Cell::Collapsed(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Cell::Collapsed(_))
     }
 
     /// Calculates the Shannon entropy of the cell. If the cell is collapsed,
@@ -102,10 +107,10 @@ impl<V: CellValue> Cell<V> {
             Cell::Collapsed(_) => 0.0,
             Cell::Uncollapsed(possibilities) => {
                 let total = possibilities
-                    .iter()
-                    .fold(0.0, |acc, (_, chance)| acc + *chance as f64);
+                    .values()
+                    .fold(0.0, |acc, chance| acc + *chance as f64);
                 let mut entropy = 0.0;
-                for (_possibility, chance) in possibilities {
+                for chance in possibilities.values() {
                     let probability = *chance as f64 / total;
                     entropy += probability * probability.log2();
                 }
@@ -139,8 +144,90 @@ impl<V: CellValue> Cell<V> {
         }
     }
 
+    /// The probability that the cell takes the value, from its weight
+    /// relative to the total weight of the cell.
+    ///
+    /// A collapsed cell has a probability of 1.0 for its value and 0.0 for
+    /// any other. The probability is 0.0 if the value is not a possibility,
+    /// or if every weight is zero.
+    pub fn probability(&self, value: &V) -> f64 {
+        match self {
+            Cell::Collapsed(collapsed) => {
+                if collapsed == value {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Cell::Uncollapsed(possibilities) => {
+                let total: usize = possibilities.values().sum();
+                match possibilities.get(value) {
+                    Some(weight) if total > 0 => *weight as f64 / total as f64,
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+
+    /// The probability of every possibility of the cell, which sum to 1.0
+    /// unless every weight is zero.
+    ///
+    /// A collapsed cell has a probability of 1.0 for its value. This is the
+    /// floating point view of the weights, see [Cell::probability].
+    pub fn probabilities(&self) -> HashMap<V, f64> {
+        match self {
+            Cell::Collapsed(value) => HashMap::from([(*value, 1.0)]),
+            Cell::Uncollapsed(possibilities) => possibilities
+                .keys()
+                .map(|value| (*value, self.probability(value)))
+                .collect(),
+        }
+    }
+
+    /// Makes the cell into [Cell::Uncollapsed] with possibilities weighted by
+    /// floating point values.
+    ///
+    /// The weights are stored as integers, in fixed point: they are scaled to
+    /// sum to [Cell::FLOAT_WEIGHT_SCALE] and rounded, so each probability is
+    /// kept to within about one in a million and the rest of the usize API
+    /// keeps working. Small integer weights cannot express most fractions,
+    /// such as a 12.3% chance, and this avoids having to pick a large integer
+    /// scale by hand. A positive weight too small to survive the rounding is
+    /// kept with a weight of 1. Weights that are zero, negative, or not finite
+    /// are not added. The stored weights are only approximately proportional
+    /// to the given ones, so do not compare them exactly. Merging them with
+    /// weights of another scale, such as with [Function::Add], mixes scales.
+    pub fn set_float_weights(&mut self, weights: &HashMap<V, f64>) {
+        let valid = || {
+            weights
+                .iter()
+                .filter(|(_, weight)| weight.is_finite() && **weight > 0.0)
+        };
+        let total: f64 = valid().map(|(_, weight)| weight).sum();
+        let scale = Self::FLOAT_WEIGHT_SCALE as f64;
+        let possibilities = valid()
+            .map(|(value, weight)| (*value, ((weight / total * scale).round() as usize).max(1)))
+            .collect();
+        *self = Cell::Uncollapsed(possibilities);
+    }
+
+    /// The largest entropy the cell could have with its current
+    /// possibilities, which is when all of their weights are equal.
+    ///
+    /// This is the base two logarithm of the number of possibilities with a
+    /// weight above zero, or 0.0 if the cell is collapsed.
+    pub fn max_entropy(&self) -> f64 {
+        match self {
+            Cell::Collapsed(_) => 0.0,
+            Cell::Uncollapsed(possibilities) => {
+                let count = possibilities.values().filter(|weight| **weight > 0).count();
+                (count.max(1) as f64).log2()
+            }
+        }
+    }
+
     /// Gets the value if the cell is collapsed, None otherwise.
-    pub fn get_value(&mut self) -> Option<V> {
+    pub fn get_value(&self) -> Option<V> {
         match self {
             Cell::Collapsed(value) => Some(*value),
             Cell::Uncollapsed(_) => None,
@@ -149,13 +236,44 @@ impl<V: CellValue> Cell<V> {
 
     /// Returns a HashMap of possibilities this cell could collapse to. If the
     /// cell is already collapsed, it returns an empty HashMap.
-    pub fn get_possibilities(&mut self) -> HashMap<V, usize> {
+    pub fn get_possibilities(&self) -> HashMap<V, usize> {
         match self {
             Cell::Collapsed(_) => HashMap::new(),
             Cell::Uncollapsed(possibilities) => possibilities.clone(),
         }
     }
 
+    /// Returns true if the cell is uncollapsed and its possibilities are
+    /// exactly the values in `expected`.
+    ///
+    /// If `compare_weights` is true, the weight of each possibility must also
+    /// match, otherwise only the set of values is compared. A collapsed cell
+    /// never matches. This is useful for testing the propagation of a
+    /// wavefunction without running a full solve.
+    pub fn possibilities_eq(&self, expected: &HashMap<V, usize>, compare_weights: bool) -> bool {
+        match self {
+            Cell::Collapsed(_) => false,
+            Cell::Uncollapsed(possibilities) => {
+                if compare_weights {
+                    possibilities == expected
+                } else {
+                    possibilities.len() == expected.len()
+                        && possibilities.keys().all(|value| expected.contains_key(value))
+                }
+            }
+        }
+    }
+
+    /// If uncollapsed, releases the memory the possibilities no longer need
+    /// after some were removed.
+    ///
+    /// This is only a memory optimization, the possibilities are unchanged.
+    pub fn shrink(&mut self) {
+        if let Self::Uncollapsed(possibilities) = self {
+            possibilities.shrink_to_fit();
+        }
+    }
+
     /// Makes the cell into [Cell::Uncollapsed] with the given set of possibilities.
     pub fn set_possibilities(&mut self, possibilities: HashMap<V, usize>) {
         *self = Cell::Uncollapsed(possibilities);
@@ -169,7 +287,7 @@ impl<V: CellValue> Cell<V> {
     pub fn add_possibility(&mut self, possibility: &V) {
         if let Self::Uncollapsed(values) = self {
             let value = values.entry(*possibility).or_insert(0);
-            *value = *value + 1;
+            *value += 1;
         }
     }
 
@@ -181,7 +299,7 @@ impl<V: CellValue> Cell<V> {
     pub fn add_possibility_count(&mut self, possibility: &V, weight: usize) {
         if let Self::Uncollapsed(values) = self {
             let value = values.entry(*possibility).or_insert(0);
-            *value = *value + weight;
+            *value += weight;
         }
     }
 
@@ -194,11 +312,22 @@ impl<V: CellValue> Cell<V> {
         if let Self::Uncollapsed(values) = self {
             for (possibility, count) in possibilities {
                 let value = values.entry(*possibility).or_insert(0);
-                *value = *value + count;
+                *value += count;
             }
         }
     }
 
+    /// If uncollapsed, adds each of the possibilities to the set of
+    /// possibilities with a weight of 1
+    ///
+    /// If a possibility is already in the set, 1 is added to the existing
+    /// weight, as with [Cell::add_possibility].
+    pub fn add_possibilities_set(&mut self, possibilities: &HashSet<V>) {
+        for possibility in possibilities {
+            self.add_possibility(possibility);
+        }
+    }
+
     /// If uncollapsed, removes a possibility from the set of possibilities
     ///
     /// The weight is always removed outright
@@ -255,6 +384,17 @@ impl<V: CellValue> Cell<V> {
     /// The second is [Function], which describes how the overlaping weights
     /// will be combined.  For example, if the function is [Function::Max], the
     /// larger of the two weights will be used.
+    ///
+    /// [Function::MultiplyNormalized] treats both maps of weights as
+    /// probability distributions. Each is scaled so that its weights sum to
+    /// 1000, the overlapping weights are multiplied, and the products are
+    /// scaled back so that they sum to 1000. With [Operation::Intersection]
+    /// this is the probabilistic AND of the two distributions. Weights that
+    /// are not in the overlap keep their original scale, so other operations
+    /// mix scales. The integer arithmetic rounds down at every step, so
+    /// possibilities less likely than about one in a thousand lose precision,
+    /// and a product of two nonzero weights that rounds to zero is kept with a
+    /// weight of 1 rather than becoming impossible.
     pub fn merge_cell_possibilities(
         &mut self,
         op: Operation,
@@ -264,16 +404,24 @@ impl<V: CellValue> Cell<V> {
         let Self::Uncollapsed(cell_weights) = self else {
             return;
         };
+        let products = match func {
+            Function::MultiplyNormalized => Some(normalized_products(cell_weights, weights)),
+            _ => None,
+        };
+        let merge_possibility = |value: &V, weight: &mut usize, other_weight: &usize| match &products {
+            Some(products) => *weight = products[value],
+            None => merge_possibility(func, weight, other_weight),
+        };
         cell_weights.retain(|value, weight| match op {
             Operation::Union => {
                 if let Some(other_weight) = weights.get(value) {
-                    merge_possibility(func, weight, other_weight);
+                    merge_possibility(value, weight, other_weight);
                 }
                 true
             }
             Operation::Modification => {
                 if let Some(other_weight) = weights.get(value) {
-                    merge_possibility(func, weight, other_weight);
+                    merge_possibility(value, weight, other_weight);
                 }
                 true
             }
@@ -281,7 +429,7 @@ impl<V: CellValue> Cell<V> {
             Operation::Subtraction => !weights.contains_key(value),
             Operation::Replacement => {
                 if let Some(other_weight) = weights.get(value) {
-                    merge_possibility(func, weight, other_weight);
+                    merge_possibility(value, weight, other_weight);
                     true
                 } else {
                     false
@@ -289,7 +437,7 @@ impl<V: CellValue> Cell<V> {
             }
             Operation::Intersection => {
                 if let Some(other_weight) = weights.get(value) {
-                    merge_possibility(func, weight, other_weight);
+                    merge_possibility(value, weight, other_weight);
                     true
                 } else {
                     false
@@ -305,11 +453,45 @@ impl<V: CellValue> Cell<V> {
             | Operation::Xor
             | Operation::Replacement
             | Operation::ExclusiveReplacement => {
-                self.add_possibilities(&weights);
+                self.add_possibilities(weights);
             }
-            _ => return, // Other options do not care
+            _ => {} // Other options do not care
         }
     }
+
+    /// Merges the other cell into this one, for example when overlaying two
+    /// candidate layouts.
+    ///
+    /// A collapsed cell is treated as certain, so the operation and function
+    /// only apply when both cells are uncollapsed:
+    ///
+    /// * Uncollapsed and uncollapsed: the possibilities of the other cell are
+    ///   merged in as by [Cell::merge_cell_possibilities].
+    /// * Uncollapsed and collapsed: this cell collapses to the value of the
+    ///   other cell if it is one of its possibilities.
+    /// * Collapsed and uncollapsed: this cell stays collapsed if its value is
+    ///   one of the possibilities of the other cell.
+    /// * Collapsed and collapsed: this cell stays collapsed if both cells have
+    ///   the same value.
+    ///
+    /// Any other combination is a contradiction, and this cell is left
+    /// uncollapsed with no possibilities.
+    pub fn merge_with(&mut self, other: &Cell<V>, op: Operation, func: Function) {
+        let compatible = match (&*self, other) {
+            (Self::Uncollapsed(_), Self::Uncollapsed(weights)) => {
+                self.merge_cell_possibilities(op, func, weights);
+                return;
+            }
+            (Self::Uncollapsed(weights), Self::Collapsed(value)) => weights.contains_key(value),
+            (Self::Collapsed(value), Self::Uncollapsed(weights)) => weights.contains_key(value),
+            (Self::Collapsed(value), Self::Collapsed(other_value)) => value == other_value,
+        };
+        *self = match (compatible, other) {
+            (true, Self::Collapsed(value)) => Self::Collapsed(*value),
+            (true, Self::Uncollapsed(_)) => return,
+            (false, _) => Self::Uncollapsed(HashMap::new()),
+        };
+    }
 }
 
 fn merge_possibility(func: Function, first: &mut usize, second: &usize) {
@@ -319,12 +501,51 @@ fn merge_possibility(func: Function, first: &mut usize, second: &usize) {
         Function::Max => *first = max(*first, *second),
         Function::A => {} // the item is already set to the first item
         Function::B => *first = *second,
-        Function::Multiply => unimplemented!("This is more complicated"), // because it should turn it into a float [0, 1] before multiplication
-        Function::Add => *first = *first + second,
+        Function::Multiply => *first = first.saturating_mul(*second),
+        Function::MultiplyNormalized => unreachable!("normalized products are merged by the cell"),
+        Function::Add => *first += second,
         Function::Subtract => *first = first.saturating_sub(*second),
+        Function::Average => *first = *first / 2 + *second / 2 + (*first % 2 + *second % 2) / 2,
     }
 }
 
+// The probability scale that Function::MultiplyNormalized works in.
+const NORMALIZED_SCALE: u128 = 1000;
+
+// Multiplies the overlapping weights of the two maps as probabilities.
+fn normalized_products<V: CellValue>(
+    first: &HashMap<V, usize>,
+    second: &HashMap<V, usize>,
+) -> HashMap<V, usize> {
+    let scale = |weights: &HashMap<V, usize>| {
+        let total = weights.values().map(|weight| *weight as u128).sum::<u128>().max(1);
+        move |weight: usize| weight as u128 * NORMALIZED_SCALE / total
+    };
+    let (scale_first, scale_second) = (scale(first), scale(second));
+    // Each product is kept with whether both of its weights were possible, so
+    // that rounding alone never makes a possibility impossible
+    let products: HashMap<V, (u128, bool)> = first
+        .iter()
+        .filter_map(|(value, weight)| {
+            let other_weight = second.get(value)?;
+            let possible = *weight > 0 && *other_weight > 0;
+            Some((*value, (scale_first(*weight) * scale_second(*other_weight), possible)))
+        })
+        .collect();
+
+    let total = products.values().map(|(product, _)| product).sum::<u128>().max(1);
+    products
+        .into_iter()
+        .map(|(value, (product, possible))| {
+            let weight = (product * NORMALIZED_SCALE / total) as usize;
+            match possible {
+                true => (value, weight.max(1)),
+                false => (value, 0),
+            }
+        })
+        .collect()
+}
+
 impl<V> Debug for Cell<V>
 where
     V: Debug + CellValue,
@@ -358,7 +579,7 @@ where
                 write!(f, "{}", value)
             }
             Cell::Uncollapsed(values) => {
-                let vals: Vec<_> = values.iter().map(|(e, _count)| e.to_string()).collect();
+                let vals: Vec<_> = values.keys().map(|e| e.to_string()).collect();
                 write!(f, "{{{}}}", vals.join("|"))
             }
         }
@@ -0,0 +1,90 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Picks one item out of a list of weighted items.
+///
+/// The solver uses a chooser to decide the order in which it tries the
+/// possible values of a cell. It calls [Chooser::choose] with the values that
+/// are left to try and their weights, and tries the chosen value next. The
+/// slice is always in the same order for the same values, independent of the
+/// iteration order of the HashMap in the [Cell](crate::Cell), so a chooser
+/// that depends only on its arguments and its own state is reproducible.
+///
+/// A custom chooser only needs to return the index of an item in the slice:
+///
+/// ```
+/// use wave_function_collapse::Chooser;
+///
+/// /// Always picks the item with the smallest weight.
+/// struct MinWeight;
+///
+/// impl<V> Chooser<V> for MinWeight {
+///     fn choose(&mut self, items: &[(V, usize)]) -> Option<usize> {
+///         (0..items.len()).min_by_key(|index| items[*index].1)
+///     }
+/// }
+/// ```
+///
+/// Returning [None], or an index that is out of bounds, stops the choosing
+/// for that cell, and the remaining values are tried in the order of the
+/// slice.
+///
+/// See [Solver::set_chooser](crate::Solver::set_chooser).
+pub trait Chooser<V> {
+    /// Returns the index of the chosen item in `items`.
+    fn choose(&mut self, items: &[(V, usize)]) -> Option<usize>;
+}
+
+/// Chooses items at random, where items with larger weights are more likely
+/// to be chosen.
+///
+/// Items with a weight of zero are only chosen once no item with a positive
+/// weight is left, in which case the first item is chosen.
+pub struct RandomChooser {
+    rng: StdRng,
+}
+
+impl RandomChooser {
+    /// Create a new [RandomChooser] whose random number generator is seeded
+    /// with the given seed.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl<V> Chooser<V> for RandomChooser {
+    fn choose(&mut self, items: &[(V, usize)]) -> Option<usize> {
+        if items.is_empty() {
+            return None;
+        }
+        let total: usize = items.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return Some(0);
+        }
+        let mut selection = self.rng.gen_range(0..total);
+        for (index, (_, weight)) in items.iter().enumerate() {
+            if selection < *weight {
+                return Some(index);
+            }
+            selection -= weight;
+        }
+        None
+    }
+}
+
+/// Always chooses the item with the largest weight, and the first of them
+/// when several items share the largest weight.
+pub struct MaxWeightChooser;
+
+impl<V> Chooser<V> for MaxWeightChooser {
+    fn choose(&mut self, items: &[(V, usize)]) -> Option<usize> {
+        let mut chosen: Option<usize> = None;
+        for (index, (_, weight)) in items.iter().enumerate() {
+            if chosen.is_none_or(|best| *weight > items[best].1) {
+                chosen = Some(index);
+            }
+        }
+        chosen
+    }
+}
@@ -0,0 +1,188 @@
+use std::{borrow::Cow, collections::HashMap, rc::Rc};
+
+use crate::{cell::Cell, CellValue, Coord2D, Grid};
+
+use super::Layout;
+
+/// A two dimensional grid [Layout] whose clones share their unchanged cells.
+///
+/// Each cell is stored behind an [Rc], so cloning the grid only copies a
+/// pointer per cell instead of the possibilities of every uncollapsed cell.
+/// A cell is copied the first time it is borrowed mutably while it is still
+/// shared with another clone. The solver clones the layout for every branch
+/// of its search, and most of those clones only change the few cells around
+/// the collapsed one, so this can make deep searches on large layouts
+/// cheaper. On small layouts such as a sudoku the reference counting costs
+/// about as much as it saves. This works best with the candidate cache of the
+/// solver, since scanning all candidates borrows every uncollapsed cell
+/// mutably and copies it.
+///
+/// Coordinates and neighbors are the same as those of [Grid], and
+/// [CowGrid::from_grid] and [CowGrid::to_grid] convert between the two. The
+/// `cow_grid` benchmark compares both on a hard sudoku.
+#[derive(Clone)]
+pub struct CowGrid<V: CellValue> {
+    x: usize,
+    y: usize,
+    /// The cells in row major order.
+    cells: Vec<Rc<Cell<V>>>,
+    /// The cells borrowed mutably since the last call to
+    /// `take_changed_cells`, possibly with repeats.
+    changed: Vec<Coord2D>,
+    /// Set when too many cells changed to list or every cell was borrowed.
+    all_changed: bool,
+}
+
+impl<V: CellValue> CowGrid<V> {
+    /// Creates a new CowGrid with size (x, y)
+    ///
+    /// Initially filled with uncollapsed, but empty [Cell]s, which all share
+    /// the same allocation until they are changed.
+    pub fn new(x: usize, y: usize) -> Self {
+        let empty = Rc::new(Cell::Uncollapsed(HashMap::new()));
+        Self {
+            x,
+            y,
+            cells: vec![empty; x * y],
+            changed: Vec::new(),
+            all_changed: true,
+        }
+    }
+
+    /// Creates a new CowGrid with the same size and cells as the [Grid].
+    pub fn from_grid(grid: &Grid<V>) -> Self {
+        let mut cells = Vec::with_capacity(grid.x() * grid.y());
+        for y in 0..grid.y() {
+            for x in 0..grid.x() {
+                let cell = grid.get_cell(&Coord2D::new(x, y)).unwrap();
+                cells.push(Rc::new(cell.clone()));
+            }
+        }
+        Self {
+            x: grid.x(),
+            y: grid.y(),
+            cells,
+            changed: Vec::new(),
+            all_changed: true,
+        }
+    }
+
+    /// Copies the cells into a [Grid] of the same size.
+    pub fn to_grid(&self) -> Grid<V> {
+        let mut grid = Grid::new(self.x, self.y);
+        for (cell, shared) in grid.cells().map(|(_, cell)| cell).zip(&self.cells) {
+            *cell = (**shared).clone();
+        }
+        grid
+    }
+
+    /// The x size of this grid
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    /// The y size of this grid
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    /// Returns a [`Vec<Coord2D>`] that contains the coordinates for the 8
+    /// cells that neighbor the cell at `coord`, as [Grid::neighbors] does.
+    pub fn neighbors(&self, coord: Coord2D) -> Vec<Coord2D> {
+        coord.neighbors()
+    }
+
+    /// Returns true if the cell at the coordinate shares its allocation with
+    /// a clone of this grid.
+    pub fn is_shared(&self, coord: &Coord2D) -> bool {
+        self.index(coord)
+            .is_some_and(|index| Rc::strong_count(&self.cells[index]) > 1)
+    }
+
+    /// The index of the cell at the coordinate, if it is in bounds.
+    fn index(&self, coord: &Coord2D) -> Option<usize> {
+        (coord.x() < self.x && coord.y() < self.y).then(|| coord.y() * self.x + coord.x())
+    }
+}
+
+impl<V: CellValue> Layout<V> for CowGrid<V> {
+    type Coordinate = Coord2D;
+
+    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.changed.clear();
+        self.all_changed = true;
+        let x = self.x;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .map(move |(index, cell)| (Coord2D::new(index % x, index / x), Rc::make_mut(cell)))
+    }
+
+    fn cells_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
+    where
+        V: 'a,
+    {
+        let x = self.x;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(index, cell)| (Coord2D::new(index % x, index / x), cell.as_ref()))
+    }
+
+    // Collapsed cells are skipped without borrowing them, so they stay shared
+    fn candidates<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.changed.clear();
+        self.all_changed = true;
+        let x = self.x;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, cell)| !cell.is_collapsed())
+            .map(move |(index, cell)| (Coord2D::new(index % x, index / x), Rc::make_mut(cell)))
+    }
+
+    fn cell_count(&self) -> usize {
+        self.x * self.y
+    }
+
+    fn dimensions(&self) -> Vec<usize> {
+        vec![self.x, self.y]
+    }
+
+    fn neighbor_coords(&self, coord: &Self::Coordinate) -> Cow<'_, [Self::Coordinate]> {
+        Cow::Owned(self.neighbors(*coord))
+    }
+
+    fn get_cell(&self, coord: &Self::Coordinate) -> Option<&Cell<V>> {
+        let index = self.index(coord)?;
+        Some(&self.cells[index])
+    }
+
+    fn get_cell_mut(&mut self, coord: &Self::Coordinate) -> Option<&mut Cell<V>> {
+        let index = self.index(coord)?;
+        if !self.all_changed {
+            if self.changed.len() >= self.x * self.y {
+                self.changed.clear();
+                self.all_changed = true;
+            } else {
+                self.changed.push(*coord);
+            }
+        }
+        Some(Rc::make_mut(&mut self.cells[index]))
+    }
+
+    fn take_changed_cells(&mut self) -> Option<Vec<Self::Coordinate>> {
+        if self.all_changed {
+            self.all_changed = false;
+            self.changed.clear();
+            return None;
+        }
+        Some(std::mem::take(&mut self.changed))
+    }
+}
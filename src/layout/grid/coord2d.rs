@@ -23,6 +23,25 @@ pub enum Direction {
     DownRight,
 }
 
+impl Direction {
+    /// The direction pointing the opposite way.
+    ///
+    /// For example, the opposite of [Direction::UpLeft] is
+    /// [Direction::DownRight].
+    pub fn opposite(&self) -> Self {
+        match self {
+            Direction::UpLeft => Direction::DownRight,
+            Direction::Up => Direction::Down,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::Down => Direction::Up,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+}
+
 /// A two dimensional coordinate used by the Grid Layout.
 ///
 /// Coord2D uses the pixel-coordinate convention where positive y values extend
@@ -33,7 +52,7 @@ pub enum Direction {
 /// Additionally, the coordinates wrap when they reach the edges of the allowed
 /// range of values. using `.left()` on a coordinate with a 0 x component will
 /// result in a coordinate with an x component of `usize::MAX`
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Coord2D {
     x: usize,
     y: usize,
@@ -58,46 +77,42 @@ impl Coord2D {
     /// Returns a Vec of the coordinates of the eight immediately neighboring
     /// cells.
     pub fn neighbors(&self) -> Vec<Coord2D> {
-        let mut v = Vec::new();
-        v.push(self.up_left());
-        v.push(self.up());
-        v.push(self.up_right());
-
-        v.push(self.left());
-        v.push(self.right());
-
-        v.push(self.down_left());
-        v.push(self.down());
-        v.push(self.down_right());
-        v
+        vec![
+            self.up_left(),
+            self.up(),
+            self.up_right(),
+            self.left(),
+            self.right(),
+            self.down_left(),
+            self.down(),
+            self.down_right(),
+        ]
     }
 
     /// Returns a Vec of a tuple of the coordinates and the [Direction] of the
     /// eight immediately neighboring locations.
     pub fn neighbor_directions(&self) -> Vec<(Coord2D, Direction)> {
-        let mut v = Vec::new();
-        v.push((self.up_left(), Direction::UpLeft));
-        v.push((self.up(), Direction::Up));
-        v.push((self.up_right(), Direction::UpRight));
-
-        v.push((self.left(), Direction::Left));
-        v.push((self.right(), Direction::Right));
-
-        v.push((self.down_left(), Direction::DownLeft));
-        v.push((self.down(), Direction::Down));
-        v.push((self.down_right(), Direction::DownRight));
-        v
+        vec![
+            (self.up_left(), Direction::UpLeft),
+            (self.up(), Direction::Up),
+            (self.up_right(), Direction::UpRight),
+            (self.left(), Direction::Left),
+            (self.right(), Direction::Right),
+            (self.down_left(), Direction::DownLeft),
+            (self.down(), Direction::Down),
+            (self.down_right(), Direction::DownRight),
+        ]
     }
 
     /// Returns a Vec of a tuple of the coordinates and the [Direction] of the
     /// four orthogonal neighboring locations.
     pub fn neighbor_directions4(&self) -> Vec<(Coord2D, Direction)> {
-        let mut v = Vec::new();
-        v.push((self.up(), Direction::Up));
-        v.push((self.left(), Direction::Left));
-        v.push((self.right(), Direction::Right));
-        v.push((self.down(), Direction::Down));
-        v
+        vec![
+            (self.up(), Direction::Up),
+            (self.left(), Direction::Left),
+            (self.right(), Direction::Right),
+            (self.down(), Direction::Down),
+        ]
     }
 
     /// Returns the coordinate of the location in the given [Direction].
@@ -163,6 +178,14 @@ impl Coord2D {
         }
     }
 
+    /// The Chebyshev distance to the other coordinate, the larger of the
+    /// distances along each axis.
+    ///
+    /// Every cell returned by [Coord2D::neighbors] is at a distance of 1.
+    pub fn chebyshev_distance(&self, other: &Self) -> usize {
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+    }
+
     /// The Coord2D immediately above the current cell.
     ///
     /// Since the y axis extends downward, the coordinate will have a smaller y
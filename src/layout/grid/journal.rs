@@ -0,0 +1,85 @@
+use crate::{cell::Cell, CellValue};
+
+use super::GridCoord;
+
+// Saved cells with their coordinates.
+type Saved<V> = Vec<(GridCoord, Cell<V>)>;
+
+/// A checkpoint of a [Grid](super::Grid), see [Layout::checkpoint](crate::Layout::checkpoint).
+pub(super) struct Checkpoint {
+    id: u64,
+    /// The length of the journal when the checkpoint was made.
+    len: usize,
+    /// The change tracking state of the grid when the checkpoint was made.
+    pub changed: Vec<GridCoord>,
+    pub all_changed: bool,
+}
+
+/// The undo log of the checkpoints of a grid.
+///
+/// The first time a cell is borrowed mutably after a checkpoint, its previous
+/// state is saved, so restoring the checkpoint only copies back the cells
+/// that may have changed.
+pub(super) struct Journal<V: CellValue> {
+    /// The previous states of cells, in the order they were saved.
+    entries: Saved<V>,
+    checkpoints: Vec<Checkpoint>,
+    /// The id of the checkpoint each cell was last saved for, by index. Empty
+    /// until the first checkpoint.
+    saved: Vec<u64>,
+    next_id: u64,
+}
+
+impl<V: CellValue> Journal<V> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            checkpoints: Vec::new(),
+            saved: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Returns true if there is a checkpoint to save cells for.
+    pub fn is_active(&self) -> bool {
+        !self.checkpoints.is_empty()
+    }
+
+    /// Saves the state of a cell that is about to be borrowed mutably, unless
+    /// it was already saved since the most recent checkpoint.
+    pub fn save(&mut self, coord: GridCoord, index: usize, cell: &Cell<V>) {
+        let Some(checkpoint) = self.checkpoints.last() else {
+            return;
+        };
+        if self.saved[index] != checkpoint.id {
+            self.saved[index] = checkpoint.id;
+            self.entries.push((coord, cell.clone()));
+        }
+    }
+
+    /// Adds a checkpoint for a grid with the given number of cells and change
+    /// tracking state.
+    pub fn checkpoint(&mut self, cell_count: usize, changed: &[GridCoord], all_changed: bool) {
+        if self.saved.len() != cell_count {
+            self.saved = vec![0; cell_count];
+        }
+        // Ids start at 1, since 0 marks cells that were never saved
+        self.next_id += 1;
+        self.checkpoints.push(Checkpoint {
+            id: self.next_id,
+            len: self.entries.len(),
+            changed: changed.to_vec(),
+            all_changed,
+        });
+    }
+
+    /// Removes the most recent checkpoint, and returns it with the saved
+    /// states of the cells changed since, the most recently saved first.
+    pub fn restore(&mut self) -> Option<(Checkpoint, Saved<V>)> {
+        let checkpoint = self.checkpoints.pop()?;
+        let mut entries = self.entries.split_off(checkpoint.len);
+        entries.reverse();
+        Some((checkpoint, entries))
+    }
+}
+
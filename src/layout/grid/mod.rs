@@ -1,37 +1,128 @@
 mod coord2d;
 pub use coord2d::{Coord2D, Direction};
 
+mod journal;
+use journal::Journal;
+
 use std::{
     array,
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
+    sync::Arc,
 };
 
-use crate::{cell::Cell, CellValue, Tile2D};
+use crate::{cell::{Cell, Function, Operation}, CellValue, Tile2D};
 
 use super::Layout;
 
+// The lists of coordinates precomputed by `with_cached_neighbors`. The
+// neighbors are in row major order.
+struct CoordCache {
+    neighbors: Vec<Vec<GridCoord>>,
+    rows: Vec<Vec<GridCoord>>,
+    cols: Vec<Vec<GridCoord>>,
+}
+
 // GridCoord is used as a common type by Grid for both its own functions as well
 // as the associated type for its implementation of Layout.
 type GridCoord = Coord2D;
 
 /// A two dimensional grid [Layout] for use by implementors of the trait
 /// Wavefunction.
-#[derive(Clone)]
 pub struct Grid<V: CellValue> {
     x: usize,
     y: usize,
     /// The outer Vec is a Vec of rows. The inner vec is the cell within the row.
     cells: Vec<Vec<Cell<V>>>,
+    /// Precomputed results of `neighbors`, `row` and `col`, shared between
+    /// clones of the grid.
+    coord_cache: Option<Arc<CoordCache>>,
+    /// The cells borrowed mutably since the last call to
+    /// `take_changed_cells`, possibly with repeats.
+    changed: Vec<GridCoord>,
+    /// Set when too many cells changed to list or every cell was borrowed.
+    all_changed: bool,
+    /// The previous states of the cells changed since each checkpoint, boxed
+    /// to keep the grid small on the solver's stack.
+    journal: Option<Box<Journal<V>>>,
 }
 
 impl<V: CellValue> Grid<V> {
     /// Creates a new Grid with size (x, y)
     ///
-    /// Initially filled with uncollapsed, but empty [Cell]s.
+    /// Initially filled with uncollapsed, but empty [Cell]s. If either size is
+    /// zero, the grid has no cells, which the [Solver](crate::Solver) treats
+    /// as already solved.
     pub fn new(x: usize, y: usize) -> Self {
         let cells = vec![vec![Cell::Uncollapsed(HashMap::new()); x]; y];
-        Self { x, y, cells }
+        Self {
+            x,
+            y,
+            cells,
+            coord_cache: None,
+            changed: Vec::new(),
+            all_changed: true,
+            journal: None,
+        }
+    }
+
+    /// The largest number of cells that [Grid::try_new] allows.
+    pub const MAX_CELLS: usize = 1 << 24;
+
+    /// Creates a new Grid with size (x, y), like [Grid::new], or returns an
+    /// error if the grid would have more than [Grid::MAX_CELLS] cells.
+    ///
+    /// [Grid::new] panics or runs out of memory when given enormous sizes, so
+    /// this should be used when the size comes from untrusted input. Use
+    /// [Grid::try_with_max_cells] to choose a different limit.
+    pub fn try_new(x: usize, y: usize) -> Result<Self, GridError> {
+        Self::try_with_max_cells(x, y, Self::MAX_CELLS)
+    }
+
+    /// Creates a new Grid with size (x, y), or returns an error if the grid
+    /// would have more than `max_cells` cells.
+    ///
+    /// Each axis is also limited to `max_cells` on its own, since a grid with
+    /// an empty axis still allocates the other one.
+    pub fn try_with_max_cells(x: usize, y: usize, max_cells: usize) -> Result<Self, GridError> {
+        match x.checked_mul(y) {
+            None => Err(GridError::Overflow { x, y }),
+            Some(cells) if cells.max(x).max(y) > max_cells => {
+                Err(GridError::TooManyCells { x, y, max_cells })
+            }
+            Some(_) => Ok(Self::new(x, y)),
+        }
+    }
+
+    /// Precomputes the neighbors of every cell, and the coordinates of every
+    /// row and column, so that they no longer have to be calculated on each
+    /// call.
+    ///
+    /// The lists only depend on the dimensions of the grid. They are stored
+    /// once and shared between clones of the grid, so the solver's cloning is
+    /// not made more expensive. The cache holds eight neighbor coordinates for
+    /// every cell, and every cell once in its row and once in its column,
+    /// which is 160 bytes per cell on 64 bit platforms and significant for
+    /// very large grids.
+    ///
+    /// [Grid::neighbors_ref], [Grid::row_ref] and [Grid::col_ref] borrow the
+    /// cached lists without allocating, as does [Layout::neighbor_coords],
+    /// which the solver uses. [Grid::neighbors], [Grid::row] and [Grid::col]
+    /// still have to copy the lists into a new Vec, which is only needed to
+    /// keep them while modifying the grid. The `neighbors` benchmark compares
+    /// these.
+    pub fn with_cached_neighbors(mut self) -> Self {
+        let mut neighbors = Vec::with_capacity(self.x * self.y);
+        for y in 0..self.y {
+            for x in 0..self.x {
+                neighbors.push(Self::compute_neighbors(GridCoord::new(x, y)));
+            }
+        }
+        let rows = (0..self.y).map(|y| self.compute_row(y)).collect();
+        let cols = (0..self.x).map(|x| self.compute_col(x)).collect();
+        self.coord_cache = Some(Arc::new(CoordCache { neighbors, rows, cols }));
+        self
     }
 
     /// The x size of this grid
@@ -44,9 +135,39 @@ impl<V: CellValue> Grid<V> {
         self.y
     }
 
+    /// Returns the row major index of the coordinate, as used by a flat
+    /// buffer of the grid's cells, or None if it is out of bounds.
+    pub fn coord_to_index(&self, coord: GridCoord) -> Option<usize> {
+        (coord.x() < self.x && coord.y() < self.y).then(|| coord.y() * self.x + coord.x())
+    }
+
+    /// Returns the coordinate of the row major index, or None if the index is
+    /// not less than the number of cells. The inverse of
+    /// [Grid::coord_to_index].
+    pub fn index_to_coord(&self, index: usize) -> Option<GridCoord> {
+        (index < self.x * self.y).then(|| GridCoord::new(index % self.x, index / self.x))
+    }
+
     /// Returns a [`Vec<Coord2D>`] that contains the coordinates for every cell in
     /// the row at position `y`
+    ///
+    /// If the grid was created using [Grid::with_cached_neighbors], the result
+    /// is copied from the cache for rows within the grid.
     pub fn row(&self, y: usize) -> Vec<GridCoord> {
+        self.row_ref(y).into_owned()
+    }
+
+    /// Returns the coordinates for every cell in the row at position `y`,
+    /// borrowed from the cache of [Grid::with_cached_neighbors] if the row is
+    /// cached.
+    pub fn row_ref(&self, y: usize) -> Cow<'_, [GridCoord]> {
+        match self.coord_cache.as_ref().and_then(|cache| cache.rows.get(y)) {
+            Some(row) => Cow::Borrowed(row),
+            None => Cow::Owned(self.compute_row(y)),
+        }
+    }
+
+    fn compute_row(&self, y: usize) -> Vec<GridCoord> {
         let mut v = Vec::new();
         for x in 0..self.x {
             v.push(GridCoord::new(x, y));
@@ -56,7 +177,24 @@ impl<V: CellValue> Grid<V> {
 
     /// Returns a [`Vec<Coord2D>`] that contains the coordinates for every cell in
     /// the column at position `x`
+    ///
+    /// If the grid was created using [Grid::with_cached_neighbors], the result
+    /// is copied from the cache for columns within the grid.
     pub fn col(&self, x: usize) -> Vec<GridCoord> {
+        self.col_ref(x).into_owned()
+    }
+
+    /// Returns the coordinates for every cell in the column at position `x`,
+    /// borrowed from the cache of [Grid::with_cached_neighbors] if the column
+    /// is cached.
+    pub fn col_ref(&self, x: usize) -> Cow<'_, [GridCoord]> {
+        match self.coord_cache.as_ref().and_then(|cache| cache.cols.get(x)) {
+            Some(col) => Cow::Borrowed(col),
+            None => Cow::Owned(self.compute_col(x)),
+        }
+    }
+
+    fn compute_col(&self, x: usize) -> Vec<GridCoord> {
         let mut v = Vec::new();
         for y in 0..self.y {
             v.push(GridCoord::new(x, y));
@@ -64,24 +202,112 @@ impl<V: CellValue> Grid<V> {
         v
     }
 
+    /// Returns a [`Vec<Coord2D>`] that contains the coordinates for every cell on
+    /// the edges of the grid, each listed once.
+    pub fn border(&self) -> Vec<GridCoord> {
+        let mut v = Vec::new();
+        for y in 0..self.y {
+            for x in 0..self.x {
+                if x == 0 || y == 0 || x == self.x - 1 || y == self.y - 1 {
+                    v.push(GridCoord::new(x, y));
+                }
+            }
+        }
+        v
+    }
+
+    /// Collapses every cell on the edges of the grid to the given value.
+    ///
+    /// Like [Layout::collapse], this does not enforce any rules of a
+    /// wavefunction. Use [Solver::collapse_border](crate::Solver::collapse_border)
+    /// to also propagate the collapse.
+    pub fn set_border(&mut self, value: V) {
+        for coord in self.border() {
+            self.collapse(&coord, value);
+        }
+    }
+
+    /// Collapses each cell to the value that `pattern` returns for its
+    /// coordinate, leaving the cell untouched where it returns None.
+    ///
+    /// This makes it simple to set up checkerboards, stripes, or gradients as
+    /// initial conditions. Like [Grid::set_border], it does not enforce any
+    /// rules of a wavefunction.
+    pub fn collapse_pattern(&mut self, pattern: impl Fn(GridCoord) -> Option<V>) {
+        for y in 0..self.y {
+            for x in 0..self.x {
+                let coord = GridCoord::new(x, y);
+                if let Some(value) = pattern(coord) {
+                    self.collapse(&coord, value);
+                }
+            }
+        }
+    }
+
+    /// Reduces the weight of `value` in the cells surrounding `center`, by less
+    /// the further away each cell is.
+    ///
+    /// Every cell within `radius` of the center, by
+    /// [Coord2D::chebyshev_distance], has the weight of the value reduced by
+    /// `strength / (1 + distance)` using [Layout::remove_cell_possibility_count].
+    /// The center itself is included at a distance of 0. As with that method,
+    /// the value is removed from any cell where its weight reaches zero.
+    pub fn apply_falloff(&mut self, center: GridCoord, value: V, strength: usize, radius: usize) {
+        let min_x = center.x().saturating_sub(radius);
+        let min_y = center.y().saturating_sub(radius);
+        let max_x = center.x().saturating_add(radius).min(self.x.saturating_sub(1));
+        let max_y = center.y().saturating_add(radius).min(self.y.saturating_sub(1));
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let coord = GridCoord::new(x, y);
+                let distance = center.chebyshev_distance(&coord);
+                self.remove_cell_possibility_count(&coord, &value, strength / (1 + distance));
+            }
+        }
+    }
+
     /// Returns a [`Vec<Coord2D>`] that contains the coordinates for the 8 cells
     /// that directly neighbor the cell at `coord`.
+    ///
+    /// If the grid was created using [Grid::with_cached_neighbors], the result
+    /// is copied from the cache for coordinates within the grid.
     pub fn neighbors(&self, coord: GridCoord) -> Vec<GridCoord> {
-        let mut v = Vec::new();
-        v.push(GridCoord::new(
-            coord.x().wrapping_sub(1),
-            coord.y().wrapping_sub(1),
-        ));
-        v.push(GridCoord::new(coord.x(), coord.y().wrapping_sub(1)));
-        v.push(GridCoord::new(coord.x() + 1, coord.y().wrapping_sub(1)));
-
-        v.push(GridCoord::new(coord.x().wrapping_sub(1), coord.y()));
-        v.push(GridCoord::new(coord.x() + 1, coord.y()));
-
-        v.push(GridCoord::new(coord.x().wrapping_sub(1), coord.y() + 1));
-        v.push(GridCoord::new(coord.x(), coord.y() + 1));
-        v.push(GridCoord::new(coord.x() + 1, coord.y() + 1));
-        v
+        self.neighbors_ref(coord).into_owned()
+    }
+
+    /// Returns the coordinates for the 8 cells that directly neighbor the cell
+    /// at `coord`, borrowed from the cache of [Grid::with_cached_neighbors] if
+    /// the coordinate is cached.
+    pub fn neighbors_ref(&self, coord: GridCoord) -> Cow<'_, [GridCoord]> {
+        match self.cached_neighbors(coord) {
+            Some(neighbors) => Cow::Borrowed(neighbors),
+            None => Cow::Owned(Self::compute_neighbors(coord)),
+        }
+    }
+
+    /// Borrows the cached neighbors of the cell at `coord`.
+    ///
+    /// Returns None if the grid does not cache neighbors or the coordinate is
+    /// out of bounds.
+    pub fn cached_neighbors(&self, coord: GridCoord) -> Option<&[GridCoord]> {
+        if coord.x() >= self.x || coord.y() >= self.y {
+            return None;
+        }
+        let cache = self.coord_cache.as_ref()?;
+        Some(&cache.neighbors[coord.y() * self.x + coord.x()])
+    }
+
+    fn compute_neighbors(coord: GridCoord) -> Vec<GridCoord> {
+        vec![
+            GridCoord::new(coord.x().wrapping_sub(1), coord.y().wrapping_sub(1)),
+            GridCoord::new(coord.x(), coord.y().wrapping_sub(1)),
+            GridCoord::new(coord.x() + 1, coord.y().wrapping_sub(1)),
+            GridCoord::new(coord.x().wrapping_sub(1), coord.y()),
+            GridCoord::new(coord.x() + 1, coord.y()),
+            GridCoord::new(coord.x().wrapping_sub(1), coord.y() + 1),
+            GridCoord::new(coord.x(), coord.y() + 1),
+            GridCoord::new(coord.x() + 1, coord.y() + 1),
+        ]
     }
 
     /// Extracts a [Tile2D] from the Layout.
@@ -124,6 +350,115 @@ impl<V: CellValue> Grid<V> {
         }));
         Some(tile)
     }
+
+    /// Iterates over every [Tile2D] that can be extracted from the grid,
+    /// yielding each with the coordinate of its upper left corner.
+    ///
+    /// Tiles overlap, a tile is tried at every coordinate in row major order.
+    /// Coordinates where [Grid::get_tile] returns None are skipped.
+    pub fn tiles<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+    ) -> impl '_ + Iterator<Item = (GridCoord, Tile2D<V, WIDTH, HEIGHT>)> {
+        (0..self.y)
+            .flat_map(move |y| (0..self.x).map(move |x| GridCoord::new(x, y)))
+            .filter_map(|coord| self.get_tile(coord).map(|tile| (coord, tile)))
+    }
+
+    /// Merges the cells of another grid into the cells of this one, for
+    /// example to layer a detail pass over a base terrain.
+    ///
+    /// Each cell is merged with the cell at the same coordinate of `other`
+    /// using [Cell::merge_with]. When both cells are uncollapsed, this is
+    /// [Cell::merge_cell_possibilities] with the given operation and function.
+    /// A collapsed cell in either grid is treated as certain, and a collapsed
+    /// value that the other cell does not allow leaves the cell without any
+    /// possibilities. If the grids differ in size, only the cells that are in
+    /// both are merged, and the rest of this grid is left unchanged.
+    pub fn merge_from(&mut self, other: &Grid<V>, op: Operation, func: Function) {
+        for y in 0..self.y.min(other.y) {
+            for x in 0..self.x.min(other.x) {
+                let coord = GridCoord::new(x, y);
+                if let Some(cell) = self.get_cell_mut(&coord) {
+                    cell.merge_with(&other.cells[y][x], op, func);
+                }
+            }
+        }
+    }
+
+    /// Splits the grid into a layer for each value, marking the cells that
+    /// could still be that value.
+    ///
+    /// Every value that is a possibility of an uncollapsed cell, or the value
+    /// of a collapsed cell, has a layer of the same size as this grid. Each
+    /// cell of a layer is collapsed to true if the value is still possible in
+    /// that cell, and false otherwise. This is useful for visualizing how the
+    /// constraints narrow the options across the grid.
+    pub fn possibility_layers(&self) -> HashMap<V, Grid<bool>> {
+        let mut layers = HashMap::new();
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                let values: Vec<V> = match cell {
+                    Cell::Collapsed(value) => vec![*value],
+                    Cell::Uncollapsed(possibilities) => possibilities.keys().copied().collect(),
+                };
+                for value in values {
+                    let layer = layers.entry(value).or_insert_with(|| {
+                        let mut layer = Grid::new(self.x, self.y);
+                        layer.collapse_pattern(|_| Some(false));
+                        layer
+                    });
+                    layer.collapse(&GridCoord::new(x, y), true);
+                }
+            }
+        }
+        layers
+    }
+
+    /// Segments the collapsed cells into maximal regions of the same value,
+    /// where cells are connected through their four orthogonal neighbors.
+    ///
+    /// Each region is returned with its value, in the order of its first cell
+    /// going row by row from the top left. Uncollapsed cells do not belong to
+    /// any region. This can identify the distinct islands or rooms of a
+    /// generated map.
+    pub fn connected_regions(&self) -> Vec<(V, HashSet<GridCoord>)> {
+        let mut regions = Vec::new();
+        let mut visited = HashSet::new();
+        for y in 0..self.y {
+            for x in 0..self.x {
+                let start = GridCoord::new(x, y);
+                let Some(value) = self.cells[y][x].get_value() else {
+                    continue;
+                };
+                if visited.contains(&start) {
+                    continue;
+                }
+                let region = self.flood_fill(start, |cell| cell.get_value() == Some(value));
+                visited.extend(region.iter().copied());
+                regions.push((value, region));
+            }
+        }
+        regions
+    }
+
+    /// Collects the cells connected to `start` through orthogonal neighbors
+    /// whose cells all satisfy `predicate`, including `start` itself.
+    fn flood_fill(&self, start: GridCoord, predicate: impl Fn(&Cell<V>) -> bool) -> HashSet<GridCoord> {
+        let mut region = HashSet::from([start]);
+        let mut frontier = vec![start];
+        while let Some(coord) = frontier.pop() {
+            for (neighbor, _) in coord.neighbor_directions4() {
+                if region.contains(&neighbor) {
+                    continue;
+                }
+                if self.get_cell(&neighbor).is_some_and(&predicate) {
+                    region.insert(neighbor);
+                    frontier.push(neighbor);
+                }
+            }
+        }
+        region
+    }
 }
 
 impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Grid<Tile2D<V, WIDTH, HEIGHT>> {
@@ -138,7 +473,7 @@ impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Grid<Tile2D<V, WIDTH
                             coord.x() * WIDTH + tile_x,
                             coord.y() * HEIGHT + tile_y
                         );
-                        grid.collapse(&tile_coord, value.clone());
+                        grid.collapse(&tile_coord, *value);
                     }
                 }
                 Cell::Uncollapsed(possibilities) => {
@@ -167,13 +502,41 @@ impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Grid<Tile2D<V, WIDTH
     }
 }
 
+impl<V: CellValue + Display, const WIDTH: usize, const HEIGHT: usize> Grid<Tile2D<V, WIDTH, HEIGHT>> {
+    /// Renders the values of the grid, as the [Display] of the grid returned
+    /// by [Grid::detile].
+    ///
+    /// The grid is detiled on a copy, so it is not changed.
+    pub fn display_detiled(&self) -> String {
+        self.clone().detile().to_string()
+    }
+}
+
+// Clones start without the checkpoints of the original
+impl<V: CellValue> Clone for Grid<V> {
+    fn clone(&self) -> Self {
+        Self {
+            x: self.x,
+            y: self.y,
+            cells: self.cells.clone(),
+            coord_cache: self.coord_cache.clone(),
+            changed: self.changed.clone(),
+            all_changed: self.all_changed,
+            journal: None,
+        }
+    }
+}
+
 impl<V: CellValue> Layout<V> for Grid<V> {
     type Coordinate = GridCoord;
 
-    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &mut Cell<V>)>
+    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
     where
         V: 'a,
     {
+        self.save_cells(|_| true);
+        self.changed.clear();
+        self.all_changed = true;
         self.cells.iter_mut().enumerate().flat_map(|(y, row)| {
             row.iter_mut()
                 .enumerate()
@@ -181,10 +544,46 @@ impl<V: CellValue> Layout<V> for Grid<V> {
         })
     }
 
+    fn cells_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.cells.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, cell)| (Coord2D::new(x, y), cell))
+        })
+    }
+
+    // Collapsed cells are skipped without borrowing them, so a checkpoint
+    // does not have to save them
+    fn candidates<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.save_cells(|cell| !cell.is_collapsed());
+        self.changed.clear();
+        self.all_changed = true;
+        self.cells.iter_mut().enumerate().flat_map(|(y, row)| {
+            row.iter_mut()
+                .enumerate()
+                .filter(|(_, cell)| !cell.is_collapsed())
+                .map(move |(x, cell)| (Coord2D::new(x, y), cell))
+        })
+    }
+
     fn cell_count(&self) -> usize {
         self.x * self.y
     }
 
+    fn dimensions(&self) -> Vec<usize> {
+        vec![self.x, self.y]
+    }
+
+    fn neighbor_coords(&self, coord: &Self::Coordinate) -> Cow<'_, [Self::Coordinate]> {
+        self.neighbors_ref(*coord)
+    }
+
     fn get_cell(&self, coord: &Self::Coordinate) -> Option<&Cell<V>> {
         if let Some(row) = self.cells.get(coord.y()) {
             if let Some(cell) = row.get(coord.x()) {
@@ -195,13 +594,70 @@ impl<V: CellValue> Layout<V> for Grid<V> {
     }
 
     fn get_cell_mut(&mut self, coord: &Self::Coordinate) -> Option<&mut Cell<V>> {
+        if let Some(journal) = self.journal.as_mut().filter(|journal| journal.is_active()) {
+            if coord.x() < self.x && coord.y() < self.y {
+                let index = coord.y() * self.x + coord.x();
+                journal.save(*coord, index, &self.cells[coord.y()][coord.x()]);
+            }
+        }
         if let Some(row) = self.cells.get_mut(coord.y()) {
             if let Some(cell) = row.get_mut(coord.x()) {
+                if !self.all_changed {
+                    if self.changed.len() >= self.x * self.y {
+                        self.changed.clear();
+                        self.all_changed = true;
+                    } else {
+                        self.changed.push(*coord);
+                    }
+                }
                 return Some(cell);
             }
         }
         None
     }
+
+    fn take_changed_cells(&mut self) -> Option<Vec<Self::Coordinate>> {
+        if self.all_changed {
+            self.all_changed = false;
+            self.changed.clear();
+            return None;
+        }
+        Some(std::mem::take(&mut self.changed))
+    }
+
+    fn checkpoint(&mut self) -> bool {
+        let journal = self.journal.get_or_insert_with(|| Box::new(Journal::new()));
+        journal.checkpoint(self.x * self.y, &self.changed, self.all_changed);
+        true
+    }
+
+    fn restore(&mut self) {
+        let Some((checkpoint, saved)) = self.journal.as_mut().and_then(|journal| journal.restore()) else {
+            return;
+        };
+        for (coord, cell) in saved {
+            self.cells[coord.y()][coord.x()] = cell;
+        }
+        self.changed = checkpoint.changed;
+        self.all_changed = checkpoint.all_changed;
+    }
+}
+
+impl<V: CellValue> Grid<V> {
+    /// Saves the cells that match the filter to the journal, before they are
+    /// all borrowed mutably.
+    fn save_cells(&mut self, filter: impl Fn(&Cell<V>) -> bool) {
+        let Some(journal) = self.journal.as_mut().filter(|journal| journal.is_active()) else {
+            return;
+        };
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if filter(cell) {
+                    journal.save(Coord2D::new(x, y), y * self.x + x, cell);
+                }
+            }
+        }
+    }
 }
 
 impl<V: CellValue + Debug> Debug for Grid<V> {
@@ -214,12 +670,48 @@ impl<V: CellValue + Debug> Debug for Grid<V> {
                     write!(f, ", ")?;
                 }
             }
-            writeln!(f, "")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
+/// The reasons that [Grid::try_new] can refuse to create a grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridError {
+    /// The number of cells does not fit in a usize.
+    Overflow {
+        /// The requested x size
+        x: usize,
+        /// The requested y size
+        y: usize,
+    },
+    /// The grid would have more cells than the limit.
+    TooManyCells {
+        /// The requested x size
+        x: usize,
+        /// The requested y size
+        y: usize,
+        /// The largest number of cells allowed
+        max_cells: usize,
+    },
+}
+
+impl Display for GridError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridError::Overflow { x, y } => {
+                write!(f, "a {x}x{y} grid has more cells than fit in a usize")
+            }
+            GridError::TooManyCells { x, y, max_cells } => {
+                write!(f, "a {x}x{y} grid exceeds the limit of {max_cells} cells")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
 impl<V: CellValue + Display> Display for Grid<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in &self.cells {
@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+/// An N dimensional coordinate used by the GridND Layout.
+///
+/// Like [Coord2D](crate::Coord2D), the components wrap when they reach the
+/// edges of the allowed range of values, so the neighbor of a coordinate with
+/// a 0 component may have a component of `usize::MAX`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoordND<const D: usize> {
+    coords: [usize; D],
+}
+
+impl<const D: usize> CoordND<D> {
+    /// Create a CoordND with the given components
+    pub fn new(coords: [usize; D]) -> Self {
+        Self { coords }
+    }
+
+    /// The components of this CoordND
+    pub fn coords(&self) -> [usize; D] {
+        self.coords
+    }
+
+    /// The component of this CoordND along the given axis.
+    ///
+    /// Panics if the axis is not less than D.
+    pub fn get(&self, axis: usize) -> usize {
+        self.coords[axis]
+    }
+
+    /// Returns the coordinate offset by the given signed amount along one
+    /// axis.
+    pub fn offset(&self, axis: usize, amount: isize) -> Self {
+        let mut coords = self.coords;
+        coords[axis] = coords[axis].wrapping_add_signed(amount);
+        Self { coords }
+    }
+
+    /// Returns a Vec of the coordinates of the `2 * D` orthogonally
+    /// neighboring locations.
+    pub fn orthogonal_neighbors(&self) -> Vec<Self> {
+        let mut v = Vec::with_capacity(2 * D);
+        for axis in 0..D {
+            v.push(self.offset(axis, -1));
+            v.push(self.offset(axis, 1));
+        }
+        v
+    }
+
+    /// Returns a Vec of the coordinates of the `3^D - 1` immediately
+    /// neighboring locations, including diagonals.
+    pub fn neighbors(&self) -> Vec<Self> {
+        let count = 3usize.pow(D as u32);
+        let mut v = Vec::with_capacity(count - 1);
+        for index in 0..count {
+            // Each base 3 digit of the index is an offset of -1, 0, or 1
+            let mut coords = self.coords;
+            let mut remaining = index;
+            for coord in coords.iter_mut() {
+                *coord = coord.wrapping_add_signed((remaining % 3) as isize - 1);
+                remaining /= 3;
+            }
+            if coords != self.coords {
+                v.push(Self { coords });
+            }
+        }
+        v
+    }
+}
+
+/// A three dimensional coordinate used by the [Grid3D](crate::Grid3D) Layout.
+pub type Coord3D = CoordND<3>;
+
+/// Helpers for three dimensions, named like those of [Coord2D](crate::Coord2D)
+/// with `forward` and `backward` for the z axis.
+impl CoordND<3> {
+    /// The x value of this Coord3D
+    pub fn x(&self) -> usize {
+        self.coords[0]
+    }
+
+    /// The y value of this Coord3D
+    pub fn y(&self) -> usize {
+        self.coords[1]
+    }
+
+    /// The z value of this Coord3D
+    pub fn z(&self) -> usize {
+        self.coords[2]
+    }
+
+    /// The coordinate one less along the y axis
+    pub fn up(&self) -> Self {
+        self.offset(1, -1)
+    }
+
+    /// The coordinate one more along the y axis
+    pub fn down(&self) -> Self {
+        self.offset(1, 1)
+    }
+
+    /// The coordinate one less along the x axis
+    pub fn left(&self) -> Self {
+        self.offset(0, -1)
+    }
+
+    /// The coordinate one more along the x axis
+    pub fn right(&self) -> Self {
+        self.offset(0, 1)
+    }
+
+    /// The coordinate one less along the z axis
+    pub fn backward(&self) -> Self {
+        self.offset(2, -1)
+    }
+
+    /// The coordinate one more along the z axis
+    pub fn forward(&self) -> Self {
+        self.offset(2, 1)
+    }
+}
+
+impl<const D: usize> Display for CoordND<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let coords: Vec<_> = self.coords.iter().map(|c| c.to_string()).collect();
+        write!(f, "({})", coords.join(", "))
+    }
+}
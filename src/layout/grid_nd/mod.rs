@@ -0,0 +1,169 @@
+mod coord_nd;
+pub use coord_nd::{Coord3D, CoordND};
+
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{cell::Cell, CellValue};
+
+use super::Layout;
+
+/// An N dimensional grid [Layout] for use by implementors of the trait
+/// Wavefunction.
+///
+/// This allows one, three, or more dimensional layouts to be generated with a
+/// single type. For two dimensions, [Grid](crate::Grid) offers more
+/// conveniences.
+#[derive(Clone)]
+pub struct GridND<V: CellValue, const D: usize> {
+    dimensions: [usize; D],
+    /// The cells in order of their index, where the first axis changes
+    /// fastest.
+    cells: Vec<Cell<V>>,
+    /// The cells borrowed mutably since the last call to
+    /// `take_changed_cells`, possibly with repeats.
+    changed: Vec<CoordND<D>>,
+    /// Set when too many cells changed to list or every cell was borrowed.
+    all_changed: bool,
+}
+
+impl<V: CellValue, const D: usize> GridND<V, D> {
+    /// Creates a new GridND with the size of each axis given by `dimensions`
+    ///
+    /// Initially filled with uncollapsed, but empty [Cell]s.
+    pub fn new(dimensions: [usize; D]) -> Self {
+        let count = dimensions.iter().product();
+        let cells = vec![Cell::Uncollapsed(HashMap::new()); count];
+        Self {
+            dimensions,
+            cells,
+            changed: Vec::new(),
+            all_changed: true,
+        }
+    }
+
+    /// The size of each axis of this grid
+    pub fn dimensions(&self) -> [usize; D] {
+        self.dimensions
+    }
+
+    /// Returns a [`Vec<CoordND>`] that contains the coordinates for the
+    /// `3^D - 1` cells that directly neighbor the cell at `coord`.
+    pub fn neighbors(&self, coord: CoordND<D>) -> Vec<CoordND<D>> {
+        coord.neighbors()
+    }
+
+    /// Returns a [`Vec<CoordND>`] that contains the coordinates for the `2 * D`
+    /// cells that orthogonally neighbor the cell at `coord`.
+    pub fn orthogonal_neighbors(&self, coord: CoordND<D>) -> Vec<CoordND<D>> {
+        coord.orthogonal_neighbors()
+    }
+
+    /// The index of the cell at the coordinate, if it is in bounds.
+    fn index(&self, coord: &CoordND<D>) -> Option<usize> {
+        let mut index = 0;
+        let mut stride = 1;
+        for (value, size) in coord.coords().iter().zip(self.dimensions) {
+            if *value >= size {
+                return None;
+            }
+            index += value * stride;
+            stride *= size;
+        }
+        Some(index)
+    }
+
+    /// The coordinate of the cell at the index.
+    fn coord(dimensions: &[usize; D], mut index: usize) -> CoordND<D> {
+        let mut coords = [0; D];
+        for (coord, size) in coords.iter_mut().zip(dimensions) {
+            *coord = index % size;
+            index /= size;
+        }
+        CoordND::new(coords)
+    }
+}
+
+/// A three dimensional grid [Layout], such as for voxel structures.
+pub type Grid3D<V> = GridND<V, 3>;
+
+impl<V: CellValue> GridND<V, 3> {
+    /// Returns a [`Vec<Coord3D>`] that contains the coordinates for every cell
+    /// in the layer at position `z`, row by row.
+    pub fn layer(&self, z: usize) -> Vec<Coord3D> {
+        let [x_size, y_size, _] = self.dimensions;
+        let mut v = Vec::new();
+        for y in 0..y_size {
+            for x in 0..x_size {
+                v.push(Coord3D::new([x, y, z]));
+            }
+        }
+        v
+    }
+}
+
+impl<V: CellValue, const D: usize> Layout<V> for GridND<V, D> {
+    type Coordinate = CoordND<D>;
+
+    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.changed.clear();
+        self.all_changed = true;
+        let dimensions = self.dimensions;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .map(move |(index, cell)| (Self::coord(&dimensions, index), cell))
+    }
+
+    fn cells_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
+    where
+        V: 'a,
+    {
+        let dimensions = self.dimensions;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(index, cell)| (Self::coord(&dimensions, index), cell))
+    }
+
+    fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn dimensions(&self) -> Vec<usize> {
+        self.dimensions.to_vec()
+    }
+
+    fn neighbor_coords(&self, coord: &Self::Coordinate) -> Cow<'_, [Self::Coordinate]> {
+        Cow::Owned(self.neighbors(*coord))
+    }
+
+    fn get_cell(&self, coord: &Self::Coordinate) -> Option<&Cell<V>> {
+        let index = self.index(coord)?;
+        self.cells.get(index)
+    }
+
+    fn get_cell_mut(&mut self, coord: &Self::Coordinate) -> Option<&mut Cell<V>> {
+        let index = self.index(coord)?;
+        if !self.all_changed {
+            if self.changed.len() >= self.cells.len() {
+                self.changed.clear();
+                self.all_changed = true;
+            } else {
+                self.changed.push(*coord);
+            }
+        }
+        self.cells.get_mut(index)
+    }
+
+    fn take_changed_cells(&mut self) -> Option<Vec<Self::Coordinate>> {
+        if self.all_changed {
+            self.all_changed = false;
+            self.changed.clear();
+            return None;
+        }
+        Some(std::mem::take(&mut self.changed))
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt::Display;
+
+/// An axial coordinate used by the HexGrid Layout.
+///
+/// The hexes are pointy topped. The `q` axis runs to the right, and the `r`
+/// axis runs down and to the right, so each row of hexes has the same `r`.
+/// The third cube coordinate, [HexCoord::s], is `-q - r`. Unlike
+/// [Coord2D](crate::Coord2D), the components are signed, and the center of a
+/// [HexGrid](crate::HexGrid) is at (0, 0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HexCoord {
+    q: isize,
+    r: isize,
+}
+
+impl HexCoord {
+    /// The offsets to the six neighbors, counterclockwise from the right.
+    const DIRECTIONS: [(isize, isize); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+    /// Create a HexCoord with the given axial components
+    pub fn new(q: isize, r: isize) -> Self {
+        Self { q, r }
+    }
+
+    /// The q component of this HexCoord
+    pub fn q(&self) -> isize {
+        self.q
+    }
+
+    /// The r component of this HexCoord
+    pub fn r(&self) -> isize {
+        self.r
+    }
+
+    /// The third cube component of this HexCoord, so that `q + r + s` is 0
+    pub fn s(&self) -> isize {
+        -self.q - self.r
+    }
+
+    /// Returns a Vec of the coordinates of the 6 neighboring hexes,
+    /// counterclockwise starting from the one to the right.
+    pub fn neighbors(&self) -> Vec<Self> {
+        Self::DIRECTIONS
+            .iter()
+            .map(|(q, r)| Self::new(self.q + q, self.r + r))
+            .collect()
+    }
+
+    /// The number of steps between neighboring hexes needed to get from this
+    /// coordinate to the other.
+    pub fn distance(&self, other: &Self) -> usize {
+        let (q, r, s) = (self.q - other.q, self.r - other.r, self.s() - other.s());
+        q.unsigned_abs().max(r.unsigned_abs()).max(s.unsigned_abs())
+    }
+}
+
+impl Display for HexCoord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.q, self.r)
+    }
+}
@@ -0,0 +1,144 @@
+mod hex_coord;
+pub use hex_coord::HexCoord;
+
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{cell::Cell, CellValue};
+
+use super::Layout;
+
+/// A hexagon shaped [Layout] of hexagonal cells, for use by implementors of
+/// the trait Wavefunction.
+///
+/// The grid holds every [HexCoord] within `radius` steps of the center at
+/// (0, 0), so a radius of 0 is a single hex and each larger radius adds a
+/// ring of `6 * radius` hexes. Each cell has six neighbors, see
+/// [HexCoord::neighbors].
+#[derive(Clone)]
+pub struct HexGrid<V: CellValue> {
+    radius: usize,
+    /// The cells row by row from the top, where q increases along each row.
+    cells: Vec<Cell<V>>,
+    /// The index of the first cell of each row.
+    row_starts: Vec<usize>,
+    /// The cells borrowed mutably since the last call to
+    /// `take_changed_cells`, possibly with repeats.
+    changed: Vec<HexCoord>,
+    /// Set when too many cells changed to list or every cell was borrowed.
+    all_changed: bool,
+}
+
+impl<V: CellValue> HexGrid<V> {
+    /// Creates a new HexGrid of all hexes within `radius` of the center
+    ///
+    /// Initially filled with uncollapsed, but empty [Cell]s.
+    pub fn new(radius: usize) -> Self {
+        let size = 2 * radius + 1;
+        let mut row_starts = Vec::with_capacity(size);
+        let mut count = 0;
+        for row in 0..size {
+            row_starts.push(count);
+            count += size - row.abs_diff(radius);
+        }
+        Self {
+            radius,
+            cells: vec![Cell::Uncollapsed(HashMap::new()); count],
+            row_starts,
+            changed: Vec::new(),
+            all_changed: true,
+        }
+    }
+
+    /// The radius of this grid
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Returns true if the coordinate is within the grid.
+    pub fn contains(&self, coord: &HexCoord) -> bool {
+        HexCoord::new(0, 0).distance(coord) <= self.radius
+    }
+
+    /// Returns a [`Vec<HexCoord>`] that contains the coordinates for the 6
+    /// cells that neighbor the cell at `coord`. Coordinates outside the grid
+    /// are included.
+    pub fn neighbors(&self, coord: HexCoord) -> Vec<HexCoord> {
+        coord.neighbors()
+    }
+
+    /// The range of q in the row with the given r, which must be in bounds.
+    fn row(radius: isize, r: isize) -> std::ops::RangeInclusive<isize> {
+        (-radius).max(-radius - r)..=radius.min(radius - r)
+    }
+
+    /// The index of the cell at the coordinate, if it is in bounds.
+    fn index(&self, coord: &HexCoord) -> Option<usize> {
+        if !self.contains(coord) {
+            return None;
+        }
+        let radius = self.radius as isize;
+        let first = *Self::row(radius, coord.r()).start();
+        Some(self.row_starts[(coord.r() + radius) as usize] + (coord.q() - first) as usize)
+    }
+}
+
+impl<V: CellValue> Layout<V> for HexGrid<V> {
+    type Coordinate = HexCoord;
+
+    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.changed.clear();
+        self.all_changed = true;
+        let radius = self.radius as isize;
+        let coords = (-radius..=radius)
+            .flat_map(move |r| Self::row(radius, r).map(move |q| HexCoord::new(q, r)));
+        coords.zip(self.cells.iter_mut())
+    }
+
+    fn cells_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
+    where
+        V: 'a,
+    {
+        let radius = self.radius as isize;
+        let coords = (-radius..=radius)
+            .flat_map(move |r| Self::row(radius, r).map(move |q| HexCoord::new(q, r)));
+        coords.zip(self.cells.iter())
+    }
+
+    fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn neighbor_coords(&self, coord: &Self::Coordinate) -> Cow<'_, [Self::Coordinate]> {
+        Cow::Owned(self.neighbors(*coord))
+    }
+
+    fn get_cell(&self, coord: &Self::Coordinate) -> Option<&Cell<V>> {
+        let index = self.index(coord)?;
+        self.cells.get(index)
+    }
+
+    fn get_cell_mut(&mut self, coord: &Self::Coordinate) -> Option<&mut Cell<V>> {
+        let index = self.index(coord)?;
+        if !self.all_changed {
+            if self.changed.len() >= self.cells.len() {
+                self.changed.clear();
+                self.all_changed = true;
+            } else {
+                self.changed.push(*coord);
+            }
+        }
+        self.cells.get_mut(index)
+    }
+
+    fn take_changed_cells(&mut self) -> Option<Vec<Self::Coordinate>> {
+        if self.all_changed {
+            self.all_changed = false;
+            self.changed.clear();
+            return None;
+        }
+        Some(std::mem::take(&mut self.changed))
+    }
+}
@@ -1,6 +1,14 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
 
+pub mod cow_grid;
 pub mod grid;
+pub mod grid_nd;
+pub mod hex_grid;
+pub mod toroidal_grid;
 
 use crate::{
     cell::{Cell, Function, Operation},
@@ -11,7 +19,9 @@ use crate::{
 /// collection of [Cell]s
 pub trait Layout<V: CellValue>: Clone {
     /// A coordinate type for this layout to refer to its Cells.
-    type Coordinate: Clone;
+    ///
+    /// Coordinates are hashable so the solver can keep track of sets of cells.
+    type Coordinate: Clone + Eq + Hash;
 
     /// Get a reference to a [Cell], if that Cell is within bounds.
     fn get_cell(&self, coord: &Self::Coordinate) -> Option<&Cell<V>>;
@@ -19,6 +29,42 @@ pub trait Layout<V: CellValue>: Clone {
     /// Get a mutable reference to a [Cell], if that Cell is within bounds.
     fn get_cell_mut(&mut self, coord: &Self::Coordinate) -> Option<&mut Cell<V>>;
 
+    /// Returns the entropy of the [Cell] at the Coordinate, or None if the Cell
+    /// is out of bounds.
+    ///
+    /// Calls [Cell::entropy] on the cell.
+    fn cell_entropy(&self, coord: &Self::Coordinate) -> Option<f64> {
+        self.get_cell(coord).map(Cell::entropy)
+    }
+
+    /// Returns the probability that the [Cell] at the Coordinate takes the
+    /// value, or None if the Cell is out of bounds.
+    ///
+    /// Calls [Cell::probability] on the cell.
+    fn cell_probability(&self, coord: &Self::Coordinate, value: &V) -> Option<f64> {
+        self.get_cell(coord).map(|cell| cell.probability(value))
+    }
+
+    /// The sum of the entropies of every [Cell] in the [Layout].
+    ///
+    /// Calls [Cell::entropy] on every cell.
+    fn total_entropy(&self) -> f64 {
+        self.cells_ref().map(|(_, cell)| cell.entropy()).sum()
+    }
+
+    /// The sum of the largest entropies every [Cell] in the [Layout] could
+    /// have with its current possibilities.
+    ///
+    /// Calls [Cell::max_entropy] on every cell. This is based on the
+    /// possibilities that are currently in each cell, not on a fixed set of
+    /// values, so it shrinks as the layout is collapsed. To measure progress,
+    /// take the maximum of the initial state once and compare the
+    /// [Layout::total_entropy] of the working layout against it, for example
+    /// as `1.0 - total / max`.
+    fn max_entropy(&self) -> f64 {
+        self.cells_ref().map(|(_, cell)| cell.max_entropy()).sum()
+    }
+
     /// Adds a possibility to the [Cell] at the Coordinate, if the Cell is in
     /// bounds.
     ///
@@ -57,6 +103,16 @@ pub trait Layout<V: CellValue>: Clone {
         }
     }
 
+    /// Adds each of the possibilities to the [Cell] at the Coordinate with a
+    /// weight of 1, if the Cell is in bounds.
+    ///
+    /// Calls [Cell::add_possibilities_set] on the cell.
+    fn add_cell_possibilities_set(&mut self, coord: &Self::Coordinate, possibilities: &HashSet<V>) {
+        if let Some(cell) = self.get_cell_mut(coord) {
+            cell.add_possibilities_set(possibilities);
+        }
+    }
+
     /// Adds a possibility to the [Cell]s at the Coordinates, if the Cells are in
     /// bounds.
     ///
@@ -96,6 +152,16 @@ pub trait Layout<V: CellValue>: Clone {
         }
     }
 
+    /// Adds each of the possibilities to the [Cell]s at the Coordinates with
+    /// a weight of 1, if the Cells are in bounds.
+    ///
+    /// Calls [Cell::add_possibilities_set] on each cell.
+    fn add_cells_possibilities_set(&mut self, coords: Vec<Self::Coordinate>, possibilities: &HashSet<V>) {
+        for coord in coords {
+            self.add_cell_possibilities_set(&coord, possibilities);
+        }
+    }
+
     /// Adds a possibility to all [Cell]s in the [Layout].
     ///
     /// Calls [Cell::add_possibility] on every cell.
@@ -120,6 +186,15 @@ pub trait Layout<V: CellValue>: Clone {
             cell.add_possibilities(possibilities);
         }
     }
+    /// Adds each of the possibilities to every [Cell] in the [Layout] with a
+    /// weight of 1, for when every value is equally likely.
+    ///
+    /// Calls [Cell::add_possibilities_set] on every cell.
+    fn add_possibilities_set(&mut self, possibilities: &HashSet<V>) {
+        for (_, cell) in self.cells() {
+            cell.add_possibilities_set(possibilities);
+        }
+    }
 
     /// Removes a possibility from the [Cell] at the Coordinate, if the Cell is
     /// in bounds.
@@ -228,6 +303,33 @@ pub trait Layout<V: CellValue>: Clone {
         }
     }
 
+    /// Removes every possibility that is not in `allowed` from the [Cell]s at
+    /// the Coordinates, if the Cells are in bounds.
+    ///
+    /// The value type is shared by every cell of a layout, but different
+    /// regions can still draw from different pools of values. Restrict each
+    /// region of the initial state to its own subset before solving, for
+    /// example limiting the border of a map to water tiles while the interior
+    /// may use any tile. Collapsed cells are not changed.
+    fn restrict_region(&mut self, coords: Vec<Self::Coordinate>, allowed: &HashSet<V>) {
+        for coord in coords {
+            if let Some(Cell::Uncollapsed(possibilities)) = self.get_cell_mut(&coord) {
+                possibilities.retain(|possibility, _| allowed.contains(possibility));
+            }
+        }
+    }
+
+    /// Shrinks the possibilities of every [Cell] in the [Layout] to fit.
+    ///
+    /// Calls [Cell::shrink] on every cell. This is only a memory optimization,
+    /// which also makes cloning the layout cheaper after many possibilities
+    /// were removed.
+    fn compact(&mut self) {
+        for (_, cell) in self.cells() {
+            cell.shrink();
+        }
+    }
+
     /// Collapses the [Cell] at the given coordinates to the given value.
     ///
     /// Since this does not involve the wavefunction, it does not enforce any
@@ -244,6 +346,25 @@ pub trait Layout<V: CellValue>: Clone {
         }
     }
 
+    /// Collapses the [Cell] at the given coordinates to the given value, and
+    /// returns the possibilities that the cell had before.
+    ///
+    /// Returns None, leaving the cell unchanged, if the cell was already
+    /// collapsed or is out of bounds. Like [Layout::collapse], the cell is
+    /// collapsed even if the value was not one of its possibilities. Passing
+    /// the result to [Cell::set_possibilities] undoes the collapse, without
+    /// having to keep a copy of the whole layout.
+    fn collapse_returning(&mut self, coord: &Self::Coordinate, value: V) -> Option<HashMap<V, usize>> {
+        let cell = self.get_cell_mut(coord)?;
+        match std::mem::replace(cell, Cell::Collapsed(value)) {
+            Cell::Uncollapsed(possibilities) => Some(possibilities),
+            collapsed => {
+                *cell = collapsed;
+                None
+            }
+        }
+    }
+
     /// Sets a cell to an uncollapsed state with no weights.
     fn clear_cell(&mut self, coord: &Self::Coordinate) {
         if let Some(cell) = self.get_cell_mut(coord) {
@@ -271,22 +392,117 @@ pub trait Layout<V: CellValue>: Clone {
     /// (Coordinate, Cell)
     ///
     /// The order is not gaurenteed to be anything in particular.
-    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &mut Cell<V>)>
+    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a;
+
+    /// Iterates over all cells in the layout without modifying it. Yielding a
+    /// 2-tuple of (Coordinate, Cell)
+    ///
+    /// Unlike [Layout::cells], this does not mark the cells as changed, so it
+    /// is safe to call on a layout in the middle of solving. The order is not
+    /// gaurenteed to be anything in particular.
+    fn cells_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
     where
         V: 'a;
 
     /// The total number of cells in the [Layout]
     fn cell_count(&self) -> usize;
 
+    /// The shape of the [Layout], as the size of each of its axes.
+    ///
+    /// This lets generic tooling, such as serializers and renderers, learn
+    /// the shape of a layout. A [Grid](crate::Grid) returns `[x, y]` and a
+    /// [GridND](crate::GridND) returns the size of each of its axes. Layouts
+    /// that are not grids may describe their shape in their own way, as long
+    /// as the product of the sizes is the number of cells. The default
+    /// implementation treats the layout as a single axis of
+    /// [Layout::cell_count] cells.
+    fn dimensions(&self) -> Vec<usize> {
+        vec![self.cell_count()]
+    }
+
+    /// Returns the coordinates of the cells that may have been modified since
+    /// the last call, and starts tracking changes anew.
+    ///
+    /// This lets the solver update its candidates incrementally rather than
+    /// rescanning the whole layout after every collapse. Coordinates may be
+    /// repeated. Returns None if the changes are not known, in which case every
+    /// cell must be assumed to have changed. The default implementation does
+    /// not track changes and always returns None.
+    fn take_changed_cells(&mut self) -> Option<Vec<Self::Coordinate>> {
+        None
+    }
+
+    /// Saves the state of the layout, so that it can be returned to with
+    /// [Layout::restore].
+    ///
+    /// Checkpoints nest, and each [Layout::restore] returns to the most recent
+    /// one that was not restored yet. The solver uses these to undo the
+    /// collapses of a failed branch of its search, instead of trying each
+    /// branch on a clone of the layout. A layout can implement them by
+    /// saving the previous state of each cell the first time it is borrowed
+    /// mutably after a checkpoint, including the change tracking of
+    /// [Layout::take_changed_cells]. Clones of a layout need not keep its
+    /// checkpoints.
+    ///
+    /// Returns false if checkpoints are not supported, in which case
+    /// [Layout::restore] does nothing. This is the default implementation.
+    fn checkpoint(&mut self) -> bool {
+        false
+    }
+
+    /// Returns the layout to the state it had at the most recent checkpoint,
+    /// and removes that checkpoint. Does nothing if there is no checkpoint.
+    fn restore(&mut self) {}
+
+    /// Returns the coordinates of the cells adjacent to the cell at the
+    /// coordinate. Coordinates that are out of bounds may be included.
+    ///
+    /// The solver uses this for heuristics that consider the surroundings of
+    /// a cell, such as [Solver::set_coherence_weight](crate::Solver::set_coherence_weight).
+    /// The default implementation returns no neighbors.
+    fn neighbor_coords(&self, _coord: &Self::Coordinate) -> Cow<'_, [Self::Coordinate]> {
+        Cow::Borrowed(&[])
+    }
+
     /// Iterates over all instances of [Cell::Uncollapsed] in the layout.
     /// Yielding a 2-tuple of (Coordinate, Cell)
     ///
     /// The order is not gaurenteed to be anything in particular.
     /// Default implementation calls and filters [Layout::cells].
-    fn candidates<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &mut Cell<V>)>
+    fn candidates<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
     where
         V: 'a,
     {
         self.cells().filter(|e| !e.1.is_collapsed())
     }
+
+    /// Iterates over all instances of [Cell::Uncollapsed] in the layout
+    /// without modifying it. Yielding a 2-tuple of (Coordinate, Cell)
+    ///
+    /// The order is not gaurenteed to be anything in particular.
+    /// Default implementation calls and filters [Layout::cells_ref].
+    fn candidates_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.cells_ref().filter(|e| !e.1.is_collapsed())
+    }
+
+    /// Returns the coordinates and entropies of every instance of
+    /// [Cell::Uncollapsed] in the layout, from the lowest entropy to the
+    /// highest.
+    ///
+    /// This is the order that [SelectionStrategy::MinEntropy](crate::SelectionStrategy::MinEntropy)
+    /// chooses from, for use by custom observation loops. Cells with equal
+    /// entropy are left in the order of [Layout::candidates_ref].
+    fn candidates_by_entropy(&self) -> Vec<(Self::Coordinate, f64)> {
+        let mut candidates: Vec<_> = self
+            .candidates_ref()
+            .map(|(coord, cell)| (coord, cell.entropy()))
+            .collect();
+        candidates.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        candidates
+    }
 }
@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+
+use crate::{cell::Cell, CellValue, Coord2D, Grid};
+
+use super::Layout;
+
+/// A two dimensional grid [Layout] whose opposite edges are connected, for
+/// generating seamless, tileable output.
+///
+/// Coordinates past an edge wrap around to the other side, so a coordinate
+/// with `x` equal to the width is in column 0, and one with an `x` of
+/// `usize::MAX`, as produced by [Coord2D::neighbors] at the left edge, is in
+/// the last column. The same holds for rows. Every [Layout] method accepts
+/// such coordinates, so a wavefunction that removes possibilities from the
+/// neighbors of a cell constrains the cells across the seam as well.
+/// [ToroidalGrid::neighbors] returns the wrapped coordinates directly.
+///
+/// On a grid less than three cells wide or tall, a cell can be its own
+/// neighbor, or neighbor another cell from both sides.
+///
+/// The cells are stored in a [Grid], which [ToroidalGrid::grid] and
+/// [ToroidalGrid::into_grid] give access to, for example to display or
+/// detile the output.
+#[derive(Clone)]
+pub struct ToroidalGrid<V: CellValue> {
+    grid: Grid<V>,
+}
+
+impl<V: CellValue> ToroidalGrid<V> {
+    /// Creates a new ToroidalGrid with size (x, y)
+    ///
+    /// Initially filled with uncollapsed, but empty [Cell]s.
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { grid: Grid::new(x, y) }
+    }
+
+    /// Creates a new ToroidalGrid that connects the edges of the [Grid].
+    pub fn from_grid(grid: Grid<V>) -> Self {
+        Self { grid }
+    }
+
+    /// The [Grid] holding the cells, where coordinates do not wrap.
+    pub fn grid(&self) -> &Grid<V> {
+        &self.grid
+    }
+
+    /// Returns the [Grid] holding the cells.
+    pub fn into_grid(self) -> Grid<V> {
+        self.grid
+    }
+
+    /// The x size of this grid
+    pub fn x(&self) -> usize {
+        self.grid.x()
+    }
+
+    /// The y size of this grid
+    pub fn y(&self) -> usize {
+        self.grid.y()
+    }
+
+    /// Returns the coordinate wrapped around into the bounds of the grid, or
+    /// None if the grid has no cells.
+    ///
+    /// Components are treated as signed, so `usize::MAX` is one before 0.
+    pub fn wrap(&self, coord: Coord2D) -> Option<Coord2D> {
+        let (x, y) = (self.grid.x() as isize, self.grid.y() as isize);
+        if x == 0 || y == 0 {
+            return None;
+        }
+        Some(Coord2D::new(
+            (coord.x() as isize).rem_euclid(x) as usize,
+            (coord.y() as isize).rem_euclid(y) as usize,
+        ))
+    }
+
+    /// Returns a [`Vec<Coord2D>`] that contains the coordinates for the 8
+    /// cells that neighbor the cell at `coord`, wrapped around the edges.
+    pub fn neighbors(&self, coord: Coord2D) -> Vec<Coord2D> {
+        coord
+            .neighbors()
+            .into_iter()
+            .filter_map(|neighbor| self.wrap(neighbor))
+            .collect()
+    }
+}
+
+impl<V: CellValue> Layout<V> for ToroidalGrid<V> {
+    type Coordinate = Coord2D;
+
+    fn cells<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.grid.cells()
+    }
+
+    fn cells_ref<'a>(&'a self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.grid.cells_ref()
+    }
+
+    fn candidates<'a>(&'a mut self) -> impl 'a + Iterator<Item = (Self::Coordinate, &'a mut Cell<V>)>
+    where
+        V: 'a,
+    {
+        self.grid.candidates()
+    }
+
+    fn cell_count(&self) -> usize {
+        self.grid.cell_count()
+    }
+
+    fn dimensions(&self) -> Vec<usize> {
+        self.grid.dimensions()
+    }
+
+    fn neighbor_coords(&self, coord: &Self::Coordinate) -> Cow<'_, [Self::Coordinate]> {
+        Cow::Owned(self.neighbors(*coord))
+    }
+
+    fn get_cell(&self, coord: &Self::Coordinate) -> Option<&Cell<V>> {
+        let coord = self.wrap(*coord)?;
+        self.grid.get_cell(&coord)
+    }
+
+    fn get_cell_mut(&mut self, coord: &Self::Coordinate) -> Option<&mut Cell<V>> {
+        let coord = self.wrap(*coord)?;
+        self.grid.get_cell_mut(&coord)
+    }
+
+    fn take_changed_cells(&mut self) -> Option<Vec<Self::Coordinate>> {
+        self.grid.take_changed_cells()
+    }
+
+    fn checkpoint(&mut self) -> bool {
+        self.grid.checkpoint()
+    }
+
+    fn restore(&mut self) {
+        self.grid.restore()
+    }
+}
@@ -127,7 +127,10 @@
 //! ```
 
 mod solver;
-pub use solver::Solver;
+pub use solver::{Difficulty, Recipe, SelectionStrategy, SetupError, SolveError, SolveEvent, Solver, StopReason, ValueOrder};
+
+mod chooser;
+pub use chooser::{Chooser, MaxWeightChooser, RandomChooser};
 
 mod cell;
 pub use cell::{Cell, CellValue, Operation, Function};
@@ -136,9 +139,11 @@ mod tile;
 pub use tile::Tile2D;
 
 mod layout;
-pub use layout::{grid::{Grid, Coord2D, Direction}, Layout};
+pub use layout::{cow_grid::CowGrid, grid::{Grid, GridError, Coord2D, Direction}, grid_nd::{GridND, CoordND, Grid3D, Coord3D}, hex_grid::{HexGrid, HexCoord}, toroidal_grid::ToroidalGrid, Layout};
 
 mod wavefunction;
-pub use wavefunction::{Wavefunction, Standard2D};
+pub use wavefunction::{Wavefunction, AdjacencyStats, CnfFormula, Standard2D, RuleConflict, RuleTable, ThicknessConstraint};
 
 mod weighted_iterator;
+
+mod candidate_heap;
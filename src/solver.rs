@@ -1,6 +1,244 @@
-use rand::{seq::SliceRandom, thread_rng};
+use std::{
+    cmp::{Ordering as CmpOrdering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fmt::{Debug, Display},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+};
 
-use crate::{cell::Cell, weighted_iterator::WeightedIterator, Layout, Wavefunction};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{candidate_heap::CandidateHeap, cell::Cell, Chooser, weighted_iterator::{stable_hash, WeightedIterator}, CellValue, Coord2D, Grid, Layout, Wavefunction};
+
+// The coordinate type of the layout used by a wavefunction.
+type Coord<W> = <<W as Wavefunction>::L as Layout<<W as Wavefunction>::V>>::Coordinate;
+
+// Per coordinate factors that the possibility weights are multiplied by.
+type BiasField<W> = Box<dyn Fn(Coord<W>) -> HashMap<<W as Wavefunction>::V, f64>>;
+
+// Constraints applied to the working layout in addition to the wavefunction's.
+type DynamicConstraint<W> = Box<dyn Fn(&mut <W as Wavefunction>::L)>;
+
+// Called with the depth and coordinate of every backtrack.
+type BacktrackCallback<W> = Box<dyn FnMut(usize, &Coord<W>)>;
+
+// Called with the layout and coordinate of every collapse.
+type StepCallback<W> = Box<dyn FnMut(&<W as Wavefunction>::L, &Coord<W>)>;
+
+// Per coordinate values that order cells with equal scores.
+type TiebreakNoise<W> = Box<dyn Fn(&Coord<W>) -> f64>;
+
+// How freely each cell of a solution was chosen.
+type ConfidenceMap<W> = HashMap<Coord<W>, f64>;
+
+// The collapsed cell, its value, and the state of its neighbors before the
+// collapse was propagated.
+type MemoKey<W> = (Coord<W>, <W as Wavefunction>::V, Vec<Option<CellState<<W as Wavefunction>::V>>>);
+
+// The neighbors of a collapsed cell after the collapse was propagated.
+type MemoCells<W> = Vec<Option<Cell<<W as Wavefunction>::V>>>;
+
+/// Determines the order in which the solver tries the possibilities of a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueOrder {
+    /// Possibilities are tried in a random order, where possibilities with
+    /// larger weights are more likely to be tried first.
+    WeightedRandom,
+    /// Possibilities that leave the most possibilities in the rest of the
+    /// layout are tried first. Ties are broken by weighted random order.
+    ///
+    /// This is the classic least constraining value heuristic. It can greatly
+    /// reduce backtracking on tightly constrained problems, but choosing each
+    /// value requires simulating the wavefunction's propagation once for every
+    /// possibility, which makes each step considerably more expensive.
+    LeastConstraining,
+}
+
+/// Determines how the solver chooses the next cell to collapse.
+///
+/// Ties between equally good cells are broken at random. Cells with no
+/// possibilities left are always chosen first, so that contradictions are
+/// found as early as possible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// Choose the cell with the lowest Shannon entropy, see [Cell::entropy].
+    MinEntropy,
+    /// Choose the cell with the fewest remaining possibilities, regardless of
+    /// their weights.
+    MinimumRemainingValues,
+    /// Choose any uncollapsed cell at random.
+    Random,
+}
+
+/// The reason a solve stopped before its search was complete.
+///
+/// When a solve returns None without a stop reason, the search was completed
+/// and no solution exists for the initial conditions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The solver reached its limit on the number of backtracks.
+    BacktrackLimit,
+    /// The solve was cancelled through [Solver::solve_cancellable].
+    Cancelled,
+}
+
+/// How much search a solve required, as estimated by
+/// [Solver::estimate_difficulty].
+///
+/// Variants are ordered from easiest to hardest, so difficulties can be
+/// compared directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    /// The initial state determines the solution through singleton
+    /// propagation alone, without making any choices.
+    Easy,
+    /// A solution was found with choices, but with fewer backtracks than
+    /// [Difficulty::HARD_BACKTRACKS].
+    Medium,
+    /// A solution was found, but only after at least
+    /// [Difficulty::HARD_BACKTRACKS] backtracks.
+    Hard,
+    /// No solution was found.
+    Unsolvable,
+}
+
+impl Difficulty {
+    /// The number of backtracks from which a solve is considered hard.
+    pub const HARD_BACKTRACKS: u32 = 10;
+}
+
+/// A problem with the setup of a [Solver] found by [Solver::validate_setup].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetupError<C> {
+    /// Every cell of the wavefunction's initial state is uncollapsed without
+    /// any possibilities, so nothing could ever be placed. This contains the
+    /// first such cell.
+    NoPossibilities(C),
+}
+
+impl<C: Display> Display for SetupError<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetupError::NoPossibilities(coord) => write!(
+                f,
+                "cell {coord} has no possibilities; did you call add_possibilities?"
+            ),
+        }
+    }
+}
+
+impl<C: Debug + Display> std::error::Error for SetupError<C> {}
+
+/// The reason [Solver::try_solve] found no solution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolveError<C> {
+    /// The search was completed without finding a solution.
+    Contradiction {
+        /// The last cell that the search left without any possibilities.
+        coord: C,
+        /// The number of backtracks the search made.
+        backtracks: u32,
+    },
+    /// The solve reached its backtrack limit, see
+    /// [Solver::set_max_backtracks], so a solution may still exist.
+    Exhausted {
+        /// The number of backtracks made before the solve stopped.
+        backtracks: u32,
+    },
+    /// The solve was cancelled through [Solver::solve_cancellable].
+    Cancelled {
+        /// The number of backtracks made before the solve stopped.
+        backtracks: u32,
+    },
+}
+
+impl<C: Display> Display for SolveError<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::Contradiction { coord, backtracks } => write!(
+                f,
+                "no solution; cell {coord} had no possibilities left after {backtracks} backtracks"
+            ),
+            SolveError::Exhausted { backtracks } => {
+                write!(f, "backtrack limit reached after {backtracks} backtracks")
+            }
+            SolveError::Cancelled { backtracks } => {
+                write!(f, "solve cancelled after {backtracks} backtracks")
+            }
+        }
+    }
+}
+
+impl<C: Debug + Display> std::error::Error for SolveError<C> {}
+
+/// An event sent by [Solver::solve_streaming] as the search progresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolveEvent<C, V> {
+    /// The solver collapsed the cell at the coordinate to the value.
+    Collapse(C, V),
+    /// The solver backtracked out of its collapse of the cell at the
+    /// coordinate. The cell is uncollapsed again.
+    Undo(C),
+}
+
+/// The configuration of a [Solver], which can be used to recreate it.
+///
+/// A recipe is created by [Solver::export_recipe] and turned back into a
+/// solver by [Solver::from_recipe]. Given the same wavefunction, a solver
+/// created from a recipe that includes a seed produces the same solution as
+/// the first solve of the solver the recipe was exported from, as long as
+/// none of the settings below that a recipe cannot capture were used.
+///
+/// Closures cannot be captured and must be set again on the new solver. These
+/// are the tile placement function, the step and backtrack callbacks, the bias
+/// field, the tiebreak noise, the [Chooser] and the dynamic constraints. The
+/// target of [Solver::solve_toward], the diversity memory, and changes to the
+/// initial state other than givens and restrictions, such as
+/// [Solver::propagate_hidden_singles], are not captured either. Restrictions
+/// are applied before the givens when the recipe is replayed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipe<C: Eq + Hash, V: Eq + Hash> {
+    /// The seed given to [Solver::set_seed], if any.
+    pub seed: Option<u64>,
+    /// The cells collapsed with [Solver::collapse_initial], in order.
+    pub collapses: Vec<(C, V)>,
+    /// The value given to [Solver::set_fallback_value], if any.
+    pub fallback: Option<V>,
+    /// The order in which possibilities are tried.
+    pub value_order: ValueOrder,
+    /// How the next cell to collapse is chosen.
+    pub selection_strategy: SelectionStrategy,
+    /// The budget given to [Solver::set_adaptive_backtrack_budget].
+    pub adaptive_budget: u32,
+    /// Whether partition solving is enabled.
+    pub partition_solving: bool,
+    /// The weight given to [Solver::set_coherence_weight].
+    pub coherence_weight: f64,
+    /// The importances given to [Solver::set_value_importance].
+    pub value_importance: HashMap<V, f64>,
+    /// Whether the candidate cache is enabled.
+    pub candidate_cache: bool,
+    /// Whether scanline order is enabled.
+    pub scanline_order: bool,
+    /// Whether batch collapse is enabled.
+    pub batch_collapse: bool,
+    /// The floor given to [Solver::set_entropy_floor], if any.
+    pub entropy_floor: Option<f64>,
+    /// The limit given to [Solver::set_max_backtracks], if any.
+    pub max_backtracks: Option<u32>,
+    /// The budget given to [Solver::set_backtrack_budget_per_cell], if any.
+    pub backtrack_budget_per_cell: Option<u32>,
+    /// The cells restricted with [Solver::restrict_initial], in order.
+    pub restrictions: Vec<(C, HashSet<V>)>,
+    /// Whether the layout is compacted before searching.
+    pub compact_layout: bool,
+    /// The cells of the region set by [Solver::set_solve_region], if any.
+    pub solve_region: Option<HashSet<C>>,
+}
 
 /// Collapses the [Wavefunction] it is created with, returning the resulting [Layout].
 pub struct Solver<W: Wavefunction> {
@@ -8,18 +246,174 @@ pub struct Solver<W: Wavefunction> {
     initial_state: W::L,
     backtracks: u32,
     on_tile_placement: fn(&mut W::L) -> (),
+    on_step: Option<StepCallback<W>>,
+    on_backtrack: Option<BacktrackCallback<W>>,
+    fallback: Option<W::V>,
+    bias_field: Option<BiasField<W>>,
+    target: Option<W::L>,
+    tiebreak_noise: Option<TiebreakNoise<W>>,
+    locked: Vec<(Coord<W>, W::V)>,
+    dynamic_constraints: Vec<DynamicConstraint<W>>,
+    value_order: ValueOrder,
+    chooser: Option<Box<dyn Chooser<W::V>>>,
+    selection_strategy: SelectionStrategy,
+    backtrack_limit: Option<u32>,
+    backtrack_budget_per_cell: Option<u32>,
+    stop_reason: Option<StopReason>,
+    contradiction: Option<Coord<W>>,
+    cancel: Option<Arc<AtomicBool>>,
+    adaptive_budget: u32,
+    adaptive_strategy: Option<SelectionStrategy>,
+    event_sender: Option<Sender<SolveEvent<Coord<W>, W::V>>>,
+    rng: StdRng,
+    partition_solving: bool,
+    region: Option<HashSet<Coord<W>>>,
+    skipped: HashSet<Coord<W>>,
+    solve_region: Option<HashSet<Coord<W>>>,
+    seed: Option<u64>,
+    initial_collapses: Vec<(Coord<W>, W::V)>,
+    restrictions: Vec<(Coord<W>, HashSet<W::V>)>,
+    diversity: bool,
+    diversity_memory: Vec<W::L>,
+    path: Vec<(Coord<W>, W::V)>,
+    record_entropy: bool,
+    entropy_trace: Vec<f64>,
+    entropy_floor: Option<f64>,
+    // The length of the path at which the search stops as if it were finished.
+    depth_limit: Option<usize>,
+    coherence_weight: f64,
+    value_importance: HashMap<W::V, f64>,
+    candidate_cache: bool,
+    compact: bool,
+    scanline: bool,
+    batch_collapse: bool,
+    journaling: bool,
+    collapse_memo: bool,
+    memo: HashMap<MemoKey<W>, MemoCells<W>>,
 }
 
 impl<W: Wavefunction> Solver<W> {
     /// Create a new Solver with the given wavefunction.
     pub fn new(wavefunction: W) -> Self {
         let layout = wavefunction.get_initial_state().clone();
-        return Self {
+        Self {
             wavefunction,
             initial_state: layout,
             backtracks: 0,
-            on_tile_placement: |_|{}
-        };
+            on_tile_placement: |_|{},
+            on_step: None,
+            on_backtrack: None,
+            fallback: None,
+            bias_field: None,
+            target: None,
+            tiebreak_noise: None,
+            locked: Vec::new(),
+            dynamic_constraints: Vec::new(),
+            value_order: ValueOrder::WeightedRandom,
+            chooser: None,
+            selection_strategy: SelectionStrategy::MinEntropy,
+            backtrack_limit: None,
+            backtrack_budget_per_cell: None,
+            stop_reason: None,
+            contradiction: None,
+            cancel: None,
+            adaptive_budget: 1000,
+            adaptive_strategy: None,
+            event_sender: None,
+            rng: StdRng::from_entropy(),
+            partition_solving: false,
+            region: None,
+            skipped: HashSet::new(),
+            solve_region: None,
+            seed: None,
+            initial_collapses: Vec::new(),
+            restrictions: Vec::new(),
+            diversity: false,
+            diversity_memory: Vec::new(),
+            path: Vec::new(),
+            record_entropy: false,
+            entropy_trace: Vec::new(),
+            entropy_floor: None,
+            depth_limit: None,
+            coherence_weight: 0.0,
+            value_importance: HashMap::new(),
+            candidate_cache: true,
+            compact: false,
+            scanline: false,
+            batch_collapse: false,
+            journaling: true,
+            collapse_memo: false,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Create a new Solver with the given wavefunction, whose random number
+    /// generator is seeded with the given seed, see [Solver::set_seed].
+    pub fn with_seed(wavefunction: W, seed: u64) -> Self {
+        let mut solver = Self::new(wavefunction);
+        solver.set_seed(seed);
+        solver
+    }
+
+    /// Create a new Solver with the given wavefunction, configured by the
+    /// recipe.
+    ///
+    /// The restrictions of the recipe are applied with
+    /// [Solver::restrict_initial] and then its collapses with
+    /// [Solver::collapse_initial], so the wavefunction should start from the
+    /// same initial state as the one the recipe was exported with.
+    pub fn from_recipe(wavefunction: W, recipe: Recipe<Coord<W>, W::V>) -> Self {
+        let mut solver = Self::new(wavefunction);
+        if let Some(seed) = recipe.seed {
+            solver.set_seed(seed);
+        }
+        for (coord, allowed) in &recipe.restrictions {
+            solver.restrict_initial(coord.clone(), allowed);
+        }
+        for (coord, value) in recipe.collapses {
+            solver.collapse_initial(coord, value);
+        }
+        solver.fallback = recipe.fallback;
+        solver.value_order = recipe.value_order;
+        solver.selection_strategy = recipe.selection_strategy;
+        solver.adaptive_budget = recipe.adaptive_budget;
+        solver.partition_solving = recipe.partition_solving;
+        solver.coherence_weight = recipe.coherence_weight;
+        solver.value_importance = recipe.value_importance;
+        solver.candidate_cache = recipe.candidate_cache;
+        solver.scanline = recipe.scanline_order;
+        solver.batch_collapse = recipe.batch_collapse;
+        solver.entropy_floor = recipe.entropy_floor;
+        solver.backtrack_limit = recipe.max_backtracks;
+        solver.backtrack_budget_per_cell = recipe.backtrack_budget_per_cell;
+        solver.compact = recipe.compact_layout;
+        solver.solve_region = recipe.solve_region;
+        solver
+    }
+
+    /// Captures the seed, initial collapses, restrictions and configuration of
+    /// the solver in a [Recipe].
+    pub fn export_recipe(&self) -> Recipe<Coord<W>, W::V> {
+        Recipe {
+            seed: self.seed,
+            collapses: self.initial_collapses.clone(),
+            fallback: self.fallback,
+            value_order: self.value_order,
+            selection_strategy: self.selection_strategy,
+            adaptive_budget: self.adaptive_budget,
+            partition_solving: self.partition_solving,
+            coherence_weight: self.coherence_weight,
+            value_importance: self.value_importance.clone(),
+            candidate_cache: self.candidate_cache,
+            scanline_order: self.scanline,
+            batch_collapse: self.batch_collapse,
+            entropy_floor: self.entropy_floor,
+            max_backtracks: self.backtrack_limit,
+            backtrack_budget_per_cell: self.backtrack_budget_per_cell,
+            restrictions: self.restrictions.clone(),
+            compact_layout: self.compact,
+            solve_region: self.solve_region.clone(),
+        }
     }
 
     /// Print the current state of the [Layout]
@@ -27,11 +421,155 @@ impl<W: Wavefunction> Solver<W> {
         self.wavefunction.print_layout(&self.initial_state);
     }
 
+    /// Returns a reference to the initial [Layout] state that each solve
+    /// starts from.
+    pub fn get_initial_state(&self) -> &W::L {
+        &self.initial_state
+    }
+
+    /// Sets a function to be called each time the solver collapses a cell
+    /// during a solve, which is off by default.
+    ///
+    /// The function is given the layout after the collapse was propagated, and
+    /// the coordinate of the cell that was collapsed. It is called for every
+    /// collapse the search makes, including those on branches it later
+    /// backtracks out of, so it can be used to animate a solve or to log its
+    /// progress. See [Solver::set_on_backtrack] for the backtracks.
+    pub fn set_on_step(&mut self, func: impl FnMut(&W::L, &Coord<W>) + 'static) {
+        self.on_step = Some(Box::new(func));
+    }
+
+    /// Sets a function to be called each time the solver backtracks, which is
+    /// off by default.
+    ///
+    /// The function is given the depth of the search, as the number of cells
+    /// the solver has collapsed on the current branch, and the coordinate of
+    /// the cell whose possibilities all failed and is being abandoned. It is
+    /// called once for every backtrack counted by
+    /// [Solver::get_backtrack_count], so collecting the depths or
+    /// coordinates into a histogram shows where the search struggles.
+    pub fn set_on_backtrack(&mut self, func: impl FnMut(usize, &Coord<W>) + 'static) {
+        self.on_backtrack = Some(Box::new(func));
+    }
+
+    /// Returns the coordinates of the cells that are collapsed in the initial
+    /// state, which the solver treats as fixed.
+    ///
+    /// These are the givens of [Solver::collapse_initial] and any cells that
+    /// the wavefunction collapsed itself. The solver never collapses them
+    /// again, and the propagation of other collapses must not change them.
+    /// If propagation does change a locked cell, for example by giving it
+    /// possibilities again, the cell is cleared, which is an immediate
+    /// contradiction that makes the solver backtrack.
+    pub fn locked_cells(&self) -> Vec<Coord<W>> {
+        collapsed_cells(&self.initial_state).into_iter().map(|(coord, _)| coord).collect()
+    }
+
     /// Returns the number of backtracks the solver made during its last solve
     pub fn get_backtrack_count(&self) -> u32 {
         self.backtracks
     }
 
+    /// Returns the collapses the solver made to produce the solution of its
+    /// last solve, in the order they were made.
+    ///
+    /// Branches the solver backtracked out of are not included, and neither
+    /// are the cells collapsed in the initial state. Collapsing each cell in
+    /// turn on the initial state rebuilds the solution, propagating each
+    /// collapse through the wavefunction if its possibilities are needed too.
+    /// The path is empty if the last solve found no solution.
+    pub fn last_solution_path(&self) -> Vec<(Coord<W>, W::V)> {
+        self.path.clone()
+    }
+
+    /// Sets whether the solver records the total entropy of the layout after
+    /// each collapse, see [Solver::entropy_trace]. This is off by default.
+    ///
+    /// Recording computes [Layout::total_entropy] after every collapse, which
+    /// visits every cell and so makes each step of the search O(cells).
+    pub fn set_record_entropy(&mut self, enabled: bool) {
+        self.record_entropy = enabled;
+        if !enabled {
+            self.entropy_trace.clear();
+        }
+    }
+
+    /// Returns the total entropy of the layout after each collapse on the
+    /// path of the last solve, as recorded with [Solver::set_record_entropy].
+    ///
+    /// There is one value for each collapse of [Solver::last_solution_path],
+    /// taken after the collapse was propagated. States the solver backtracked
+    /// out of are not recorded, so this shows how quickly the successful
+    /// branch reduced the uncertainty of the layout. With uniform weights the
+    /// trace never increases, but removing a likely possibility from a cell
+    /// with uneven weights can raise its entropy. The trace is empty if the
+    /// last solve found no solution, and after [Solver::resolve_after_change]
+    /// it only covers the collapses made by the repair.
+    pub fn entropy_trace(&self) -> &[f64] {
+        &self.entropy_trace
+    }
+
+    /// Stops a solve early once every uncollapsed cell has an entropy below
+    /// the floor, for quick rough drafts.
+    ///
+    /// When the floor is reached, the solve returns the layout as it is, so
+    /// the result may not be fully collapsed. Its remaining cells are nearly
+    /// determined, and [Solver::finalize] can usually complete it with little
+    /// or no backtracking. A cell without any possibilities is never below the
+    /// floor, so the early return is never a contradiction. The check visits
+    /// every cell after each collapse.
+    pub fn set_entropy_floor(&mut self, floor: f64) {
+        self.entropy_floor = Some(floor);
+    }
+
+    /// Limits the number of backtracks of a solve, to bound the time spent on
+    /// layouts whose constraints need a lot of search.
+    ///
+    /// Once the solver has backtracked `limit` times, the solve stops and
+    /// returns None with [StopReason::BacktrackLimit]. Use
+    /// [Solver::last_solve_exhausted] to tell this apart from a search that
+    /// proved there is no solution. By default there is no limit.
+    pub fn set_max_backtracks(&mut self, limit: u32) {
+        self.backtrack_limit = Some(limit);
+    }
+
+    /// Returns true if the last solve stopped because it reached the
+    /// backtrack limit, see [Solver::set_max_backtracks].
+    ///
+    /// When a solve returns None and this is false, the search was complete
+    /// or cancelled, rather than cut short by the limit.
+    pub fn last_solve_exhausted(&self) -> bool {
+        self.stop_reason == Some(StopReason::BacktrackLimit)
+    }
+
+    /// Limits the number of backtracks of a solve in proportion to the size of
+    /// the layout.
+    ///
+    /// The limit is `budget` times the number of cells in the layout, so grids
+    /// of different sizes behave consistently without computing a limit for
+    /// each. When the solver also has a limit set by
+    /// [Solver::set_max_backtracks], the stricter of the two applies. Reaching the limit stops the solve with
+    /// [StopReason::BacktrackLimit].
+    pub fn set_backtrack_budget_per_cell(&mut self, budget: u32) {
+        self.backtrack_budget_per_cell = Some(budget);
+    }
+
+    /// Returns the number of backtracks a solve may make before it is stopped,
+    /// or None if it is unlimited.
+    ///
+    /// This is the stricter of the limit set by [Solver::set_max_backtracks]
+    /// and the budget set by [Solver::set_backtrack_budget_per_cell].
+    pub fn get_backtrack_limit(&self) -> Option<u32> {
+        let per_cell = self.backtrack_budget_per_cell.map(|budget| {
+            let cells = u32::try_from(self.initial_state.cell_count()).unwrap_or(u32::MAX);
+            budget.saturating_mul(cells)
+        });
+        match (self.backtrack_limit, per_cell) {
+            (Some(limit), Some(per_cell)) => Some(limit.min(per_cell)),
+            (limit, per_cell) => limit.or(per_cell),
+        }
+    }
+
     /// Sets a function to be called each time the layout has a tile added to
     /// it. The function must take a single mutable reference to the layout
     /// type.
@@ -39,18 +577,505 @@ impl<W: Wavefunction> Solver<W> {
         self.on_tile_placement = func;
     }
 
+    /// Seeds the random number generator the solver uses to choose values
+    /// and to break ties between equally good cells to collapse next.
+    ///
+    /// Without a seed, the generator is seeded from system entropy. The
+    /// generator is not reset between solves, so reseed before each solve
+    /// that should repeat the same choices.
+    ///
+    /// The solver never depends on the iteration order of the HashMaps in a
+    /// [Cell], which is random for every map. So given the same seed, the
+    /// same wavefunction and the same initial collapses, a solve makes the
+    /// same collapses in the same order, as long as the wavefunction itself
+    /// is deterministic in the changes it makes.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Returns true if reseeding with the same seed before a solve repeats
+    /// the same solve, so that test harnesses can check the setup of a
+    /// reproducible solver.
+    ///
+    /// This holds when all of the following are true:
+    ///
+    /// * A seed was set with [Solver::set_seed], [Solver::with_seed] or a
+    ///   [Recipe], rather than seeding from system entropy.
+    /// * No [Chooser] is set with [Solver::set_chooser]. A chooser keeps its
+    ///   own state, which the seed does not reset.
+    /// * [Solver::set_diversity_memory] is disabled and no solutions are
+    ///   remembered, since each remembered solution changes the weights of
+    ///   later solves.
+    ///
+    /// Hashing never affects the choices of the solver, see
+    /// [Solver::set_seed], and neither does the candidate cache or any other
+    /// setting once the seed is fixed. The solver cannot check the code it is
+    /// given, so this also assumes that the wavefunction and any bias field,
+    /// tiebreak noise or dynamic constraint are deterministic, and that a
+    /// solve is not stopped through [Solver::solve_cancellable].
+    pub fn is_reproducible(&self) -> bool {
+        self.seed.is_some() && self.chooser.is_none() && !self.diversity && self.diversity_memory.is_empty()
+    }
+
+    /// Sets how strongly the solver prefers to collapse cells next to cells
+    /// that are already collapsed.
+    ///
+    /// The score of each cell under the [SelectionStrategy] is reduced by
+    /// `weight` times the number of its [Layout::neighbor_coords] that are
+    /// collapsed, and the cell with the lowest score is collapsed next. At a
+    /// weight of 0, the default, the strategy alone decides. Higher weights
+    /// make the layout grow outward from the collapsed regions rather than
+    /// being scattered across the layout. Cells with no possibilities left
+    /// are still always chosen first.
+    pub fn set_coherence_weight(&mut self, weight: f64) {
+        self.coherence_weight = weight;
+    }
+
+    /// Sets how important each value is to resolve early, to steer the order
+    /// in which cells are collapsed toward key features such as rare
+    /// landmarks.
+    ///
+    /// The score of each cell under the [SelectionStrategy] is divided by the
+    /// largest importance among its possibilities, so a cell that may still
+    /// become an important value is collapsed before equally uncertain cells
+    /// that may not. Values without an importance, or with an importance that
+    /// is not positive, count as 1. Scaling the terms of the entropy itself
+    /// would barely affect rare values, since their low probability makes
+    /// their share of the entropy small.
+    ///
+    /// This only changes the order in which cells are collapsed, not the
+    /// probability of any value being chosen, see [Solver::set_bias_field]
+    /// for that.
+    pub fn set_value_importance(&mut self, importance: HashMap<W::V, f64>) {
+        self.value_importance = importance;
+    }
+
+    /// Sets whether the solver keeps its candidate cells in a priority queue.
+    ///
+    /// When enabled, the default, [Solver::solve] and [Solver::solve_greedy]
+    /// keep the uncollapsed cells in a heap ordered by their score under the
+    /// [SelectionStrategy]. After each collapse, only the cells reported by
+    /// [Layout::take_changed_cells] are rescored, rather than rescanning every
+    /// cell of the layout to choose the next one. Layouts that do not report
+    /// their changes are rescanned anyway, and the heap is not used while a
+    /// coherence weight is set, since collapsing a cell also changes the
+    /// scores of its neighbors. Disabling the cache falls back to scanning
+    /// every cell, which can be useful for debugging.
+    ///
+    /// Ties are broken at random either way, but not in the same way, so the
+    /// same seed produces different results with and without the cache.
+    pub fn set_candidate_cache(&mut self, enabled: bool) {
+        self.candidate_cache = enabled;
+    }
+
+    /// Sets whether the solver undoes failed branches of its search through
+    /// [Layout::checkpoint] and [Layout::restore], rather than trying each
+    /// branch on a clone of the layout.
+    ///
+    /// This is enabled by default, and only has an effect for layouts that
+    /// support checkpoints, such as [Grid]. Restoring a checkpoint only copies
+    /// back the cells that were borrowed mutably since, while a clone copies
+    /// every cell for every value tried. Scanning the candidates of a layout
+    /// borrows every uncollapsed cell, so the savings are smallest when the
+    /// candidate cache is not used. The search itself is the same either way,
+    /// so a seeded solve gives the same result with and without it. The
+    /// `journal` benchmark compares both on a hard sudoku.
+    pub fn set_journaling(&mut self, enabled: bool) {
+        self.journaling = enabled;
+    }
+
+    /// Sets whether cells are collapsed in the order that [Layout::cells]
+    /// iterates them, rather than by the [SelectionStrategy].
+    ///
+    /// When enabled, the next cell to collapse is always the first uncollapsed
+    /// one, which for a [Grid] is row major order. Values are still chosen by
+    /// the [ValueOrder], so the output still varies, but fills in a
+    /// predictable pattern with a different texture than entropy driven
+    /// collapse. Cells with no possibilities left are still chosen first, so
+    /// contradictions are found immediately. The coherence weight and the
+    /// candidate cache are not used while this is enabled.
+    pub fn set_scanline_order(&mut self, enabled: bool) {
+        self.scanline = enabled;
+    }
+
+    /// Sets whether the solver collapses every cell tied for the lowest
+    /// score in one step, rather than one cell per step. This is off by
+    /// default.
+    ///
+    /// When enabled, each step collapses all selectable cells with the same
+    /// score under the [SelectionStrategy] as the chosen cell, without
+    /// choosing again between them. This saves a selection per cell, which
+    /// can be much faster on large uniform layouts. Each collapse of the batch
+    /// is still propagated before the next cell's value is chosen, since
+    /// propagation never changes a collapsed cell and so could not reject two
+    /// incompatible values placed together. Cells of the batch that were
+    /// collapsed by propagation are skipped.
+    ///
+    /// This changes the character of the output, since cells that would have
+    /// been chosen in between are not. Batches tend to run into more
+    /// contradictions, and when one fails the step is retried as a regular
+    /// single cell collapse, so a solve may need more backtracking. No
+    /// solutions are lost either way.
+    pub fn set_batch_collapse(&mut self, enabled: bool) {
+        self.batch_collapse = enabled;
+    }
+
+    /// Sets whether [Solver::solve] compacts its copy of the initial state
+    /// before searching.
+    ///
+    /// The search clones the layout for every collapse, and a clone keeps the
+    /// capacity of the possibilities it was cloned from. After propagating
+    /// many initial collapses, calling [Layout::compact] first releases the
+    /// capacity of the removed possibilities, which reduces the memory used
+    /// and the cost of each clone. This is only a memory optimization, and is
+    /// disabled by default. The stored initial state is not changed.
+    pub fn set_compact_layout(&mut self, enabled: bool) {
+        self.compact = enabled;
+    }
+
+    /// Sets whether the effects of collapses are memoized, for wavefunctions
+    /// where [Wavefunction::is_deterministic] returns true.
+    ///
+    /// When enabled, the neighbors of each collapsed cell are recorded before
+    /// and after [Wavefunction::collapse] is called. If the same value is
+    /// later collapsed at the same coordinate with the same neighbors, the
+    /// recorded result is written to the neighbors instead of calling the
+    /// wavefunction again. This is disabled by default, since the memo is
+    /// kept between solves and grows without bound with every distinct
+    /// neighborhood that is seen. It pays off for expensive wavefunctions
+    /// that revisit the same states, such as when backtracking heavily or
+    /// solving the same problem repeatedly. Disabling this also clears the
+    /// memo. It has no effect on other wavefunctions.
+    pub fn set_collapse_memo(&mut self, enabled: bool) {
+        self.collapse_memo = enabled;
+        if !enabled {
+            self.memo = HashMap::new();
+        }
+    }
+
+    /// Sets whether [Solver::solve] splits the layout into independent groups
+    /// of cells and solves each group separately.
+    ///
+    /// The groups are the connected components of the uncollapsed cells, where
+    /// cells are connected by [Wavefunction::coupled_cells]. Since no collapse
+    /// in one group can affect another, backtracking in one group never undoes
+    /// the work done in the others, which can make large sparse problems much
+    /// faster to solve.
+    ///
+    /// The groups are solved one after another on the calling thread, not in
+    /// parallel. Each group is searched with the state of the solver itself,
+    /// its random number generator, solution path, backtrack count and
+    /// callbacks, which are shared across the groups so that a seeded solve
+    /// stays reproducible and the callbacks see every collapse in order.
+    ///
+    /// This requires the constraints of the wavefunction to be local: collapsing
+    /// a cell must only change the cells reported by
+    /// [Wavefunction::coupled_cells]. [Standard2D](crate::Standard2D) reports
+    /// the neighbors its adjacency rules constrain. If the wavefunction does
+    /// not report its coupled cells, the layout is solved as a whole. When a group has no
+    /// solution, [Solver::solve_streaming] does not send undo events for the
+    /// groups solved before it.
+    pub fn set_partition_solving(&mut self, enabled: bool) {
+        self.partition_solving = enabled;
+    }
+
+    /// Sets whether the solver remembers its solutions, and steers later solves
+    /// away from them to produce more varied output.
+    ///
+    /// While enabled, each solution found by [Solver::solve] is remembered.
+    /// When choosing a value for a cell, the solver looks at the remembered
+    /// solutions that agree with every cell collapsed so far, since continuing
+    /// to follow them would generate the same output again. The weight of each
+    /// value is divided by one more than the number of those solutions that
+    /// have that value in the cell.
+    ///
+    /// Every remembered solution is a full copy of the layout, and each choice
+    /// compares the current layout against all of them, so both memory use and
+    /// the cost of solving grow with the number of solutions remembered. Use
+    /// [Solver::clear_diversity_memory] to forget them. Disabling the memory
+    /// stops new solutions from being remembered, but keeps penalizing the
+    /// ones already remembered.
+    pub fn set_diversity_memory(&mut self, enabled: bool) {
+        self.diversity = enabled;
+    }
+
+    /// Forgets every solution remembered by [Solver::set_diversity_memory].
+    pub fn clear_diversity_memory(&mut self) {
+        self.diversity_memory.clear();
+    }
+
+    /// Sets the value used by [Solver::solve_greedy] for cells that have run
+    /// out of possibilities.
+    ///
+    /// If no fallback value is set, a dead cell is instead collapsed to one of
+    /// the possibilities it had in the initial state.
+    pub fn set_fallback_value(&mut self, value: W::V) {
+        self.fallback = Some(value);
+    }
+
+    /// Sets a field of factors that bias which value a cell collapses to.
+    ///
+    /// When the solver chooses a value for the cell at a coordinate, the
+    /// weight of each possibility is multiplied by the factor the field
+    /// returns for that value. Values missing from the returned map keep their
+    /// weight. This can be used to couple the solver with procedural noise,
+    /// such as an elevation map, so that the output follows a large scale
+    /// gradient.
+    ///
+    /// The field only affects the probability of each value being chosen, not
+    /// whether it is valid. Possibilities with a factor of zero are still tried
+    /// if all others fail. Factors that are infinite or NaN are ignored, as if
+    /// they were missing, and weights that a factor would make too large to
+    /// sum are capped.
+    pub fn set_bias_field(&mut self, field: impl Fn(Coord<W>) -> HashMap<W::V, f64> + 'static) {
+        self.bias_field = Some(Box::new(field));
+    }
+
+    /// Adds a constraint that is applied to the working layout during every
+    /// solve, in addition to the wavefunction's own propagation.
+    ///
+    /// Constraints are applied in the order they were added: once to the copy
+    /// of the initial state that a solve starts from, and again after every
+    /// collapse, after [Wavefunction::collapse] has propagated it. This allows
+    /// rules to be layered on at runtime, such as forbidding a value, without
+    /// modifying the wavefunction. A constraint that removes every
+    /// possibility from a cell causes the solver to backtrack like any other
+    /// contradiction. The stored initial state is not changed.
+    pub fn add_dynamic_constraint(&mut self, constraint: DynamicConstraint<W>) {
+        self.dynamic_constraints.push(constraint);
+    }
+
+    /// Sets the order in which the possibilities of a cell are tried. The
+    /// default is [ValueOrder::WeightedRandom].
+    pub fn set_value_order(&mut self, order: ValueOrder) {
+        self.value_order = order;
+    }
+
+    /// Sets the [Chooser] that picks the order in which the possibilities of
+    /// a cell are tried, in place of the weighted random choice driven by
+    /// [Solver::set_seed].
+    ///
+    /// The chooser is given the weights of the possibilities after the
+    /// [Solver::set_bias_field] factors and diversity adjustments, rounded up
+    /// to whole numbers. [ValueOrder::LeastConstraining] and
+    /// [Solver::solve_toward] still reorder the chosen values afterwards.
+    pub fn set_chooser(&mut self, chooser: impl Chooser<W::V> + 'static) {
+        self.chooser = Some(Box::new(chooser));
+    }
+
+    /// Sets how the next cell to collapse is chosen. The default is
+    /// [SelectionStrategy::MinEntropy].
+    pub fn set_selection_strategy(&mut self, strategy: SelectionStrategy) {
+        self.selection_strategy = strategy;
+    }
+
+    /// Sets the number of backtracks each strategy is allowed during
+    /// [Solver::solve_adaptive]. The default is 1000.
+    pub fn set_adaptive_backtrack_budget(&mut self, budget: u32) {
+        self.adaptive_budget = budget;
+    }
+
+    /// Checks the wavefunction's initial state for mistakes that would make
+    /// every solve fail.
+    ///
+    /// Cells start out uncollapsed without any possibilities, so forgetting
+    /// to add possibilities to the layout makes [Solver::solve] return None
+    /// without explanation. This returns [SetupError::NoPossibilities] if
+    /// every cell of the wavefunction's initial state is in that state. A
+    /// layout with no cells at all is not an error. Cells emptied by the
+    /// propagation of [Solver::collapse_initial] are contradictions rather
+    /// than setup mistakes, and are not reported.
+    pub fn validate_setup(&self) -> Result<(), SetupError<Coord<W>>> {
+        let mut layout = self.wavefunction.get_initial_state().clone();
+        let mut first_empty = None;
+        for (coord, cell) in layout.cells() {
+            match cell {
+                Cell::Uncollapsed(possibilities) if possibilities.is_empty() => {
+                    first_empty.get_or_insert(coord);
+                }
+                _ => return Ok(()),
+            }
+        }
+        match first_empty {
+            Some(coord) => Err(SetupError::NoPossibilities(coord)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the reason the last solve stopped before completing its
+    /// search, or None if it found a solution or proved there is none.
+    pub fn get_stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Returns the strategy that found the solution during the last call to
+    /// [Solver::solve_adaptive], or None if it did not find one.
+    pub fn get_adaptive_strategy(&self) -> Option<SelectionStrategy> {
+        self.adaptive_strategy
+    }
+
     /// Modify the initial [Layout] by collapsing a cell.
     ///
     /// This will internally call the wavefunction's collapse method to ensure
     /// that the wavefunction's rules and constraints are not violated. This
     /// method is preferred to manually modifying the layout if the wavefunction
     /// permits access.
-    pub fn collapse_initial(&mut self, coord: <W::L as Layout<W::V>>::Coordinate, value: W::V) {
+    pub fn collapse_initial(&mut self, coord: Coord<W>, value: W::V) {
+        self.initial_collapses.push((coord.clone(), value));
         self.initial_state.collapse(&coord, value);
         self.wavefunction
             .collapse(&mut self.initial_state, coord, value);
     }
 
+    /// Modify the initial [Layout] by restricting the cell at `coord` to the
+    /// values in `allowed`, without collapsing it.
+    ///
+    /// Every other possibility is removed from the cell, so the solver can
+    /// only ever collapse it to one of the allowed values. Propagation only
+    /// removes possibilities, so the restriction holds for the whole solve.
+    /// It is not propagated to the neighbors until the cell is collapsed. A
+    /// collapsed cell is not changed, and leaving a cell without any
+    /// possibilities makes the problem unsolvable.
+    ///
+    /// Unlike the givens of [Solver::collapse_initial], restrictions are kept
+    /// by [Solver::diagnose_givens], and they are part of a [Recipe].
+    pub fn restrict_initial(&mut self, coord: Coord<W>, allowed: &HashSet<W::V>) {
+        self.initial_state.restrict_region(vec![coord.clone()], allowed);
+        self.restrictions.push((coord, allowed.clone()));
+    }
+
+    /// Modify the initial [Layout] by collapsing hidden singles, and returns
+    /// how many cells were collapsed.
+    ///
+    /// A hidden single is a value that only one cell of a group can still
+    /// take, so that cell must be that value, even if it has other
+    /// possibilities. Each group is a set of cells that must all have
+    /// different values, such as a row, column, or box of a sudoku. The
+    /// wavefunction does not describe its constraints in this form, so the
+    /// groups have to be supplied by the caller. Values that are already the
+    /// value of a collapsed cell in the group are ignored.
+    ///
+    /// Each hidden single is collapsed and propagated through the
+    /// wavefunction, which can reveal more of them, so the groups are checked
+    /// again until none are left. This is a standard technique of human sudoku
+    /// solvers, and running it before [Solver::solve] can greatly reduce
+    /// the search. The collapses are not recorded as givens.
+    pub fn propagate_hidden_singles(&mut self, groups: &[Vec<Coord<W>>]) -> usize {
+        let mut collapsed = 0;
+        loop {
+            let mut found = None;
+            for group in groups {
+                let mut cells: HashMap<W::V, Vec<&Coord<W>>> = HashMap::new();
+                let mut placed = HashSet::new();
+                for coord in group {
+                    match self.initial_state.get_cell(coord) {
+                        Some(Cell::Collapsed(value)) => {
+                            placed.insert(*value);
+                        }
+                        Some(Cell::Uncollapsed(possibilities)) => {
+                            for value in possibilities.keys() {
+                                cells.entry(*value).or_default().push(coord);
+                            }
+                        }
+                        None => {}
+                    }
+                }
+                found = cells
+                    .into_iter()
+                    .filter(|(value, cells)| cells.len() == 1 && !placed.contains(value))
+                    .map(|(value, cells)| (cells[0].clone(), value))
+                    .min_by_key(stable_hash);
+                if found.is_some() {
+                    break;
+                }
+            }
+
+            let Some((coord, value)) = found else {
+                return collapsed;
+            };
+            self.initial_state.collapse(&coord, value);
+            self.wavefunction
+                .collapse(&mut self.initial_state, coord, value);
+            collapsed += 1;
+        }
+    }
+
+    /// Modify the initial [Layout] by collapsing many cells at once.
+    ///
+    /// Every cell is collapsed first, and only then is each collapse
+    /// propagated through the wavefunction, in the order given. Propagation
+    /// then never removes possibilities from cells that are about to be
+    /// collapsed anyway, which saves work when loading many givens, such as a
+    /// nearly filled puzzle. For wavefunctions whose propagation only removes
+    /// possibilities from uncollapsed cells based on the collapsed value, the
+    /// result is the same as calling [Solver::collapse_initial] for each
+    /// given. Wavefunctions that inspect the rest of the layout while
+    /// propagating may see a different state.
+    pub fn collapse_initial_batch(&mut self, givens: &[(Coord<W>, W::V)]) {
+        for (coord, value) in givens {
+            self.initial_collapses.push((coord.clone(), *value));
+            self.initial_state.collapse(coord, *value);
+        }
+        for (coord, value) in givens {
+            self.wavefunction
+                .collapse(&mut self.initial_state, coord.clone(), *value);
+        }
+    }
+
+    /// Modify the initial [Layout] by collapsing a cell to the possibility
+    /// that the fewest collapsed cells of the initial state have, and returns
+    /// the value chosen.
+    ///
+    /// Calling this for many cells spreads the values out as evenly as the
+    /// possibilities allow, for generation that should use every value. Ties
+    /// go to the value with the largest weight, and then to a fixed order of
+    /// the values. The collapse is made with [Solver::collapse_initial], so it
+    /// is propagated. The collapsed cells are counted anew on each call, which
+    /// visits every cell. Returns None, leaving the layout unchanged, if the
+    /// cell is out of bounds, already collapsed, or has no possibilities.
+    pub fn collapse_rarest(&mut self, coord: Coord<W>) -> Option<W::V> {
+        let cell = self.initial_state.get_cell(&coord)?;
+        if cell.is_collapsed() {
+            return None;
+        }
+        let possibilities = cell.get_possibilities();
+        let mut counts: HashMap<W::V, usize> = possibilities.keys().map(|value| (*value, 0)).collect();
+        for (_, cell) in self.initial_state.cells() {
+            if let Some(count) = cell.get_value().and_then(|value| counts.get_mut(&value)) {
+                *count += 1;
+            }
+        }
+        let value = possibilities
+            .into_iter()
+            .min_by_key(|(value, weight)| (counts[value], Reverse(*weight), stable_hash(value)))
+            .map(|(value, _)| value)?;
+        self.collapse_initial(coord, value);
+        Some(value)
+    }
+
+    /// Generate a solution that matches the collapsed cells of `target` as
+    /// closely as the wavefunction's constraints allow.
+    ///
+    /// Whenever the solver collapses a cell, the value of the target at that
+    /// coordinate is tried first, if it is still a possibility, and the other
+    /// values are only tried when it fails. Uncollapsed cells of the target
+    /// express no preference. This turns a rough sketch into a valid layout
+    /// that follows it, like denoising an image under constraints.
+    ///
+    /// Cells that can no longer take their target value are collapsed after
+    /// all others, so that the cells around them match the target first and
+    /// the deviations stay where the constraints require them. The closeness
+    /// is still best effort, not optimal. Which cells deviate depends on the
+    /// order in which the solver collapses them, so some solution may match
+    /// the target in more cells than the one returned.
+    pub fn solve_toward(&mut self, target: &W::L) -> Option<W::L> {
+        self.target = Some(target.clone());
+        let result = self.solve();
+        self.target = None;
+        result
+    }
+
     /// Generate a solution to the wavefunction using its current initial
     /// conditions.
     ///
@@ -60,80 +1085,1501 @@ impl<W: Wavefunction> Solver<W> {
     /// This does not modify the initial conditions of the Layout. The function
     /// can be called again and will generate another possibly different result,
     /// if the wavefunction's constraints do not force a unique solution.
+    ///
+    /// A layout without any cells, such as a [Grid] with a size of zero, is
+    /// already solved, so it is returned as it is, without any collapses or
+    /// backtracks. [Solver::count_solutions] counts it as a single solution.
+    /// Layouts that are a single row or column need no special handling.
     pub fn solve(&mut self) -> Option<W::L> {
-        let mut layout = self.initial_state.clone();
+        self.try_solve().ok()
+    }
+
+    /// Generate a solution like [Solver::solve], but report why none was
+    /// found.
+    ///
+    /// If the search was completed, the error is [SolveError::Contradiction]
+    /// with the last cell that the search left without any possibilities.
+    /// Every branch of the search ends at such a cell, so this is the cell
+    /// that ruled out the final branch, which is often near the source of
+    /// the conflict but not necessarily at it. A solve stopped by its
+    /// backtrack limit or through [Solver::solve_cancellable] returns
+    /// [SolveError::Exhausted] or [SolveError::Cancelled] instead.
+    pub fn try_solve(&mut self) -> Result<W::L, SolveError<Coord<W>>> {
+        self.backtracks = 0;
+        self.stop_reason = None;
+        self.contradiction = None;
+        self.path.clear();
+        self.entropy_trace.clear();
+        let result = self.solve_partitioned();
+        if self.diversity {
+            if let Some(layout) = &result {
+                self.diversity_memory.push(layout.clone());
+            }
+        }
+        let backtracks = self.backtracks;
+        match (result, self.stop_reason) {
+            (Some(layout), _) => Ok(layout),
+            (None, Some(StopReason::BacktrackLimit)) => Err(SolveError::Exhausted { backtracks }),
+            (None, Some(StopReason::Cancelled)) => Err(SolveError::Cancelled { backtracks }),
+            (None, None) => {
+                let coord = self
+                    .contradiction
+                    .take()
+                    .expect("a failed search ends at a cell without possibilities");
+                Err(SolveError::Contradiction { coord, backtracks })
+            }
+        }
+    }
+
+    /// Generate a solution like [Solver::solve], along with how freely each
+    /// of its cells was chosen.
+    ///
+    /// The confidence of a cell that the solver collapsed is one over the
+    /// number of possibilities the cell had left when it was collapsed, so a
+    /// cell with a single possibility left is 1.0 and a choice between four
+    /// values is 0.25. Cells that were collapsed in the initial state or by
+    /// propagation, and so were never chosen, are 1.0. Every cell of the
+    /// solution has a confidence.
+    ///
+    /// The counts are found by replaying [Solver::last_solution_path] on the
+    /// initial state, so they reflect the successful branch of the search
+    /// only.
+    pub fn solve_with_confidence(&mut self) -> Option<(W::L, ConfidenceMap<W>)> {
+        let mut solution = self.solve()?;
+        let mut confidence: HashMap<_, _> = solution.cells().map(|(coord, _)| (coord, 1.0)).collect();
+        let mut layout = self.working_layout();
+        if self.compact {
+            layout.compact();
+        }
+        for (coord, value) in self.path.clone() {
+            let count = layout.get_cell(&coord).map_or(1, |cell| cell.get_possibilities().len());
+            confidence.insert(coord.clone(), 1.0 / count.max(1) as f64);
+            layout.collapse(&coord, value);
+            self.propagate(&mut layout, &coord, value);
+        }
+        Some((solution, confidence))
+    }
+
+    /// Completes a layout that a solve returned early because of
+    /// [Solver::set_entropy_floor], ignoring the floor.
+    ///
+    /// The search continues from the layout as if it had not stopped, so the
+    /// result is a full solution, or None if the remaining cells cannot be
+    /// collapsed. The backtrack count and solution path are those of this
+    /// call only.
+    pub fn finalize(&mut self, layout: W::L) -> Option<W::L> {
         self.backtracks = 0;
+        self.stop_reason = None;
+        self.path.clear();
+        self.entropy_trace.clear();
+        let floor = self.entropy_floor.take();
+        let result = self.solve_from(layout);
+        self.entropy_floor = floor;
+        result
+    }
+
+    /// Solves the initial state, as a whole or in independent groups if
+    /// partition solving is enabled.
+    fn solve_partitioned(&mut self) -> Option<W::L> {
+        let mut layout = self.working_layout();
+        if self.compact {
+            layout.compact();
+        }
+        if !self.partition_solving {
+            return self.solve_from(layout);
+        }
+        let Some(components) = self.components(&mut layout) else {
+            return self.solve_from(layout);
+        };
+
+        // Each component is solved on top of the solutions of the previous ones
+        let mut result = Some(layout);
+        for component in components {
+            let Some(layout) = result else {
+                break;
+            };
+            self.region = Some(component);
+            result = self.solve_from(layout);
+        }
+        self.region = None;
+        result
+    }
+
+    /// Solves the layout, or only the current region of it if there is one.
+    fn solve_from(&mut self, mut layout: W::L) -> Option<W::L> {
+        if self.below_entropy_floor(&mut layout) {
+            return Some(layout);
+        }
+
         // Choose a cell at random to collapse
-        let new_coord = match self.next_coord(&mut layout) {
+        let mut heap = None;
+        let new_coord = match self.next_candidate(&mut layout, &mut heap) {
             Some(value) => value,
             None => return Some(layout),
         };
 
         // Collapse the cell with the wavefunction
-        let result = self.collapse(&mut layout, &new_coord);
-
-        return result;
+        self.collapse(&mut layout, &mut heap, &new_coord)
     }
 
-    /// Internal recursive collapse function.
+    /// Groups the uncollapsed cells of the layout into sets that are not
+    /// coupled to each other, see [Solver::set_partition_solving].
     ///
-    /// Takes a layout and a coordinate of the next cell to collapse.
-    /// Returns a Layout if successful or None if no solution could be found.
-    fn collapse(
-        &mut self,
-        layout: &mut W::L,
-        coord: &<W::L as Layout<W::V>>::Coordinate,
-    ) -> Option<W::L> {
-        (self.on_tile_placement)(layout);
-        // For each possibility in the chosen cell, try solving with that configuration
-        let possibilities = layout.get_cell_mut(&coord).unwrap().get_possibilities();
-        for possibility in WeightedIterator::new(possibilities) {
-            // Clone cells to test possability
-            let mut new_layout = layout.clone();
-
-            // Modify cell
-            let new_cell = new_layout.get_cell_mut(&coord).unwrap();
-            *new_cell = Cell::Collapsed(possibility.clone());
-
-            // Propagate this proposed collapse
-            self.wavefunction
-                .collapse(&mut new_layout, coord.clone(), possibility.clone());
-
-            let new_coord = match self.next_coord(&mut new_layout) {
-                Some(value) => value,
-                None => return Some(new_layout),
-            };
+    /// Returns None if the wavefunction does not report its coupled cells.
+    fn components(&self, layout: &mut W::L) -> Option<Vec<HashSet<Coord<W>>>> {
+        let candidates: Vec<_> = layout.candidates().map(|(coord, _)| coord).collect();
+        let mut edges: HashMap<_, Vec<_>> = candidates
+            .iter()
+            .map(|coord| (coord.clone(), Vec::new()))
+            .collect();
+        for coord in &candidates {
+            for coupled in self.wavefunction.coupled_cells(layout, coord)? {
+                if coupled == *coord || !edges.contains_key(&coupled) {
+                    continue;
+                }
+                edges.get_mut(coord).unwrap().push(coupled.clone());
+                edges.get_mut(&coupled).unwrap().push(coord.clone());
+            }
+        }
 
-            // Recurse
-            let result = self.collapse(&mut new_layout, &new_coord);
-            if result.is_some() {
-                return result;
+        let mut components = Vec::new();
+        let mut unvisited: HashSet<_> = candidates.iter().cloned().collect();
+        for coord in candidates {
+            if !unvisited.remove(&coord) {
+                continue;
             }
+            let mut component = HashSet::new();
+            let mut stack = vec![coord];
+            while let Some(coord) = stack.pop() {
+                for coupled in &edges[&coord] {
+                    if unvisited.remove(coupled) {
+                        stack.push(coupled.clone());
+                    }
+                }
+                component.insert(coord);
+            }
+            components.push(component);
         }
-        self.backtracks += 1;
-        None
+        Some(components)
     }
 
-    /// Chooses the next coordinate to collapse by iterating through all
-    /// candidates and returning the one with the lowest entropy.
-    fn next_coord(
-        &self,
-        layout: &mut <W as Wavefunction>::L,
-    ) -> Option<<<W as Wavefunction>::L as Layout<<W as Wavefunction>::V>>::Coordinate> {
+    /// Generate a solution like [Solver::solve], while bounding how deep the
+    /// search recurses by committing to its progress every few collapses.
+    ///
+    /// The search collapses `increment` cells and then continues from the
+    /// partial layout it reached, without being able to backtrack into it.
+    /// If the search from a partial layout fails, the solve starts over from
+    /// the initial state with `increment` more cells between commitments,
+    /// until a solution is found or the horizon covers every uncollapsed
+    /// cell, at which point the search is the same as [Solver::solve]. So it
+    /// finds a solution whenever [Solver::solve] does.
+    ///
+    /// [Solver::solve] keeps a layout for every cell on its current path, as
+    /// clones for layouts that do not support [Layout::checkpoint], and
+    /// always a frame of the recursion, so its peak memory and stack use grow
+    /// with the number of cells. Here they grow with the horizon instead. The
+    /// cost is time: every round that fails repeats the work of the round
+    /// before, so problems that need to backtrack far, or a small
+    /// `increment`, can take many times longer than [Solver::solve]. Problems
+    /// that rarely backtrack finish in the first round at about the same
+    /// cost. An `increment` of 0 is treated as 1.
+    ///
+    /// The backtrack count and [Solver::set_max_backtracks] cover all rounds
+    /// together, and [Solver::last_solution_path] is the path of the
+    /// successful round. Partition solving is not used.
+    pub fn solve_iterative_deepening(&mut self, increment: usize) -> Option<W::L> {
+        self.backtracks = 0;
+        self.stop_reason = None;
+        self.contradiction = None;
+        let mut start = self.working_layout();
+        if self.compact {
+            start.compact();
+        }
+        let cells = start.candidates().count();
+        let increment = increment.max(1);
+        let mut horizon = increment;
+        let result = loop {
+            if let Some(solution) = self.solve_in_chunks(start.clone(), horizon) {
+                break Some(solution);
+            }
+            if self.stop_reason.is_some() || horizon >= cells {
+                break None;
+            }
+            horizon = horizon.saturating_add(increment);
+        };
+        self.depth_limit = None;
+        result
+    }
+
+    /// Searches the layout `horizon` collapses at a time, starting each
+    /// search from the partial layout the last one reached, see
+    /// [Solver::solve_iterative_deepening]. Returns None if any search fails.
+    fn solve_in_chunks(&mut self, mut layout: W::L, horizon: usize) -> Option<W::L> {
+        self.path.clear();
+        self.entropy_trace.clear();
+        loop {
+            let limit = self.path.len().saturating_add(horizon);
+            self.depth_limit = Some(limit);
+            layout = self.solve_from(layout)?;
+            // A search that stopped short of its limit finished the layout
+            if self.path.len() < limit {
+                return Some(layout);
+            }
+        }
+    }
+
+    /// Generate a solution like [Solver::solve], while sending each collapse
+    /// the solver makes through the channel as it happens.
+    ///
+    /// This allows another thread to render the solve as it progresses. A
+    /// [SolveEvent::Collapse] is sent each time the solver collapses a cell.
+    /// When the solver backtracks out of a collapse, a [SolveEvent::Undo] is
+    /// sent for that cell. Events are sent in the order they happen, so
+    /// replaying them in order always reflects the solver's current path, and
+    /// after a successful solve the cells still collapsed are exactly those
+    /// the solver collapsed in the solution. If no solution is found, every
+    /// collapse has been undone.
+    ///
+    /// Only the cells chosen by the solver are sent, not the cells collapsed
+    /// in the initial state. Sending stops silently if the receiver is
+    /// dropped.
+    pub fn solve_streaming(&mut self, tx: Sender<SolveEvent<Coord<W>, W::V>>) -> Option<W::L> {
+        self.event_sender = Some(tx);
+        let result = self.solve();
+        self.event_sender = None;
+        result
+    }
+
+    /// Generate a solution like [Solver::solve], stopping early if `cancel` is
+    /// set.
+    ///
+    /// This allows a solve to be cancelled from another thread. The flag is
+    /// checked each time the solver chooses a value for a cell, so the solve
+    /// stops within one step of the flag being set. A step runs the
+    /// wavefunction's propagation of the chosen value, so a slow propagation
+    /// delays cancellation accordingly.
+    ///
+    /// When cancelled, None is returned and [Solver::get_stop_reason] returns
+    /// [StopReason::Cancelled]. The solver only reads the flag, and never sets
+    /// it.
+    ///
+    /// The same flag can be shared by solvers running on several threads, for
+    /// example to sample solutions in parallel. The caller sets the flag once
+    /// any attempt returns a solution, and every other attempt stops within
+    /// one step, rather than running its search to completion.
+    pub fn solve_cancellable(&mut self, cancel: Arc<AtomicBool>) -> Option<W::L> {
+        self.cancel = Some(cancel);
+        let result = self.solve();
+        self.cancel = None;
+        result
+    }
+
+    /// Generate a solution by trying several selection strategies in turn.
+    ///
+    /// Some problems are solved quickly with one [SelectionStrategy] but not
+    /// another. This tries [SelectionStrategy::MinEntropy],
+    /// [SelectionStrategy::MinimumRemainingValues] and
+    /// [SelectionStrategy::Random] in that order, giving each the number of
+    /// backtracks set by [Solver::set_adaptive_backtrack_budget]. The first
+    /// solution found is returned, and the strategy that found it is available
+    /// from [Solver::get_adaptive_strategy].
+    ///
+    /// If a strategy finishes its search within its budget without finding a
+    /// solution, the problem has no solution and None is returned without
+    /// trying the remaining strategies. The selection strategy set on the
+    /// solver is not changed.
+    pub fn solve_adaptive(&mut self) -> Option<W::L> {
+        let strategy = self.selection_strategy;
+        let limit = self.backtrack_limit;
+        self.adaptive_strategy = None;
+        self.backtrack_limit = Some(self.adaptive_budget);
+
+        let mut result = None;
+        for candidate in [
+            SelectionStrategy::MinEntropy,
+            SelectionStrategy::MinimumRemainingValues,
+            SelectionStrategy::Random,
+        ] {
+            self.selection_strategy = candidate;
+            result = self.solve();
+            if result.is_some() {
+                self.adaptive_strategy = Some(candidate);
+                break;
+            }
+            if self.stop_reason != Some(StopReason::BacktrackLimit) {
+                break;
+            }
+        }
+
+        self.selection_strategy = strategy;
+        self.backtrack_limit = limit;
+        result
+    }
+
+    /// Find up to `k` solutions with the highest likelihood, ordered from
+    /// most to least likely.
+    ///
+    /// The likelihood of a solution is the product of the probabilities of
+    /// each value the solver chose, where the probability of a value is its
+    /// weight divided by the total weight of the cell's possibilities at the
+    /// time it was collapsed. Cells collapsed in the initial state, or by the
+    /// wavefunction's propagation, do not contribute. The likelihood is
+    /// returned alongside each layout.
+    ///
+    /// Rather than searching at random, this performs a best-first search over
+    /// the collapse tree, always expanding the most likely partial layout.
+    /// Every partial layout that is reached is kept in memory until it is
+    /// expanded, so this can explore and store a very large number of
+    /// branches, especially when the weights are nearly uniform. It is
+    /// intended for small wavefunctions.
+    pub fn top_solutions(&mut self, k: usize) -> Vec<(W::L, f64)> {
+        let mut solutions = Vec::new();
+        let mut frontier = BinaryHeap::new();
+        if k > 0 {
+            frontier.push(Ranked {
+                score: 0.0,
+                layout: self.working_layout(),
+            });
+        }
+
+        while let Some(Ranked { score, mut layout }) = frontier.pop() {
+            let Some(coord) = self.next_coord(&mut layout) else {
+                solutions.push((layout, score.exp()));
+                if solutions.len() >= k {
+                    break;
+                }
+                continue;
+            };
+
+            let possibilities = layout.get_cell(&coord).unwrap().get_possibilities();
+            let total: usize = possibilities.values().sum();
+            // Equally likely layouts leave the heap in the order they entered
+            let mut possibilities: Vec<_> = possibilities.into_iter().collect();
+            possibilities.sort_by_cached_key(|(value, _)| stable_hash(value));
+            for (value, weight) in possibilities {
+                if weight == 0 {
+                    continue;
+                }
+                let mut new_layout = layout.clone();
+                new_layout.collapse(&coord, value);
+                self.propagate(&mut new_layout, &coord, value);
+                frontier.push(Ranked {
+                    score: score + (weight as f64 / total as f64).ln(),
+                    layout: new_layout,
+                });
+            }
+        }
+        solutions
+    }
+
+    /// Counts the distinct solutions of the initial state, stopping once `cap`
+    /// solutions have been found.
+    ///
+    /// Every branch of the search is explored rather than stopping at the first
+    /// solution, but solutions are only counted, not stored. Returns `cap` if
+    /// there are at least that many solutions, so a cap of 2 is enough to check
+    /// that a puzzle has a unique solution. Without a cap, the count can take
+    /// as long as the full search tree is large.
+    pub fn count_solutions(&mut self, cap: usize) -> usize {
+        let mut layout = self.working_layout();
+        self.backtracks = 0;
+        self.stop_reason = None;
+        if cap == 0 {
+            return 0;
+        }
+        let Some(coord) = self.next_coord(&mut layout) else {
+            return 1;
+        };
+        let mut count = 0;
+        self.count_from(&layout, &coord, cap, &mut count);
+        count
+    }
+
+    /// Internal recursive counting function for [Solver::count_solutions].
+    fn count_from(&mut self, layout: &W::L, coord: &Coord<W>, cap: usize, count: &mut usize) {
+        let possibilities = layout.get_cell(coord).unwrap().get_possibilities();
+        for possibility in possibilities.into_keys() {
+            let mut new_layout = layout.clone();
+            new_layout.collapse(coord, possibility);
+            self.propagate(&mut new_layout, coord, possibility);
+
+            match self.next_coord(&mut new_layout) {
+                Some(new_coord) => self.count_from(&new_layout, &new_coord, cap, count),
+                None => *count += 1,
+            }
+            if *count >= cap {
+                return;
+            }
+        }
+    }
+
+    /// Returns the possibilities that the wavefunction's propagation removes
+    /// from each cell when the cell at `coord` is collapsed to `value`.
+    ///
+    /// The collapse is applied to a clone of the initial state, including
+    /// its givens, and propagated once with [Wavefunction::collapse]. Every
+    /// other cell that lost a possibility is included with the values it
+    /// lost, and a cell that propagation collapsed counts as having lost all
+    /// of its other values. Dynamic constraints are not applied. Neither the
+    /// initial state nor the last solution is changed, this takes `&mut self`
+    /// only because [Wavefunction::collapse] does.
+    pub fn explain_collapse(&mut self, coord: Coord<W>, value: W::V) -> HashMap<Coord<W>, HashSet<W::V>> {
+        let possible = |cell: &Cell<W::V>| -> HashSet<W::V> {
+            match cell {
+                Cell::Collapsed(value) => HashSet::from([*value]),
+                Cell::Uncollapsed(possibilities) => possibilities.keys().copied().collect(),
+            }
+        };
+        let mut layout = self.initial_state.clone();
+        let before: HashMap<_, _> = layout.cells().map(|(coord, cell)| (coord, possible(cell))).collect();
+        layout.collapse(&coord, value);
+        self.wavefunction.collapse(&mut layout, coord.clone(), value);
+
+        let mut removed = HashMap::new();
+        for (cell_coord, cell) in layout.cells() {
+            if cell_coord == coord {
+                continue;
+            }
+            let after = possible(cell);
+            let lost: HashSet<_> = before[&cell_coord].difference(&after).copied().collect();
+            if !lost.is_empty() {
+                removed.insert(cell_coord, lost);
+            }
+        }
+        removed
+    }
+
+    /// Finds givens that must be removed to make an unsolvable initial state
+    /// solvable.
+    ///
+    /// The givens are the collapses made with [Solver::collapse_initial].
+    /// Starting from the wavefunction's initial state, they are added back one
+    /// at a time in the order they were made, and a given is left out if the
+    /// problem has no solution with it. The givens left out are returned, and
+    /// none of them could be added back without making the problem unsolvable
+    /// again, although another set of a different size may also work.
+    /// Returns an empty Vec if the initial state is already solvable, and
+    /// every given if the problem has no solution even without any.
+    ///
+    /// This is a debugging aid for puzzle authors, and it is potentially very
+    /// expensive, since the problem is solved again for every given. A
+    /// backtrack limit makes it faster, but solves that hit the limit are
+    /// treated as having no solution. The givens are restored afterwards.
+    pub fn diagnose_givens(&mut self) -> Vec<(Coord<W>, W::V)> {
+        if self.is_solvable() {
+            return Vec::new();
+        }
+
+        let initial_state = self.restricted_state();
+        let saved_state = std::mem::replace(&mut self.initial_state, initial_state);
+        let givens = std::mem::take(&mut self.initial_collapses);
+        let mut removed = Vec::new();
+        for (coord, value) in &givens {
+            let previous = self.initial_state.clone();
+            self.collapse_initial(coord.clone(), *value);
+            if !self.is_solvable() {
+                self.initial_state = previous;
+                self.initial_collapses.pop();
+                removed.push((coord.clone(), *value));
+            }
+        }
+
+        self.initial_state = saved_state;
+        self.initial_collapses = givens;
+        removed
+    }
+
+    /// The wavefunction's initial state with the restrictions of
+    /// [Solver::restrict_initial] applied, but none of the givens.
+    fn restricted_state(&self) -> W::L {
+        let mut layout = self.wavefunction.get_initial_state().clone();
+        for (coord, allowed) in &self.restrictions {
+            layout.restrict_region(vec![coord.clone()], allowed);
+        }
+        layout
+    }
+
+    /// Changes the given at `coord` to `value` and repairs the last solution
+    /// to match, instead of solving again from scratch.
+    ///
+    /// If `coord` was collapsed with [Solver::collapse_initial], its value is
+    /// replaced, otherwise the change is added as a new given. The collapses of
+    /// the last solution are then replayed on top of the new givens in their
+    /// original order, and only the cells whose value is no longer possible
+    /// are left for the solver to fill in. If those cells cannot be solved
+    /// together with the rest, the replayed collapses that removed
+    /// possibilities from them are released as well, and the repair is tried
+    /// again on the larger set of cells. Cells that do not depend on the
+    /// change keep their values, which is what makes this fast for small edits
+    /// in an editor.
+    ///
+    /// This is an approximation. The repaired layout is a valid solution, but
+    /// it can differ from the one a fresh solve would find, and it does not
+    /// necessarily change the fewest cells. If no set of released cells can be
+    /// solved, the new initial state is solved from scratch instead. Without a
+    /// previous solution, this is the same as [Solver::solve] after changing
+    /// the given.
+    pub fn resolve_after_change(&mut self, coord: Coord<W>, value: W::V) -> Option<W::L> {
+        let previous: Vec<_> = self
+            .path
+            .iter()
+            .filter(|(collapsed, _)| *collapsed != coord)
+            .cloned()
+            .collect();
+
+        // Rebuild the initial state with the changed given
+        match self.initial_collapses.iter_mut().find(|(given, _)| *given == coord) {
+            Some(given) => given.1 = value,
+            None => self.initial_collapses.push((coord.clone(), value)),
+        }
+        let mut initial_state = self.restricted_state();
+        for (given, given_value) in &self.initial_collapses {
+            initial_state.collapse(given, *given_value);
+            self.wavefunction
+                .collapse(&mut initial_state, given.clone(), *given_value);
+        }
+        self.initial_state = initial_state.clone();
+        let locked = collapsed_cells(&initial_state);
+
+        let mut released = HashSet::from([coord]);
+        loop {
+            let mut layout = initial_state.clone();
+            self.locked = locked.clone();
+            let mut kept = Vec::new();
+            let mut dependents = Vec::new();
+            for (collapsed, collapsed_value) in &previous {
+                if released.contains(collapsed) {
+                    continue;
+                }
+                match layout.get_cell(collapsed) {
+                    Some(Cell::Uncollapsed(possibilities)) if possibilities.contains_key(collapsed_value) => {}
+                    Some(Cell::Collapsed(current)) if current == collapsed_value => continue,
+                    _ => {
+                        released.insert(collapsed.clone());
+                        continue;
+                    }
+                }
+
+                let state = |layout: &W::L, coord: &Coord<W>| {
+                    layout.get_cell(coord).map(|cell| (cell.get_value(), cell.get_possibilities()))
+                };
+                let before: Vec<_> = released.iter().map(|coord| state(&layout, coord)).collect();
+                layout.collapse(collapsed, *collapsed_value);
+                self.propagate(&mut layout, collapsed, *collapsed_value);
+                if released.iter().zip(before).any(|(coord, before)| state(&layout, coord) != before) {
+                    dependents.push(collapsed.clone());
+                }
+                kept.push((collapsed.clone(), *collapsed_value));
+            }
+
+            self.initial_state = layout;
+            self.backtracks = 0;
+            self.stop_reason = None;
+            self.path = kept;
+            self.entropy_trace.clear();
+            let result = self.solve_partitioned();
+            self.initial_state = initial_state.clone();
+            if result.is_some() {
+                return result;
+            }
+            if dependents.is_empty() {
+                return self.solve();
+            }
+            released.extend(dependents);
+        }
+    }
+
+    /// Returns true if searching the initial state finds a solution.
+    fn is_solvable(&mut self) -> bool {
+        self.backtracks = 0;
+        self.stop_reason = None;
+        self.path.clear();
+        self.entropy_trace.clear();
+        self.solve_partitioned().is_some()
+    }
+
+    /// Returns true if the initial state determines a solution without making
+    /// any choices.
+    ///
+    /// Any cell with a single possibility is collapsed to it and propagated
+    /// through the wavefunction, until no such cells remain. If every cell is
+    /// then collapsed, the solution is fully determined by propagation, which
+    /// can be used to grade trivial puzzles. The propagation happens on a
+    /// copy, so the stored initial state is not changed. This takes `&mut self`
+    /// only because [Wavefunction::collapse] does.
+    pub fn is_already_determined(&mut self) -> bool {
+        let mut layout = self.initial_state.clone();
+        loop {
+            let singleton = layout.cells().find_map(|(coord, cell)| match cell {
+                Cell::Uncollapsed(possibilities) if possibilities.len() == 1 => {
+                    possibilities.keys().next().map(|value| (coord, *value))
+                }
+                _ => None,
+            });
+            let Some((coord, value)) = singleton else {
+                break;
+            };
+            layout.collapse(&coord, value);
+            self.wavefunction.collapse(&mut layout, coord, value);
+        }
+        let determined = layout.cells().all(|(_, cell)| cell.is_collapsed());
+        determined
+    }
+
+    /// Estimates how difficult the initial state is to solve, by how much
+    /// search beyond pure propagation it requires.
+    ///
+    /// If [Solver::is_already_determined] is true, the difficulty is
+    /// [Difficulty::Easy]. Otherwise the initial state is solved once, and the
+    /// difficulty is [Difficulty::Hard] if that took at least
+    /// [Difficulty::HARD_BACKTRACKS] backtracks and [Difficulty::Medium] if it
+    /// took fewer. The backtrack count of the solve is available afterwards
+    /// through [Solver::get_backtrack_count].
+    ///
+    /// The choices of a solve are random unless a seed is set, so the same
+    /// puzzle can be graded Medium by one call and Hard by another. Anything
+    /// that needs choices is at least Medium, however, so an Easy puzzle is
+    /// always graded easier than one that is not.
+    pub fn estimate_difficulty(&mut self) -> Difficulty {
+        if self.is_already_determined() {
+            self.backtracks = 0;
+            return Difficulty::Easy;
+        }
+        match self.solve() {
+            None => Difficulty::Unsolvable,
+            Some(_) if self.backtracks >= Difficulty::HARD_BACKTRACKS => Difficulty::Hard,
+            Some(_) => Difficulty::Medium,
+        }
+    }
+
+    /// Generate a layout without ever backtracking.
+    ///
+    /// This repeatedly collapses the lowest entropy cell and propagates the
+    /// result through the wavefunction, accepting whatever state that leaves
+    /// behind. When a cell has no possibilities left, it is collapsed to the
+    /// fallback value set with [Solver::set_fallback_value], or to one of its
+    /// initial possibilities if no fallback was set. Each cell is collapsed at
+    /// most once, so this always terminates after O(cells) collapses and is
+    /// the fastest way to fill a layout.
+    ///
+    /// Unlike [Solver::solve], the result is not guaranteed to satisfy the
+    /// wavefunction's constraints. Cells that received a fallback value may
+    /// conflict with their neighbors. It is intended for real time generation
+    /// where occasional imperfections are acceptable. If a dead cell had no
+    /// possibilities in the initial state and no fallback value is set,
+    /// generation stops and the partially collapsed layout is returned.
+    pub fn solve_greedy(&mut self) -> W::L {
+        let mut layout = self.working_layout();
+        self.backtracks = 0;
+        self.stop_reason = None;
+        let mut heap = None;
+        while let Some(coord) = self.next_candidate(&mut layout, &mut heap) {
+            (self.on_tile_placement)(&mut layout);
+            let value = match self.possibility_order(&mut layout, &coord).first().copied() {
+                Some(value) => value,
+                None => {
+                    let initial = self
+                        .initial_state
+                        .get_cell_mut(&coord)
+                        .unwrap()
+                        .get_possibilities();
+                    match self.fallback.or_else(|| WeightedIterator::new(initial, &mut self.rng).next()) {
+                        Some(value) => value,
+                        None => return layout,
+                    }
+                }
+            };
+
+            layout.collapse(&coord, value);
+            self.propagate(&mut layout, &coord, value);
+        }
+        layout
+    }
+
+    /// Collapse as many cells as possible without ever backtracking, leaving
+    /// the rest uncollapsed.
+    ///
+    /// Like [Solver::solve_greedy], the cell to collapse is chosen by the
+    /// [SelectionStrategy] and each cell is visited at most once. Each value
+    /// of the cell is tried in turn, and the first one that does not leave any
+    /// other cell without possibilities is kept. If every value of a cell
+    /// would cause a contradiction, the cell is left uncollapsed and skipped.
+    /// Once every remaining cell has been skipped, the partial layout is
+    /// returned.
+    ///
+    /// This never backtracks, so it always terminates, and it never returns a
+    /// contradiction unless the initial state already had one, in which case
+    /// it stops immediately. It may leave many cells uncollapsed, and their
+    /// remaining possibilities are not guaranteed to lead to a solution. Each
+    /// value is tried on a copy of the layout, which is the main cost on very
+    /// large layouts.
+    pub fn solve_until_stable(&mut self) -> W::L {
+        let mut layout = self.working_layout();
+        self.backtracks = 0;
+        self.stop_reason = None;
+        let mut heap = None;
+        let mut next = self.next_candidate(&mut layout, &mut heap);
+        while let Some(coord) = next.take() {
+            let Some(Cell::Uncollapsed(possibilities)) = layout.get_cell(&coord) else {
+                break;
+            };
+            if possibilities.is_empty() {
+                break;
+            }
+
+            (self.on_tile_placement)(&mut layout);
+            let mut accepted = None;
+            for value in self.possibility_order(&mut layout, &coord) {
+                let mut trial = layout.clone();
+                let mut trial_heap = heap.clone();
+                trial.collapse(&coord, value);
+                self.propagate(&mut trial, &coord, value);
+
+                // A contradiction is always chosen first
+                let trial_next = self.next_candidate(&mut trial, &mut trial_heap);
+                let dead = trial_next.as_ref().is_some_and(|next| {
+                    matches!(trial.get_cell(next), Some(Cell::Uncollapsed(p)) if p.is_empty())
+                });
+                if !dead {
+                    accepted = Some((trial, trial_heap, trial_next));
+                    break;
+                }
+            }
+
+            match accepted {
+                Some((trial, trial_heap, trial_next)) => {
+                    layout = trial;
+                    heap = trial_heap;
+                    next = trial_next;
+                }
+                None => {
+                    self.skipped.insert(coord);
+                    next = self.next_candidate(&mut layout, &mut heap);
+                }
+            }
+        }
+        self.skipped.clear();
+        layout
+    }
+
+    /// Internal recursive collapse function.
+    ///
+    /// Takes a layout and a coordinate of the next cell to collapse.
+    /// Returns a Layout if successful or None if no solution could be found.
+    fn collapse(
+        &mut self,
+        layout: &mut W::L,
+        heap: &mut Option<CandidateHeap<Coord<W>>>,
+        coord: &Coord<W>,
+    ) -> Option<W::L> {
+        (self.on_tile_placement)(layout);
+        if self.batch_collapse {
+            if let Some(result) = self.collapse_batch(layout, coord) {
+                return Some(result);
+            }
+            if self.stop_reason.is_some() {
+                return None;
+            }
+        }
+        let order = self.possibility_order(layout, coord);
+        if order.is_empty() {
+            self.contradiction = Some(coord.clone());
+        }
+        // For each possibility in the chosen cell, try solving with that configuration
+        for possibility in order {
+            if self.is_cancelled() {
+                self.stop_reason = Some(StopReason::Cancelled);
+                return None;
+            }
+
+            // Test the possibility in place if the layout can undo it, and on a
+            // clone otherwise. The heap always undoes its own updates.
+            let journaled = self.journaling && layout.checkpoint();
+            let mut copy = (!journaled).then(|| layout.clone());
+            let new_layout = match copy.as_mut() {
+                Some(copy) => copy,
+                None => &mut *layout,
+            };
+            let heap_checkpoint = heap.as_mut().map(CandidateHeap::checkpoint).is_some();
+            let Some(new_coord) = self.collapse_step(new_layout, heap, coord, possibility) else {
+                return Some(copy.unwrap_or_else(|| layout.clone()));
+            };
+
+            // Recurse
+            let result = self.collapse(new_layout, heap, &new_coord);
+            if result.is_some() {
+                return result;
+            }
+            if journaled {
+                layout.restore();
+            }
+            match heap.as_mut() {
+                Some(heap) if heap_checkpoint => heap.restore(),
+                // A heap built during the branch is rebuilt when next needed
+                _ => *heap = None,
+            }
+            self.undo_step(coord);
+            if self.stop_reason.is_some() {
+                return None;
+            }
+        }
+        self.backtracks += 1;
+        if let Some(on_backtrack) = &mut self.on_backtrack {
+            on_backtrack(self.path.len(), coord);
+        }
+        if let Some(limit) = self.get_backtrack_limit() {
+            if self.backtracks >= limit {
+                self.stop_reason = Some(StopReason::BacktrackLimit);
+            }
+        }
+        None
+    }
+
+    /// Collapses the cell at the coordinate to the value, propagates and
+    /// records the collapse, and returns the next cell to collapse, or None
+    /// if the layout is finished.
+    ///
+    /// This is kept out of [Solver::collapse] so that its temporaries do not
+    /// take up stack space in every frame of the recursion.
+    fn collapse_step(
+        &mut self,
+        layout: &mut W::L,
+        heap: &mut Option<CandidateHeap<Coord<W>>>,
+        coord: &Coord<W>,
+        value: W::V,
+    ) -> Option<Coord<W>> {
+        *layout.get_cell_mut(coord).unwrap() = Cell::Collapsed(value);
+        self.propagate(layout, coord, value);
+        if let Some(on_step) = &mut self.on_step {
+            on_step(layout, coord);
+        }
+        self.send_event(SolveEvent::Collapse(coord.clone(), value));
+        self.path.push((coord.clone(), value));
+        if self.record_entropy {
+            self.entropy_trace.push(layout.total_entropy());
+        }
+
+        if self.below_entropy_floor(layout) {
+            return None;
+        }
+        let next = self.next_candidate(layout, heap)?;
+        // Dead cells are chosen first, so the search only stops at its depth
+        // limit if none are left
+        let limit_reached = self.depth_limit.is_some_and(|limit| self.path.len() >= limit);
+        if limit_reached && !layout.get_cell(&next)?.get_possibilities().is_empty() {
+            return None;
+        }
+        Some(next)
+    }
+
+    /// Undoes the records of a collapse made by [Solver::collapse_step].
+    fn undo_step(&mut self, coord: &Coord<W>) {
+        self.send_event(SolveEvent::Undo(coord.clone()));
+        self.path.pop();
+        if self.record_entropy {
+            self.entropy_trace.pop();
+        }
+    }
+
+    /// Collapses every selectable cell with the same score as the cell at
+    /// the coordinate in one step, and continues the search from there, see
+    /// [Solver::set_batch_collapse].
+    ///
+    /// Returns None without making any change if the batch would only have a
+    /// single cell, or if the search from the batch failed, in which case its
+    /// collapses are undone.
+    fn collapse_batch(&mut self, layout: &mut W::L, coord: &Coord<W>) -> Option<W::L> {
+        let score = self.selection_score(coord, layout.get_cell(coord)?);
+        let batch: Vec<_> = layout
+            .candidates()
+            .filter(|(coord, cell)| self.is_selectable(coord, cell) && self.selection_score(coord, cell) == score)
+            .map(|(coord, _)| coord)
+            .collect();
+        if batch.len() < 2 {
+            return None;
+        }
+
+        let depth = self.path.len();
+        let mut new_layout = layout.clone();
+        let mut failed = false;
+        for cell_coord in &batch {
+            if new_layout.get_cell(cell_coord).is_some_and(|cell| cell.is_collapsed()) {
+                continue;
+            }
+            let Some(value) = self.possibility_order(&mut new_layout, cell_coord).into_iter().next() else {
+                self.contradiction = Some(cell_coord.clone());
+                failed = true;
+                break;
+            };
+            new_layout.collapse(cell_coord, value);
+            self.propagate(&mut new_layout, cell_coord, value);
+            if let Some(on_step) = &mut self.on_step {
+                on_step(&new_layout, cell_coord);
+            }
+            self.send_event(SolveEvent::Collapse(cell_coord.clone(), value));
+            self.path.push((cell_coord.clone(), value));
+            if self.record_entropy {
+                self.entropy_trace.push(new_layout.total_entropy());
+            }
+        }
+
+        if !failed {
+            if self.below_entropy_floor(&mut new_layout) {
+                return Some(new_layout);
+            }
+            let mut heap = None;
+            let Some(next) = self.next_candidate(&mut new_layout, &mut heap) else {
+                return Some(new_layout);
+            };
+            let result = self.collapse(&mut new_layout, &mut heap, &next);
+            if result.is_some() {
+                return result;
+            }
+        }
+        while self.path.len() > depth {
+            if let Some((undone, _)) = self.path.pop() {
+                self.send_event(SolveEvent::Undo(undone));
+            }
+            if self.record_entropy {
+                self.entropy_trace.pop();
+            }
+        }
+        None
+    }
+
+    /// Returns true if an entropy floor is set and every uncollapsed cell has
+    /// some possibilities and an entropy below it.
+    fn below_entropy_floor(&self, layout: &mut W::L) -> bool {
+        let Some(floor) = self.entropy_floor else {
+            return false;
+        };
+        let below = layout.candidates().all(|(_, cell)| match cell {
+            Cell::Uncollapsed(possibilities) => !possibilities.is_empty() && cell.entropy() < floor,
+            Cell::Collapsed(_) => true,
+        });
+        below
+    }
+
+    /// Propagates the collapse of the cell at the coordinate through the
+    /// wavefunction and the dynamic constraints.
+    fn propagate(&mut self, layout: &mut W::L, coord: &Coord<W>, value: W::V) {
+        self.propagate_wavefunction(layout, coord, value);
+        for constraint in &self.dynamic_constraints {
+            constraint(layout);
+        }
+
+        // A locked cell that was changed is a contradiction, see
+        // Solver::locked_cells
+        for (locked, locked_value) in &self.locked {
+            let intact = layout
+                .get_cell(locked)
+                .is_none_or(|cell| cell.get_value() == Some(*locked_value));
+            if !intact {
+                layout.clear_cell(locked);
+            }
+        }
+    }
+
+    /// Calls [Wavefunction::collapse], or replays its memoized result.
+    fn propagate_wavefunction(&mut self, layout: &mut W::L, coord: &Coord<W>, value: W::V) {
+        if !self.collapse_memo || !self.wavefunction.is_deterministic() {
+            self.wavefunction.collapse(layout, coord.clone(), value);
+            return;
+        }
+
+        // The neighbors are kept while the layout is modified below
+        let neighbors = layout.neighbor_coords(coord).into_owned();
+        let states = neighbors
+            .iter()
+            .map(|neighbor| layout.get_cell(neighbor).map(CellState::new))
+            .collect();
+        let key = (coord.clone(), value, states);
+        if let Some(cells) = self.memo.get(&key) {
+            for (neighbor, cell) in neighbors.iter().zip(cells) {
+                if let (Some(target), Some(cell)) = (layout.get_cell_mut(neighbor), cell) {
+                    *target = cell.clone();
+                }
+            }
+            return;
+        }
+
+        self.wavefunction.collapse(layout, coord.clone(), value);
+        let cells = neighbors
+            .iter()
+            .map(|neighbor| layout.get_cell(neighbor).cloned())
+            .collect();
+        self.memo.insert(key, cells);
+    }
+
+    /// A copy of the initial state with the dynamic constraints applied.
+    fn working_layout(&mut self) -> W::L {
+        let mut layout = self.initial_state.clone();
+        self.locked = collapsed_cells(&layout);
+        for constraint in &self.dynamic_constraints {
+            constraint(&mut layout);
+        }
+        layout
+    }
+
+    /// Returns true if the cancellation flag of [Solver::solve_cancellable]
+    /// is set.
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Sends an event to the receiver of [Solver::solve_streaming], if any.
+    fn send_event(&self, event: SolveEvent<Coord<W>, W::V>) {
+        if let Some(tx) = &self.event_sender {
+            // A dropped receiver only means nobody is listening anymore.
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Returns the possibilities of the cell at the coordinate in the order
+    /// they should be tried.
+    fn possibility_order(&mut self, layout: &mut W::L, coord: &Coord<W>) -> Vec<W::V> {
+        let possibilities = layout.get_cell_mut(coord).unwrap().get_possibilities();
+        let factors = self.bias_field.as_ref().map(|field| field(coord.clone()));
+        let repeats = self.remembered_values(layout, coord);
+        // Keeps the total of the weights finite
+        let limit = f64::MAX / possibilities.len().max(1) as f64;
+        let weights: Vec<_> = possibilities
+            .into_iter()
+            .map(|(value, weight)| {
+                let mut weight = weight as f64;
+                if let Some(factor) = factors.as_ref().and_then(|factors| factors.get(&value)) {
+                    if factor.is_finite() {
+                        weight *= factor;
+                    }
+                }
+                if let Some(count) = repeats.get(&value) {
+                    weight /= (1 + count) as f64;
+                }
+                (value, weight.min(limit))
+            })
+            .collect();
+        let order: Vec<_> = match self.chooser.as_mut() {
+            Some(chooser) => choose_order(chooser.as_mut(), weights),
+            None => WeightedIterator::from_weights(weights, &mut self.rng).collect(),
+        };
+
+        let mut order = match self.value_order {
+            ValueOrder::WeightedRandom => order,
+            ValueOrder::LeastConstraining => {
+                let mut scored: Vec<_> = order
+                    .into_iter()
+                    .map(|value| {
+                        let mut simulated = layout.clone();
+                        simulated.collapse(coord, value);
+                        self.wavefunction
+                            .collapse(&mut simulated, coord.clone(), value);
+                        (value, remaining_possibilities(&mut simulated))
+                    })
+                    .collect();
+                // The sort is stable, so ties keep their weighted random order
+                scored.sort_by_key(|(_, remaining)| Reverse(*remaining));
+                scored.into_iter().map(|(value, _)| value).collect()
+            }
+        };
+
+        // The value of the target of Solver::solve_toward is tried first
+        let targeted = self.target.as_ref().and_then(|target| target.get_cell(coord)?.get_value());
+        if let Some(index) = targeted.and_then(|value| order.iter().position(|v| *v == value)) {
+            let value = order.remove(index);
+            order.insert(0, value);
+        }
+        order
+    }
+
+    /// Counts the values that the remembered solutions of
+    /// [Solver::set_diversity_memory] have at the coordinate, considering only
+    /// the solutions that agree with every collapsed cell of the layout.
+    fn remembered_values(&self, layout: &mut W::L, coord: &Coord<W>) -> HashMap<W::V, usize> {
+        let mut counts = HashMap::new();
+        if self.diversity_memory.is_empty() {
+            return counts;
+        }
+        let collapsed: Vec<_> = layout
+            .cells()
+            .filter_map(|(coord, cell)| cell.get_value().map(|value| (coord, value)))
+            .collect();
+        for solution in &self.diversity_memory {
+            let agrees = collapsed.iter().all(|(coord, value)| {
+                solution.get_cell(coord).and_then(|cell| cell.get_value()) == Some(*value)
+            });
+            if !agrees {
+                continue;
+            }
+            if let Some(value) = solution.get_cell(coord).and_then(|cell| cell.get_value()) {
+                *counts.entry(value).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Chooses the next coordinate to collapse like [Solver::next_coord], using
+    /// the candidate heap when it is enabled.
+    ///
+    /// The heap is rebuilt from a full scan if there is none yet, or if the
+    /// layout does not know which of its cells changed.
+    fn next_candidate(
+        &mut self,
+        layout: &mut W::L,
+        heap: &mut Option<CandidateHeap<Coord<W>>>,
+    ) -> Option<Coord<W>> {
+        if !self.candidate_cache
+            || self.coherence_weight != 0.0
+            || self.scanline
+            || self.tiebreak_noise.is_some()
+        {
+            return self.next_coord(layout);
+        }
+        let scores: Vec<_> = match (layout.take_changed_cells(), heap.is_some()) {
+            (Some(changed), true) => changed
+                .into_iter()
+                .map(|coord| {
+                    let score = layout
+                        .get_cell(&coord)
+                        .filter(|cell| !cell.is_collapsed() && self.is_selectable(&coord, cell))
+                        .map(|cell| self.selection_score(&coord, cell));
+                    (coord, score)
+                })
+                .collect(),
+            _ => {
+                match heap.as_mut() {
+                    Some(heap) => heap.reset(&mut self.rng),
+                    None => *heap = Some(CandidateHeap::new(&mut self.rng)),
+                }
+                let scores = layout
+                    .candidates()
+                    .filter(|(coord, cell)| self.is_selectable(coord, cell))
+                    .map(|(coord, cell)| {
+                        let score = self.selection_score(&coord, cell);
+                        (coord, Some(score))
+                    })
+                    .collect();
+                // The scan itself borrowed every cell mutably
+                layout.take_changed_cells();
+                scores
+            }
+        };
+
+        let heap = heap.as_mut()?;
+        heap.update(scores);
+        heap.pop()
+    }
+
+    /// Chooses the next coordinate to collapse by iterating through all
+    /// candidates and returning the one with the lowest score according to the
+    /// selection strategy and the coherence weight, or the first one in
+    /// scanline order. Only candidates in the current region are considered,
+    /// if there is one.
+    fn next_coord(
+        &mut self,
+        layout: &mut <W as Wavefunction>::L,
+    ) -> Option<Coord<W>> {
+        if self.scanline {
+            let mut first = None;
+            for (coord, cell) in layout.candidates() {
+                if !self.is_selectable(&coord, cell) {
+                    continue;
+                }
+                if cell.get_possibilities().is_empty() {
+                    return Some(coord);
+                }
+                first.get_or_insert(coord);
+            }
+            return first;
+        }
+
+        let mut scored: Vec<_> = layout
+            .candidates()
+            .filter(|(coord, cell)| self.is_selectable(coord, cell))
+            .map(|(coord, cell)| {
+                let score = self.selection_score(&coord, cell);
+                (coord, score)
+            })
+            .collect();
+        if self.coherence_weight != 0.0 {
+            for (coord, score) in scored.iter_mut() {
+                // Dead cells are always chosen first
+                if *score == f64::MIN {
+                    continue;
+                }
+                let collapsed = layout
+                    .neighbor_coords(coord)
+                    .iter()
+                    .filter(|neighbor| layout.get_cell(neighbor).is_some_and(|cell| cell.is_collapsed()))
+                    .count();
+                *score -= self.coherence_weight * collapsed as f64;
+            }
+        }
+
         let mut last_coords = Vec::new();
         let mut last_entropy = f64::MAX;
-        for (coord, cell) in layout.candidates() {
-            let entropy = cell.entropy();
+        for (coord, entropy) in scored {
             if entropy == last_entropy {
-                last_coords.push(coord.clone());
-            }
-            if entropy < last_entropy {
-                last_coords = vec![coord.clone()];
+                last_coords.push(coord);
+            } else if entropy < last_entropy {
+                last_coords = vec![coord];
                 last_entropy = entropy;
             }
         }
 
+        // Only the tied cells with the lowest noise remain
+        if let Some(noise) = &self.tiebreak_noise {
+            let noises: Vec<_> = last_coords.iter().map(noise).collect();
+            let lowest = noises.iter().copied().min_by(f64::total_cmp);
+            last_coords = last_coords
+                .into_iter()
+                .zip(noises)
+                .filter(|(_, noise)| Some(*noise) == lowest)
+                .map(|(coord, _)| coord)
+                .collect();
+        }
+
         // Choose a possible item, or None if the list is empty
-        last_coords.choose(&mut thread_rng()).cloned()
+        last_coords.choose(&mut self.rng).cloned()
+    }
+
+    /// Returns true if the uncollapsed cell may be chosen next. It must be in
+    /// the current region and the solve region, if there are any, and must not have been skipped by
+    /// [Solver::solve_until_stable] unless it has no possibilities left.
+    fn is_selectable(&self, coord: &Coord<W>, cell: &Cell<W::V>) -> bool {
+        let dead = matches!(cell, Cell::Uncollapsed(possibilities) if possibilities.is_empty());
+        self.region.as_ref().is_none_or(|region| region.contains(coord))
+            && self.solve_region.as_ref().is_none_or(|region| region.contains(coord))
+            && (dead || !self.skipped.contains(coord))
+    }
+
+    /// Scores an uncollapsed cell for selection, lower scores are chosen
+    /// first.
+    fn selection_score(&self, coord: &Coord<W>, cell: &Cell<W::V>) -> f64 {
+        let Cell::Uncollapsed(possibilities) = cell else {
+            return 0.0;
+        };
+        if possibilities.is_empty() {
+            return f64::MIN;
+        }
+        let score = match self.selection_strategy {
+            SelectionStrategy::MinEntropy => cell.entropy(),
+            SelectionStrategy::MinimumRemainingValues => possibilities.len() as f64,
+            SelectionStrategy::Random => 0.0,
+        };
+        let importance = possibilities
+            .keys()
+            .map(|value| match self.value_importance.get(value) {
+                Some(importance) if *importance > 0.0 => *importance,
+                _ => 1.0,
+            })
+            .fold(f64::MIN, f64::max);
+        let score = score / importance;
+
+        // Cells that can no longer match the target of Solver::solve_toward
+        // are chosen after all others, so their neighbors match it first
+        let targeted = self.target.as_ref().and_then(|target| target.get_cell(coord)?.get_value());
+        match targeted {
+            Some(value) if !possibilities.contains_key(&value) => score + 1e9,
+            _ => score,
+        }
+    }
+}
+
+/// A partial layout in the frontier of [Solver::top_solutions], ordered by
+/// the log of its likelihood.
+struct Ranked<L> {
+    score: f64,
+    layout: L,
+}
+
+impl<L> PartialEq for Ranked<L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score.total_cmp(&other.score) == CmpOrdering::Equal
+    }
+}
+
+impl<L> Eq for Ranked<L> {}
+
+impl<L> PartialOrd for Ranked<L> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<L> Ord for Ranked<L> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.score.total_cmp(&other.score)
+    }
+}
+
+impl<V: CellValue, W: Wavefunction<V = V, L = Grid<V>>> Solver<W> {
+    /// Modify the initial [Grid] by collapsing every cell on its edges to the
+    /// given value.
+    ///
+    /// Each cell is collapsed with [Solver::collapse_initial], so the
+    /// wavefunction's constraints are propagated into the interior.
+    pub fn collapse_border(&mut self, value: V) {
+        for coord in self.initial_state.border() {
+            self.collapse_initial(coord, value);
+        }
+    }
+
+    /// Restricts solving to the rectangle of cells with its top left corner
+    /// at `top_left`, clipped to the grid.
+    ///
+    /// The solver only chooses cells inside the region to collapse, and cells
+    /// outside it are never collapsed by the solver. They still take part in
+    /// propagation, so already solved neighbors constrain the region, which
+    /// allows a large map to be solved in chunks. A solve succeeds once every
+    /// cell in the region is collapsed, even if cells outside it were left
+    /// without possibilities. This replaces any previous region.
+    pub fn set_solve_region(&mut self, top_left: Coord2D, width: usize, height: usize) {
+        let max_x = top_left.x().saturating_add(width).min(self.initial_state.x());
+        let max_y = top_left.y().saturating_add(height).min(self.initial_state.y());
+        let region = (top_left.y()..max_y)
+            .flat_map(|y| (top_left.x()..max_x).map(move |x| Coord2D::new(x, y)))
+            .collect();
+        self.solve_region = Some(region);
+    }
+
+    /// Removes the region set by [Solver::set_solve_region], so that every
+    /// cell is solved again.
+    pub fn clear_solve_region(&mut self) {
+        self.solve_region = None;
+    }
+
+    /// Sets a noise function that decides between cells with equally good
+    /// scores, in place of a random choice.
+    ///
+    /// When several cells tie under the [SelectionStrategy], which is common
+    /// early in a solve, the cell with the lowest noise value is collapsed
+    /// next. This gives art directable collapse patterns: a function of the
+    /// distance from the center grows the layout outward from the middle,
+    /// while the negated distance collapses it from the edges inward. Cells
+    /// with the same noise value are still chosen between by the random
+    /// number generator, so a seeded solve with a deterministic noise function
+    /// stays reproducible. Cells with no possibilities left are still always
+    /// chosen first.
+    ///
+    /// The candidate cache does not order ties by noise, so it is bypassed
+    /// while a noise function is set, which makes choosing each cell slower
+    /// on large grids.
+    pub fn set_tiebreak_noise(&mut self, noise: impl Fn(Coord2D) -> f64 + 'static) {
+        self.tiebreak_noise = Some(Box::new(move |coord| noise(*coord)));
+    }
+
+    /// Finds the cells that are the given value in at least one solution.
+    ///
+    /// Each cell of the returned grid is collapsed to true if some solution of
+    /// the initial state has `value` in that cell, and false otherwise. This
+    /// answers questions such as where treasure could be placed in a level
+    /// without knowing the rest of it.
+    ///
+    /// This is very expensive: the initial state is solved once with each
+    /// cell collapsed to the value, although cells that cannot hold the value
+    /// in the initial state are skipped, and every solution found also marks
+    /// the other cells that it placed the value in. A backtrack limit makes it
+    /// faster, but solves that hit the limit count as having no solution. The
+    /// initial state is restored afterwards.
+    pub fn value_feasibility(&mut self, value: V) -> Grid<bool> {
+        let (width, height) = (self.initial_state.x(), self.initial_state.y());
+        let mut feasibility = Grid::new(width, height);
+        feasibility.collapse_pattern(|_| Some(false));
+
+        let saved_state = self.initial_state.clone();
+        for coord in (0..height).flat_map(|y| (0..width).map(move |x| Coord2D::new(x, y))) {
+            if feasibility.get_cell(&coord).unwrap().get_value() == Some(true) {
+                continue;
+            }
+            let mut trial = saved_state.clone();
+            match trial.get_cell(&coord).unwrap() {
+                Cell::Collapsed(collapsed) if *collapsed == value => {}
+                Cell::Uncollapsed(possibilities) if possibilities.contains_key(&value) => {
+                    trial.collapse(&coord, value);
+                    self.wavefunction.collapse(&mut trial, coord, value);
+                }
+                _ => continue,
+            }
+
+            self.initial_state = trial;
+            self.backtracks = 0;
+            self.stop_reason = None;
+            self.path.clear();
+            self.entropy_trace.clear();
+            if let Some(mut solution) = self.solve_partitioned() {
+                for (coord, cell) in solution.cells() {
+                    if cell.get_value() == Some(value) {
+                        feasibility.collapse(&coord, true);
+                    }
+                }
+            }
+        }
+        self.initial_state = saved_state;
+        feasibility
+    }
+}
+
+/// Orders the values by asking the chooser for the next value until every
+/// value is used. Once the chooser gives up, the rest keep their order.
+fn choose_order<V: CellValue>(chooser: &mut dyn Chooser<V>, weights: Vec<(V, f64)>) -> Vec<V> {
+    let mut items: Vec<_> = weights
+        .into_iter()
+        .map(|(value, weight)| (value, weight.max(0.0).ceil() as usize))
+        .collect();
+    items.sort_by_cached_key(|(value, _)| stable_hash(value));
+    let mut order = Vec::with_capacity(items.len());
+    while let Some(index) = chooser.choose(&items).filter(|index| *index < items.len()) {
+        order.push(items.remove(index).0);
+    }
+    order.extend(items.into_iter().map(|(value, _)| value));
+    order
+}
+
+/// The coordinates and values of the collapsed cells of the layout.
+fn collapsed_cells<V: CellValue, L: Layout<V>>(layout: &L) -> Vec<(L::Coordinate, V)> {
+    layout
+        .cells_ref()
+        .filter_map(|(coord, cell)| cell.get_value().map(|value| (coord, value)))
+        .collect()
+}
+
+/// Counts the possibilities across all uncollapsed cells of the layout.
+///
+/// Returns None if any uncollapsed cell has no possibilities left, since that
+/// layout can never be solved.
+fn remaining_possibilities<V: CellValue, L: Layout<V>>(layout: &mut L) -> Option<usize> {
+    let mut total = 0;
+    for (_, cell) in layout.candidates() {
+        if let Cell::Uncollapsed(possibilities) = cell {
+            if possibilities.is_empty() {
+                return None;
+            }
+            total += possibilities.len();
+        }
+    }
+    Some(total)
+}
+
+/// A hashable copy of the state of a [Cell], used to memoize collapses.
+#[derive(PartialEq, Eq, Hash)]
+enum CellState<V: CellValue> {
+    Collapsed(V),
+    Uncollapsed(Vec<(V, usize)>),
+}
+
+impl<V: CellValue> CellState<V> {
+    fn new(cell: &Cell<V>) -> Self {
+        match cell {
+            Cell::Collapsed(value) => CellState::Collapsed(*value),
+            Cell::Uncollapsed(possibilities) => {
+                // Equal cells almost always sort the same way. When they do
+                // not, the memo only misses.
+                let mut possibilities: Vec<_> = possibilities.iter().map(|(v, w)| (*v, *w)).collect();
+                possibilities.sort_by_cached_key(|(value, weight)| (stable_hash(value), *weight));
+                CellState::Uncollapsed(possibilities)
+            }
+        }
     }
 }
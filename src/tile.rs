@@ -1,6 +1,9 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    ops::{Index, IndexMut},
+};
 
-use crate::CellValue;
+use crate::{CellValue, Direction};
 
 
 /// Tile2D implements [CellValue], and contains a small grid of CellValues
@@ -18,6 +21,17 @@ impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Tile2D<V, WIDTH, HEI
         Self { contents }
     }
 
+    /// Create a new Tile2D from a flat slice of values in row major order.
+    ///
+    /// None if the length of the slice is not `WIDTH * HEIGHT`
+    pub fn from_slice(data: &[V]) -> Option<Self> {
+        if data.len() != WIDTH * HEIGHT {
+            return None;
+        }
+        let contents = std::array::from_fn(|y| std::array::from_fn(|x| data[y * WIDTH + x]));
+        Some(Self { contents })
+    }
+
     /// The width of the tile
     pub fn x(&self) -> usize {
         WIDTH
@@ -35,6 +49,47 @@ impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Tile2D<V, WIDTH, HEI
         self.contents.get(y)?.get(x)
     }
 
+    /// Get a mutable reference to a value in the tile
+    ///
+    /// None if out of bounds
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut V> {
+        self.contents.get_mut(y)?.get_mut(x)
+    }
+
+    /// Replace a value in the tile
+    ///
+    /// Returns false, leaving the tile unchanged, if out of bounds
+    pub fn set(&mut self, x: usize, y: usize, value: V) -> bool {
+        match self.get_mut(x, y) {
+            Some(existing) => {
+                *existing = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The values along the edge of the tile that faces `direction`.
+    ///
+    /// Rows are read left to right and columns top to bottom, so two tiles
+    /// overlap cleanly when the edge of one equals the opposite edge of the
+    /// other. A diagonal direction yields the single value in that corner.
+    pub fn edge(&self, direction: Direction) -> Vec<V> {
+        let (right, bottom) = (WIDTH.saturating_sub(1), HEIGHT.saturating_sub(1));
+        let (xs, ys) = match direction {
+            Direction::UpLeft => (0..1, 0..1),
+            Direction::Up => (0..WIDTH, 0..1),
+            Direction::UpRight => (right..right + 1, 0..1),
+            Direction::Left => (0..1, 0..HEIGHT),
+            Direction::Right => (right..right + 1, 0..HEIGHT),
+            Direction::DownLeft => (0..1, bottom..bottom + 1),
+            Direction::Down => (0..WIDTH, bottom..bottom + 1),
+            Direction::DownRight => (right..right + 1, bottom..bottom + 1),
+        };
+        ys.flat_map(|y| xs.clone().filter_map(move |x| self.get(x, y).cloned()))
+            .collect()
+    }
+
     /// Iterates through the values of the tile, yeilding a tuple of (x, y,
     /// value)
     pub fn values(&self) -> impl Iterator<Item = (usize, usize, &V)> + '_ {
@@ -46,6 +101,21 @@ impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Tile2D<V, WIDTH, HEI
     }
 }
 
+/// Indexes the tile by `(x, y)`, panicking if out of bounds.
+impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> Index<(usize, usize)> for Tile2D<V, WIDTH, HEIGHT> {
+    type Output = V;
+
+    fn index(&self, (x, y): (usize, usize)) -> &V {
+        &self.contents[y][x]
+    }
+}
+
+impl<V: CellValue, const WIDTH: usize, const HEIGHT: usize> IndexMut<(usize, usize)> for Tile2D<V, WIDTH, HEIGHT> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut V {
+        &mut self.contents[y][x]
+    }
+}
+
 impl<V: CellValue + Display> Display for Tile2D<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in &self.contents {
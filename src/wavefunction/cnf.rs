@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use crate::{CellValue, Coord2D, Grid, Layout};
+
+/// A boolean formula in conjunctive normal form, for handing a problem to an
+/// external SAT solver.
+///
+/// There is one variable for every value that a cell of a [Grid] may take,
+/// which is true when the cell is that value. Variables are numbered from 1,
+/// going through the cells row by row from the top left, and through the
+/// possibilities of each cell in a fixed order. [CnfFormula::variable] looks
+/// up the number of a cell and value. Each clause is a list of literals, where
+/// a positive literal is a variable and a negative one is its negation, as in
+/// the DIMACS format written by [CnfFormula::to_dimacs].
+///
+/// See [Standard2D::to_cnf](crate::Standard2D::to_cnf) for a wavefunction
+/// that produces one.
+#[derive(Clone, Debug)]
+pub struct CnfFormula<V: CellValue> {
+    x: usize,
+    y: usize,
+    variables: Vec<(Coord2D, V)>,
+    numbers: HashMap<(Coord2D, V), usize>,
+    clauses: Vec<Vec<i64>>,
+}
+
+impl<V: CellValue> CnfFormula<V> {
+    /// Create a new [CnfFormula] without any variables or clauses, for a grid
+    /// with the given dimensions.
+    pub fn new(x: usize, y: usize) -> Self {
+        Self {
+            x,
+            y,
+            variables: Vec::new(),
+            numbers: HashMap::new(),
+            clauses: Vec::new(),
+        }
+    }
+
+    /// Adds a variable for the cell at the coordinate being the value, and
+    /// returns its number. A variable that already exists is not added again.
+    pub fn add_variable(&mut self, coord: Coord2D, value: V) -> usize {
+        if let Some(number) = self.numbers.get(&(coord, value)) {
+            return *number;
+        }
+        self.variables.push((coord, value));
+        let number = self.variables.len();
+        self.numbers.insert((coord, value), number);
+        number
+    }
+
+    /// Adds a clause that is satisfied if any of its literals is true. An
+    /// empty clause can never be satisfied.
+    pub fn add_clause(&mut self, literals: Vec<i64>) {
+        self.clauses.push(literals);
+    }
+
+    /// Returns the number of the variable for the cell at the coordinate
+    /// being the value, or None if the value is not a possibility of the cell.
+    pub fn variable(&self, coord: Coord2D, value: &V) -> Option<usize> {
+        self.numbers.get(&(coord, *value)).copied()
+    }
+
+    /// The number of variables in the formula.
+    pub fn variable_count(&self) -> usize {
+        self.variables.len()
+    }
+
+    /// The clauses of the formula, all of which must be satisfied.
+    pub fn clauses(&self) -> &[Vec<i64>] {
+        &self.clauses
+    }
+
+    /// Returns true if the assignment satisfies every clause, where the value
+    /// of variable `n` is `assignment[n - 1]`. Missing variables are false.
+    pub fn is_satisfied_by(&self, assignment: &[bool]) -> bool {
+        self.clauses.iter().all(|clause| {
+            clause.iter().any(|literal| {
+                let value = assignment
+                    .get(literal.unsigned_abs() as usize - 1)
+                    .copied()
+                    .unwrap_or(false);
+                value == (*literal > 0)
+            })
+        })
+    }
+
+    /// Converts a satisfying assignment back into a [Grid], such as the model
+    /// found by an external solver.
+    ///
+    /// Each cell is collapsed to the value of its first true variable, and
+    /// cells without any true variable are left uncollapsed and empty.
+    pub fn decode(&self, assignment: &[bool]) -> Grid<V> {
+        let mut grid = Grid::new(self.x, self.y);
+        for ((coord, value), assigned) in self.variables.iter().zip(assignment) {
+            if *assigned && !grid.get_cell(coord).is_some_and(|cell| cell.is_collapsed()) {
+                grid.collapse(coord, *value);
+            }
+        }
+        grid
+    }
+
+    /// Writes the formula in the DIMACS CNF format read by most SAT solvers.
+    pub fn to_dimacs(&self) -> String {
+        let mut dimacs = format!("p cnf {} {}\n", self.variables.len(), self.clauses.len());
+        for clause in &self.clauses {
+            for literal in clause {
+                dimacs.push_str(&format!("{} ", literal));
+            }
+            dimacs.push_str("0\n");
+        }
+        dimacs
+    }
+}
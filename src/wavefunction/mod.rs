@@ -1,7 +1,16 @@
 mod standard;
-pub use standard::Standard2D;
+pub use standard::{AdjacencyStats, Standard2D};
 
-use crate::{layout, CellValue, Layout};
+mod cnf;
+pub use cnf::CnfFormula;
+
+mod rule_table;
+pub use rule_table::{RuleConflict, RuleTable};
+
+mod thickness;
+pub use thickness::ThicknessConstraint;
+
+use crate::{layout, Cell, CellValue, Layout};
 
 /// Implementors of Wavefunction can be passed to the solver to produce the
 /// corresponding output. Different implementors may choose to use different
@@ -38,6 +47,78 @@ pub trait Wavefunction {
         value: Self::V,
     );
 
+    /// Returns true if the collapsed cells of the layout satisfy the
+    /// constraints of this Wavefunction.
+    ///
+    /// Starting from a clone of the initial state, each collapsed cell of the
+    /// layout is collapsed in turn and propagated with
+    /// [Wavefunction::collapse]. If the value of a collapsed cell was already
+    /// eliminated by the propagation of the cells before it, the layout is
+    /// invalid. Uncollapsed cells are ignored, so partial layouts can also be
+    /// checked.
+    ///
+    /// This takes `&mut self` because it calls [Wavefunction::collapse], but
+    /// it does not modify the initial state.
+    fn is_valid(&mut self, layout: &Self::L) -> bool {
+        let mut fresh = self.get_initial_state().clone();
+        let mut layout = layout.clone();
+        for (coord, cell) in layout.cells() {
+            let Cell::Collapsed(value) = cell else {
+                continue;
+            };
+            let allowed = match fresh.get_cell(&coord) {
+                Some(Cell::Collapsed(existing)) => existing == value,
+                Some(Cell::Uncollapsed(possibilities)) => possibilities.contains_key(value),
+                None => false,
+            };
+            if !allowed {
+                return false;
+            }
+            fresh.collapse(&coord, *value);
+            self.collapse(&mut fresh, coord, *value);
+        }
+        true
+    }
+
+    /// Returns the coordinates of the cells whose possibilities may change
+    /// when the cell at `coord` is collapsed.
+    ///
+    /// This is used by [Solver::set_partition_solving](crate::Solver::set_partition_solving)
+    /// to find groups of cells that do not affect each other. The relation is
+    /// treated as symmetric, so each pair of coupled cells only needs to be
+    /// listed from one side. Coordinates out of bounds are ignored.
+    ///
+    /// The default implementation returns None, meaning that any cell may be
+    /// affected.
+    fn coupled_cells(
+        &self,
+        _layout: &Self::L,
+        _coord: &<<Self as Wavefunction>::L as layout::Layout<Self::V>>::Coordinate,
+    ) -> Option<Vec<<<Self as Wavefunction>::L as layout::Layout<Self::V>>::Coordinate>> {
+        None
+    }
+
+    /// Returns true if [Wavefunction::collapse] only depends on and changes
+    /// the neighbors of the collapsed cell.
+    ///
+    /// When this is true, the solver may memoize the effect of a collapse and
+    /// replay it when the same value is collapsed at the same coordinate with
+    /// the same neighbors, see
+    /// [Solver::set_collapse_memo](crate::Solver::set_collapse_memo). This is
+    /// only correct if all of the following hold:
+    ///
+    /// * The only cells that collapse reads or changes are those returned by
+    ///   [Layout::neighbor_coords] for the collapsed coordinate.
+    /// * The result depends only on the coordinate, the value, and the state
+    ///   of those cells, not on any other state of the wavefunction or
+    ///   layout, and not on randomness.
+    /// * The wavefunction does not change while the solver uses it.
+    ///
+    /// The default implementation returns false.
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+
     /// Prints the layout. Sometimes used for debugging the solving process.
     /// 
     /// This only needs to be implemented for wavefunctions used in solver
@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{CellValue, Coord2D, Direction, Grid, Layout};
+
+const ALL_DIRECTIONS: [Direction; 8] = [
+    Direction::UpLeft,
+    Direction::Up,
+    Direction::UpRight,
+    Direction::Left,
+    Direction::Right,
+    Direction::DownLeft,
+    Direction::Down,
+    Direction::DownRight,
+];
+
+/// A table of declarative rules for a [Wavefunction](crate::Wavefunction) on
+/// a [Grid].
+///
+/// Each rule states that when a cell collapses to a value, the neighbor in a
+/// [Direction] may not take any of a set of forbidden values. A wavefunction
+/// can store a table and implement its collapse as
+/// `self.rules.apply(layout, coord, value)`, rather than matching on every
+/// value by hand.
+#[derive(Clone, Debug, Default)]
+pub struct RuleTable<V: CellValue> {
+    rules: HashMap<V, HashMap<Direction, HashSet<V>>>,
+}
+
+impl<V: CellValue> RuleTable<V> {
+    /// Create a new, empty [RuleTable].
+    pub fn new() -> Self {
+        Self {
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Forbids the neighbor in the direction of a cell collapsed to `value`
+    /// from taking any of the forbidden values.
+    ///
+    /// Adding more rules for the same value and direction forbids the union of
+    /// their values.
+    pub fn add_rule(&mut self, value: V, direction: Direction, forbidden_values: &[V]) {
+        self.rules
+            .entry(value)
+            .or_default()
+            .entry(direction)
+            .or_default()
+            .extend(forbidden_values.iter().copied());
+    }
+
+    /// Returns the values forbidden in the direction of a cell collapsed to
+    /// `value`, if there are any rules for them.
+    pub fn get_forbidden(&self, value: &V, direction: Direction) -> Option<&HashSet<V>> {
+        self.rules.get(value)?.get(&direction)
+    }
+
+    /// Finds values that the rules leave without any allowed neighbor in some
+    /// direction, which is usually an authoring mistake.
+    ///
+    /// A value may have a neighbor in a [Direction] if some value of the
+    /// `universe` is neither forbidden there by the value's own rules, nor
+    /// forbids the value from the opposite side by its rules. A value
+    /// without any allowed neighbor in a direction can only be placed on the
+    /// edge of the grid facing that direction, and nowhere at all if it is
+    /// blocked in two opposite directions, so the solver fails wherever else
+    /// it is needed. Each conflict reports the value and the blocked
+    /// directions. The order of the conflicts is not specified.
+    pub fn validate(&self, universe: &HashSet<V>) -> Vec<RuleConflict<V>> {
+        let mut conflicts = Vec::new();
+        for value in universe {
+            let directions: Vec<_> = ALL_DIRECTIONS
+                .into_iter()
+                .filter(|direction| {
+                    !universe.iter().any(|neighbor| {
+                        let forbidden_by_value = self
+                            .get_forbidden(value, *direction)
+                            .is_some_and(|forbidden| forbidden.contains(neighbor));
+                        let forbidden_by_neighbor = self
+                            .get_forbidden(neighbor, direction.opposite())
+                            .is_some_and(|forbidden| forbidden.contains(value));
+                        !forbidden_by_value && !forbidden_by_neighbor
+                    })
+                })
+                .collect();
+            if !directions.is_empty() {
+                conflicts.push(RuleConflict {
+                    value: *value,
+                    directions,
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Removes the forbidden values from the neighbors of the cell at `coord`,
+    /// which has just collapsed to `value`.
+    ///
+    /// Neighbors that are out of bounds are skipped.
+    pub fn apply(&self, layout: &mut Grid<V>, coord: Coord2D, value: V) {
+        let Some(directions) = self.rules.get(&value) else {
+            return;
+        };
+        for (direction, forbidden) in directions {
+            let neighbor = coord.get_neighbor(*direction);
+            for possibility in forbidden {
+                layout.remove_cell_possibility(&neighbor, possibility);
+            }
+        }
+    }
+}
+
+/// A value that a [RuleTable] leaves without any allowed neighbor, found by
+/// [RuleTable::validate].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleConflict<V: CellValue> {
+    /// The value that is over constrained.
+    pub value: V,
+    /// The directions in which no value may be placed next to it.
+    pub directions: Vec<Direction>,
+}
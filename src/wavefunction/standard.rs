@@ -1,8 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::{cell::{Function, Operation}, layout::grid::Direction, CellValue, Coord2D, Grid, Layout, Tile2D};
+use crate::{
+    cell::{Cell, Function, Operation},
+    layout::grid::Direction,
+    weighted_iterator::stable_hash,
+    CellValue, Coord2D, Grid, Layout, Tile2D,
+};
 
-use super::Wavefunction;
+use super::{CnfFormula, Wavefunction};
+
+// For a tile type, there is a probability map for each directional neighbor.
+type AdjacencyMap<V> = HashMap<Tile2D<V>, HashMap<Direction, HashMap<Tile2D<V>, usize>>>;
 
 /// This is an implementation of the standard two dimensional wavefunction
 /// collapse algorithm.
@@ -13,7 +21,8 @@ pub struct Standard2D<V: CellValue> {
     layout: Grid<Tile2D<V>>,
     /// For a tile type, there is a probability map for each directional
     /// neighbor.
-    adj_map: HashMap<Tile2D<V>, HashMap<Direction, HashMap<Tile2D<V>, usize>>>,
+    adj_map: AdjacencyMap<V>,
+    symmetric: bool,
 }
 
 impl<V: CellValue> Standard2D<V>{
@@ -22,6 +31,7 @@ impl<V: CellValue> Standard2D<V>{
         Self {
             layout: Grid::new(x, y),
             adj_map: HashMap::new(),
+            symmetric: false,
         }
     }
 
@@ -34,29 +44,367 @@ impl<V: CellValue> Standard2D<V>{
     /// order to complete the layout.
     pub fn learn(&mut self, material: &Grid<V>) {
         // Iterate through the material, accumulating tiles for the rules list.
-        for x in 0..material.x() {
-            for y in 0..material.y() {
-                let tile_coord = Coord2D::new(x, y);
-                let Some(tile) = material.get_tile(tile_coord) else {
-                    continue;
-                };
+        for (tile_coord, tile) in material.tiles() {
+            // get neighboring tiles and add them as possibilities in the map
+            for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                let neighbor_coord = tile_coord.get_neighbor_scaled(direction, tile.x(), tile.y());
+                if let Some(adjacent) = material.get_tile(neighbor_coord) {
+                    self.record_adjacency(tile, direction, adjacent);
+                }
+            }
+
+            // Since we have found a tile, it needs to be added to the board
+            // as possibilities.
+            self.layout.add_possibility(&tile);
+        }
 
-                // get neighboring tiles and add them as possibilities in the map
-                for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
-                    let neighbor_coord = tile_coord.get_neighbor_scaled(direction, tile.x(), tile.y());
-                    if let Some(adjacent) = material.get_tile(neighbor_coord) {
-                        self.add_adjacency(tile, direction, adjacent);
+        if self.symmetric {
+            self.symmetrize();
+        }
+    }
+
+    /// Build a set of adjacency rules from a tileset by matching the edges of
+    /// the tiles, rather than from a sample [Grid].
+    ///
+    /// Tile A may be placed to the left of tile B when the right edge of A
+    /// equals the left edge of B, and likewise above B when the bottom edge of
+    /// A equals the top edge of B. Each match is recorded once, in both
+    /// directions. Every tile is added as an initial possibility, so a tile
+    /// that appears more than once in `tiles` is weighted more heavily.
+    pub fn learn_edge_matching(&mut self, tiles: &[Tile2D<V>]) {
+        let distinct: HashSet<_> = tiles.iter().copied().collect();
+        for tile in &distinct {
+            for adjacent in &distinct {
+                for direction in [Direction::Right, Direction::Down] {
+                    if tile.edge(direction) == adjacent.edge(direction.opposite()) {
+                        self.record_adjacency(*tile, direction, *adjacent);
+                        self.record_adjacency(*adjacent, direction.opposite(), *tile);
                     }
                 }
+            }
+        }
 
-                // Since we have found a tile, it needs to be added to the board
-                // as possibilities.
-                self.layout.add_possibility(&tile);
+        for tile in tiles {
+            self.layout.add_possibility(tile);
+        }
+
+        if self.symmetric {
+            self.symmetrize();
+        }
+    }
+
+    /// Counts how many times each distinct tile pattern occurs in the
+    /// material.
+    ///
+    /// These are the frequencies that [Standard2D::learn] turns into the
+    /// weights of the initial possibilities, which can help explain why some
+    /// tiles dominate the output or never appear in it. Tiles overlap, so each
+    /// cell contributes to several patterns.
+    pub fn pattern_histogram<const WIDTH: usize, const HEIGHT: usize>(
+        material: &Grid<V>,
+    ) -> HashMap<Tile2D<V, WIDTH, HEIGHT>, usize> {
+        let mut histogram = HashMap::new();
+        for (_, tile) in material.tiles() {
+            *histogram.entry(tile).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Learn from a layout produced by solving this wavefunction.
+    ///
+    /// The solution is detiled and passed to [Standard2D::learn]. If `replace`
+    /// is true, the existing rules and tile frequencies are discarded first,
+    /// otherwise the solution augments them. This allows a feedback loop that
+    /// reinforces the patterns of good outputs.
+    ///
+    /// Learning repeatedly from outputs risks overfitting the rules to a
+    /// single output, reducing the variety of future solutions.
+    pub fn learn_from_solution(&mut self, solved: &Grid<Tile2D<V>>, replace: bool) {
+        if replace {
+            self.adj_map.clear();
+            self.layout = Grid::new(self.layout.x(), self.layout.y());
+        }
+        let material = solved.clone().detile();
+        self.learn(&material);
+    }
+
+    /// Sets the initial possibilities of every cell to the given tile
+    /// frequencies, replacing those accumulated by [Standard2D::learn].
+    ///
+    /// This allows the frequency of each tile to be tuned independently of
+    /// the training material. The adjacencies of the tiles must still be
+    /// learned separately. A tile without any known adjacencies will clear
+    /// its neighbors when it is placed, which causes the solver to backtrack.
+    pub fn set_tile_frequencies(&mut self, freqs: HashMap<Tile2D<V>, usize>) {
+        for (_, cell) in self.layout.cells() {
+            cell.set_possibilities(freqs.clone());
+        }
+    }
+
+    /// When enabled, every adjacency rule is given a reciprocal rule with a
+    /// matching weight. If tile A may be to the left of tile B, then tile B
+    /// may be to the right of tile A.
+    ///
+    /// Enabling this immediately applies it to the rules that were already
+    /// learned, as well as to any that are learned or added later. This
+    /// assumes that the adjacency relation should be symmetric, which is true
+    /// for most grids. Where both a rule and its reciprocal exist with
+    /// different weights, the larger weight is used for both.
+    pub fn set_enforce_symmetric_adjacency(&mut self, enabled: bool) {
+        self.symmetric = enabled;
+        if enabled {
+            self.symmetrize();
+        }
+    }
+
+    /// Returns the weights of the tiles allowed next to `tile` in the given
+    /// [Direction], or None if there are no known adjacencies.
+    pub fn get_adjacencies(
+        &self,
+        tile: &Tile2D<V>,
+        direction: Direction,
+    ) -> Option<&HashMap<Tile2D<V>, usize>> {
+        self.adj_map.get(tile)?.get(&direction)
+    }
+
+    /// Returns which tiles may be placed next to each tile in the given
+    /// [Direction], ignoring the weights of the adjacencies.
+    ///
+    /// Tiles without any known adjacencies in that direction are not included.
+    pub fn compatibility_matrix(&self, direction: Direction) -> HashMap<Tile2D<V>, HashSet<Tile2D<V>>> {
+        let mut matrix = HashMap::new();
+        for (tile, directions) in &self.adj_map {
+            if let Some(adjacents) = directions.get(&direction) {
+                if !adjacents.is_empty() {
+                    matrix.insert(*tile, adjacents.keys().copied().collect());
+                }
             }
         }
+        matrix
+    }
+
+    /// Returns every known tile that has no allowed neighbor in at least one
+    /// of the four orthogonal directions.
+    ///
+    /// Known tiles are those that appear in the adjacency rules or as a
+    /// possibility in the initial state. Placing an isolated tile anywhere
+    /// but the edge of the grid always causes a contradiction, so these tiles
+    /// are a common cause of excessive backtracking.
+    pub fn find_isolated_tiles(&self) -> Vec<Tile2D<V>> {
+        self.known_tiles()
+            .into_iter()
+            .filter(|tile| {
+                [Direction::Up, Direction::Right, Direction::Down, Direction::Left]
+                    .iter()
+                    .any(|direction| {
+                        self.get_adjacencies(tile, *direction)
+                            .is_none_or(|adjacents| adjacents.is_empty())
+                    })
+            })
+            .collect()
+    }
+
+    /// Summarizes the learned adjacency rules, as a quick check of a model
+    /// before solving large grids.
+    ///
+    /// The statistics cover every known tile, as in
+    /// [Standard2D::find_isolated_tiles], in each of the four orthogonal
+    /// directions. A tile with few allowed neighbors in some direction is
+    /// likely to cause contradictions.
+    pub fn adjacency_stats(&self) -> AdjacencyStats {
+        let tiles = self.known_tiles();
+        let directions = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+        let counts: Vec<usize> = tiles
+            .iter()
+            .flat_map(|tile| directions.iter().map(move |direction| (tile, direction)))
+            .map(|(tile, direction)| self.get_adjacencies(tile, *direction).map_or(0, HashMap::len))
+            .collect();
+        let rule_count = counts.iter().sum();
+        AdjacencyStats {
+            tile_count: tiles.len(),
+            rule_count,
+            average_neighbors: if counts.is_empty() { 0.0 } else { rule_count as f64 / counts.len() as f64 },
+            min_neighbors: counts.iter().copied().min().unwrap_or(0),
+            max_neighbors: counts.iter().copied().max().unwrap_or(0),
+        }
+    }
+
+    /// Estimates the side length, in tiles, of the largest square grid that
+    /// the learned rules can reliably fill.
+    ///
+    /// This is a rough heuristic, not a guarantee. It takes the density of
+    /// the rules, the fraction of the known tiles allowed next to a tile in
+    /// each of the four orthogonal directions, and estimates how likely a cell
+    /// with two collapsed neighbors is to be left without any possibilities.
+    /// The recommended grid has about as many cells as are expected to be
+    /// collapsed before such a contradiction occurs. Richly connected rules
+    /// and larger tilesets give larger recommendations, and rules that allow
+    /// every tile next to every other give [usize::MAX]. Returns 0 if nothing
+    /// has been learned.
+    pub fn recommended_max_size(&self) -> usize {
+        let tiles = self.known_tiles();
+        if tiles.is_empty() {
+            return 0;
+        }
+
+        let directions = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+        let allowed: usize = tiles
+            .iter()
+            .flat_map(|tile| directions.iter().map(move |direction| (tile, direction)))
+            .map(|(tile, direction)| {
+                self.get_adjacencies(tile, *direction)
+                    .map_or(0, |adjacents| adjacents.keys().filter(|a| tiles.contains(a)).count())
+            })
+            .sum();
+        let count = tiles.len() as f64;
+        let density = allowed as f64 / (count * count * directions.len() as f64);
+
+        // A tile survives both constraints with probability density^2
+        let contradiction = (1.0 - density * density).powf(count);
+        let cells = 1.0 / contradiction;
+        // Saturates to usize::MAX when a contradiction is impossible
+        (cells.sqrt() as usize).max(1)
     }
 
-    fn add_adjacency(&mut self, tile: Tile2D<V>, direction: Direction, adjacent: Tile2D<V>) {
+    /// Encodes the problem of completing the grid under the learned adjacency
+    /// rules as a [CnfFormula], so that it can be handed to an external SAT
+    /// solver.
+    ///
+    /// Each cell has a variable for every tile it may take: its value if it is
+    /// collapsed, or each of its possibilities otherwise. The variables of a
+    /// cell are numbered consecutively in a fixed order of the tiles, and the
+    /// cells are numbered row by row from the top left. The clauses state that
+    /// each cell is exactly one of its tiles, and that a tile in a cell implies
+    /// that each orthogonal neighbor in the grid is one of the tiles allowed
+    /// next to it in that direction. A tile without any allowed neighbor in a
+    /// direction cannot be placed where that neighbor exists. Weights only
+    /// guide the choices of the solver, so they are not encoded.
+    ///
+    /// A satisfying assignment is a solution that [Solver](crate::Solver)
+    /// would accept, and the formula is satisfiable exactly when the grid can
+    /// be solved, as long as the rules are symmetric, see
+    /// [Standard2D::set_enforce_symmetric_adjacency]. With asymmetric rules the
+    /// formula requires both tiles of every pair to allow each other, while
+    /// the solver only checks the rules of the tile that was placed first.
+    pub fn to_cnf(&self, grid: &Grid<Tile2D<V>>) -> CnfFormula<Tile2D<V>> {
+        let mut cnf = CnfFormula::new(grid.x(), grid.y());
+        let coords: Vec<_> = (0..grid.y())
+            .flat_map(|y| (0..grid.x()).map(move |x| Coord2D::new(x, y)))
+            .collect();
+
+        // One variable per possible tile, and exactly one tile per cell
+        let mut candidates = HashMap::new();
+        for coord in &coords {
+            let mut tiles = match grid.get_cell(coord).unwrap() {
+                Cell::Collapsed(tile) => vec![*tile],
+                Cell::Uncollapsed(possibilities) => possibilities.keys().copied().collect(),
+            };
+            tiles.sort_by_key(stable_hash);
+            let variables: Vec<_> = tiles
+                .iter()
+                .map(|tile| cnf.add_variable(*coord, *tile) as i64)
+                .collect();
+            cnf.add_clause(variables.clone());
+            for (index, first) in variables.iter().enumerate() {
+                for second in &variables[index + 1..] {
+                    cnf.add_clause(vec![-first, -second]);
+                }
+            }
+            candidates.insert(*coord, tiles);
+        }
+
+        // A tile implies that each neighbor is one of its allowed adjacencies
+        for coord in &coords {
+            for tile in &candidates[coord] {
+                let variable = cnf.variable(*coord, tile).unwrap() as i64;
+                for (neighbor, direction) in coord.neighbor_directions4() {
+                    let Some(neighbor_tiles) = candidates.get(&neighbor) else {
+                        continue;
+                    };
+                    let allowed = self.get_adjacencies(tile, direction);
+                    let mut clause = vec![-variable];
+                    clause.extend(
+                        neighbor_tiles
+                            .iter()
+                            .filter(|adjacent| allowed.is_some_and(|allowed| allowed.contains_key(adjacent)))
+                            .map(|adjacent| cnf.variable(neighbor, adjacent).unwrap() as i64),
+                    );
+                    cnf.add_clause(clause);
+                }
+            }
+        }
+        cnf
+    }
+
+    /// Detiles the grid and checks that the tiles read back from the values
+    /// are the ones that were placed, and that every pair of orthogonally
+    /// adjacent tiles is allowed by the learned adjacency rules.
+    ///
+    /// This is a debugging and testing aid for the tiling pipeline, since a
+    /// mistake in [Grid::detile] or in the rules would otherwise only show up
+    /// as odd looking output. Unlike [Wavefunction::is_valid], it checks the
+    /// rules in both directions of every pair and does not depend on the
+    /// order in which the tiles were placed. Returns false if any cell of the
+    /// grid is uncollapsed.
+    pub fn verify_detiled(&self, tiled: &Grid<Tile2D<V>>) -> bool {
+        let detiled = tiled.clone().detile();
+        let tile_at = |coord: Coord2D| -> Option<Tile2D<V>> {
+            let tile = detiled.get_tile(Coord2D::new(coord.x() * 2, coord.y() * 2))?;
+            (tiled.get_cell(&coord)?.get_value()? == tile).then_some(tile)
+        };
+        for y in 0..tiled.y() {
+            for x in 0..tiled.x() {
+                let coord = Coord2D::new(x, y);
+                let Some(tile) = tile_at(coord) else {
+                    return false;
+                };
+                for (neighbor, direction) in coord.neighbor_directions4() {
+                    if neighbor.x() >= tiled.x() || neighbor.y() >= tiled.y() {
+                        continue;
+                    }
+                    let Some(adjacent) = tile_at(neighbor) else {
+                        return false;
+                    };
+                    let allowed = self.get_adjacencies(&tile, direction);
+                    if !allowed.is_some_and(|allowed| allowed.contains_key(&adjacent)) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Every tile that appears in the adjacency rules or as a possibility in
+    /// the initial state.
+    fn known_tiles(&self) -> HashSet<Tile2D<V>> {
+        let mut tiles = HashSet::new();
+        for (tile, directions) in &self.adj_map {
+            tiles.insert(*tile);
+            for adjacents in directions.values() {
+                tiles.extend(adjacents.keys().copied());
+            }
+        }
+        let mut layout = self.layout.clone();
+        for (_, cell) in layout.cells() {
+            tiles.extend(cell.get_possibilities().into_keys());
+        }
+        tiles
+    }
+
+    /// Records a single observation that `adjacent` may be placed next to
+    /// `tile` in the given [Direction].
+    ///
+    /// This is used by [Standard2D::learn], but can also be used to author
+    /// rules by hand.
+    pub fn add_adjacency(&mut self, tile: Tile2D<V>, direction: Direction, adjacent: Tile2D<V>) {
+        self.record_adjacency(tile, direction, adjacent);
+        if self.symmetric {
+            self.symmetrize();
+        }
+    }
+
+    fn record_adjacency(&mut self, tile: Tile2D<V>, direction: Direction, adjacent: Tile2D<V>) {
         // Get map of direction->valid tiles from th adj map
         let tile_adj = self.adj_map.entry(tile).or_default();
 
@@ -67,7 +415,31 @@ impl<V: CellValue> Standard2D<V>{
         let count = direction_adj.entry(adjacent).or_default();
 
         // Increase count since we have an observation
-        *count = *count + 1;
+        *count += 1;
+    }
+
+    /// Ensures every rule has a reciprocal rule of at least the same weight.
+    fn symmetrize(&mut self) {
+        let mut rules = Vec::new();
+        for (tile, directions) in &self.adj_map {
+            for (direction, adjacents) in directions {
+                for (adjacent, weight) in adjacents {
+                    rules.push((*tile, *direction, *adjacent, *weight));
+                }
+            }
+        }
+
+        for (tile, direction, adjacent, weight) in rules {
+            let reciprocal = self
+                .adj_map
+                .entry(adjacent)
+                .or_default()
+                .entry(direction.opposite())
+                .or_default()
+                .entry(tile)
+                .or_default();
+            *reciprocal = (*reciprocal).max(weight);
+        }
     }
 }
 
@@ -106,4 +478,43 @@ impl<V: CellValue> Wavefunction for Standard2D<V> {
             }
         }
     }
+
+    // A value without adjacencies clears all eight neighbors, otherwise only
+    // the orthogonal neighbors are constrained
+    fn coupled_cells(
+        &self,
+        layout: &Self::L,
+        coord: &<<Self as Wavefunction>::L as crate::layout::Layout<Self::V>>::Coordinate,
+    ) -> Option<Vec<<<Self as Wavefunction>::L as crate::layout::Layout<Self::V>>::Coordinate>> {
+        let known = match layout.get_cell(coord)? {
+            Cell::Collapsed(value) => self.adj_map.contains_key(value),
+            Cell::Uncollapsed(possibilities) => possibilities.keys().all(|value| self.adj_map.contains_key(value)),
+        };
+        if known {
+            Some(coord.neighbor_directions4().into_iter().map(|(neighbor, _)| neighbor).collect())
+        } else {
+            Some(coord.neighbors())
+        }
+    }
+}
+
+/// Statistics about the adjacency rules of a [Standard2D], found by
+/// [Standard2D::adjacency_stats].
+///
+/// The neighbor counts are taken for each known tile in each of the four
+/// orthogonal directions, and count the distinct tiles allowed next to it,
+/// ignoring their weights. All of them are zero if nothing has been learned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdjacencyStats {
+    /// The number of distinct known tiles.
+    pub tile_count: usize,
+    /// The number of distinct rules, each allowing one tile next to another
+    /// in one direction.
+    pub rule_count: usize,
+    /// The average number of tiles allowed next to a tile in a direction.
+    pub average_neighbors: f64,
+    /// The fewest tiles allowed next to any tile in any direction.
+    pub min_neighbors: usize,
+    /// The most tiles allowed next to any tile in any direction.
+    pub max_neighbors: usize,
 }
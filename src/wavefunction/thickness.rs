@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+
+use crate::{CellValue, Coord2D, Grid, Layout};
+
+/// A check that the features of a collapsed [Grid] are at least two cells
+/// thick.
+///
+/// Naive wavefunction collapse output often contains stringy, single cell
+/// wide features and regions that only touch diagonally. The exact rule is
+/// that every collapsed cell with a checked value must be part of some 2x2
+/// square of four collapsed cells with that same value. This rejects one cell
+/// wide lines, one cell wide bridges between larger regions, and diagonal
+/// only connections, while accepting any region made of overlapping 2x2
+/// squares. Uncollapsed cells are ignored.
+///
+/// When singletons are allowed, a cell whose eight neighbors all have other
+/// values is also accepted. A wavefunction can use this in its implementation
+/// of [Wavefunction::is_valid](crate::Wavefunction::is_valid), or a layout can
+/// be checked after solving.
+#[derive(Clone, Debug, Default)]
+pub struct ThicknessConstraint<V: CellValue> {
+    values: Option<HashSet<V>>,
+    allow_singletons: bool,
+}
+
+impl<V: CellValue> ThicknessConstraint<V> {
+    /// Create a new [ThicknessConstraint] that checks every value and does
+    /// not allow singletons.
+    pub fn new() -> Self {
+        Self {
+            values: None,
+            allow_singletons: false,
+        }
+    }
+
+    /// Only checks cells with one of the given values. Cells with any other
+    /// value may be as thin as they like.
+    pub fn set_values(&mut self, values: &[V]) {
+        self.values = Some(values.iter().copied().collect());
+    }
+
+    /// Sets whether a cell with no same valued neighbor, orthogonal or
+    /// diagonal, is accepted.
+    pub fn set_allow_singletons(&mut self, allowed: bool) {
+        self.allow_singletons = allowed;
+    }
+
+    /// Returns the coordinates of every cell that breaks the constraint, in
+    /// row major order.
+    pub fn violations(&self, grid: &Grid<V>) -> Vec<Coord2D> {
+        let value_at = |x: usize, y: usize| grid.get_cell(&Coord2D::new(x, y))?.get_value();
+        let mut violations = Vec::new();
+        for y in 0..grid.y() {
+            for x in 0..grid.x() {
+                let Some(value) = value_at(x, y) else {
+                    continue;
+                };
+                if self.values.as_ref().is_some_and(|values| !values.contains(&value)) {
+                    continue;
+                }
+
+                // The squares containing the cell have their corner at one of
+                // the four cells up and to the left of it, including itself.
+                let in_square = [(0, 0), (1, 0), (0, 1), (1, 1)].iter().any(|(dx, dy)| {
+                    let (Some(left), Some(top)) = (x.checked_sub(*dx), y.checked_sub(*dy)) else {
+                        return false;
+                    };
+                    [(0, 0), (1, 0), (0, 1), (1, 1)]
+                        .iter()
+                        .all(|(sx, sy)| value_at(left + sx, top + sy) == Some(value))
+                });
+                let singleton = self.allow_singletons
+                    && grid
+                        .neighbors_ref(Coord2D::new(x, y))
+                        .iter()
+                        .all(|neighbor| grid.get_cell(neighbor).and_then(|cell| cell.get_value()) != Some(value));
+                if !in_square && !singleton {
+                    violations.push(Coord2D::new(x, y));
+                }
+            }
+        }
+        violations
+    }
+
+    /// Returns true if no cell of the grid breaks the constraint.
+    pub fn is_satisfied(&self, grid: &Grid<V>) -> bool {
+        self.violations(grid).is_empty()
+    }
+}
@@ -1,41 +1,84 @@
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
 
 use rand::Rng;
 
-pub(crate) struct WeightedIterator<T: Hash + Eq> {
-    items: Vec<(T, usize)>,
-    total_sum: usize,
+/// Yields items in a random order, where items with larger weights are more
+/// likely to come first.
+///
+/// Items with a weight of zero or less are never chosen at random, but are
+/// still yielded once every positively weighted item has been used, so that
+/// no possibility is skipped entirely.
+///
+/// The items are put in an order that does not depend on the iteration order
+/// of the map they came from, so the same random number generator state always
+/// yields the same sequence.
+pub(crate) struct WeightedIterator<'a, T: Hash + Eq, R: Rng> {
+    items: Vec<(T, f64)>,
+    rng: &'a mut R,
 }
 
-impl<T: Hash + Eq> WeightedIterator<T> {
-    pub fn new(map: HashMap<T, usize>) -> Self {
-        let total_sum = map.iter().map(|(_item, chance)| chance).sum();
-        Self {
-            items: map.into_iter().collect(),
-            total_sum,
-        }
+impl<'a, T: Hash + Eq, R: Rng> WeightedIterator<'a, T, R> {
+    pub fn new(map: HashMap<T, usize>, rng: &'a mut R) -> Self {
+        Self::from_weights(
+            map.into_iter()
+                .map(|(item, chance)| (item, chance as f64))
+                .collect(),
+            rng,
+        )
+    }
+
+    /// Create an iterator from items that have already been given floating
+    /// point weights.
+    pub fn from_weights(mut items: Vec<(T, f64)>, rng: &'a mut R) -> Self {
+        items.sort_by_cached_key(|(item, _chance)| stable_hash(item));
+        Self { items, rng }
     }
 }
 
-impl<T: Hash + Eq> Iterator for WeightedIterator<T> {
+impl<T: Hash + Eq, R: Rng> Iterator for WeightedIterator<'_, T, R> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.total_sum == 0 {
+        if self.items.is_empty() {
             return None;
         }
-        let mut chosen_index = None;
-        let mut cumulation = 0;
-        let selection = rand::thread_rng().gen_range(0..self.total_sum);
-        for (index, chance) in self.items.iter().map(|(_item, chance)| chance).enumerate() {
-            cumulation += chance;
-            if cumulation >= selection {
-                chosen_index = Some(index);
-                break;
+        let total_sum: f64 = self
+            .items
+            .iter()
+            .map(|(_item, chance)| chance.max(0.0))
+            .sum();
+
+        // Without any positive weights left, the remaining items are used in
+        // order.
+        let mut chosen_index = 0;
+        if total_sum > 0.0 {
+            let mut cumulation = 0.0;
+            let selection = self.rng.gen_range(0.0..total_sum);
+            for (index, chance) in self.items.iter().map(|(_item, chance)| chance).enumerate() {
+                if *chance <= 0.0 {
+                    continue;
+                }
+                chosen_index = index;
+                cumulation += chance;
+                if cumulation > selection {
+                    break;
+                }
             }
         }
 
-        let (item, _chance) = self.items.swap_remove(chosen_index?);
+        let (item, _chance) = self.items.swap_remove(chosen_index);
         Some(item)
     }
 }
+
+/// Hashes the value the same way every time, unlike the randomly seeded hashers
+/// of HashMaps, so that it can be used to put values in a reproducible order.
+pub(crate) fn stable_hash<T: Hash>(value: &T) -> u64 {
+    // The default hasher uses fixed keys
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
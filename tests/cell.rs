@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use wave_function_collapse::*;
+
+mod common;
+use common::LandCoastSea;
+
+#[test]
+fn possibilities_eq_after_removal() {
+    let mut layout = Grid::new(3, 3);
+    let mut possibilities = HashMap::new();
+    possibilities.insert(LandCoastSea::Land, 3);
+    possibilities.insert(LandCoastSea::Coast, 1);
+    possibilities.insert(LandCoastSea::Sea, 3);
+    layout.add_possibilities(&possibilities);
+
+    // Simulate the propagation of a sea cell in the center
+    let center = Coord2D::new(1, 1);
+    layout.collapse(&center, LandCoastSea::Sea);
+    let coords = layout.neighbors(center);
+    layout.remove_cells_possibility(coords, &LandCoastSea::Land);
+
+    let mut expected = HashMap::new();
+    expected.insert(LandCoastSea::Coast, 1);
+    expected.insert(LandCoastSea::Sea, 3);
+    let corner = layout.get_cell(&Coord2D::new(0, 0)).unwrap();
+    assert!(corner.possibilities_eq(&expected, true));
+
+    // Weights are only compared when requested
+    expected.insert(LandCoastSea::Sea, 1);
+    assert!(!corner.possibilities_eq(&expected, true));
+    assert!(corner.possibilities_eq(&expected, false));
+
+    // Collapsed cells have no possibilities to compare
+    let center = layout.get_cell(&center).unwrap();
+    assert!(!center.possibilities_eq(&expected, false));
+}
+
+#[test]
+fn merge_with() {
+    let mut first = HashMap::new();
+    first.insert(LandCoastSea::Land, 2);
+    first.insert(LandCoastSea::Coast, 1);
+    let mut second = HashMap::new();
+    second.insert(LandCoastSea::Coast, 4);
+    second.insert(LandCoastSea::Sea, 1);
+
+    // Uncollapsed and uncollapsed follow the operation and function
+    let mut cell = Cell::Uncollapsed(first.clone());
+    cell.merge_with(&Cell::Uncollapsed(second.clone()), Operation::Intersection, Function::Max);
+    let mut expected = HashMap::new();
+    expected.insert(LandCoastSea::Coast, 4);
+    assert!(cell.possibilities_eq(&expected, true));
+
+    // Uncollapsed and collapsed
+    let mut cell = Cell::Uncollapsed(first.clone());
+    cell.merge_with(&Cell::Collapsed(LandCoastSea::Land), Operation::Union, Function::Add);
+    assert_eq!(cell.get_value(), Some(LandCoastSea::Land));
+    let mut cell = Cell::Uncollapsed(first.clone());
+    cell.merge_with(&Cell::Collapsed(LandCoastSea::Sea), Operation::Union, Function::Add);
+    assert!(cell.possibilities_eq(&HashMap::new(), false));
+
+    // Collapsed and uncollapsed
+    let mut cell = Cell::Collapsed(LandCoastSea::Sea);
+    cell.merge_with(&Cell::Uncollapsed(second.clone()), Operation::Union, Function::Add);
+    assert_eq!(cell.get_value(), Some(LandCoastSea::Sea));
+    let mut cell = Cell::Collapsed(LandCoastSea::Land);
+    cell.merge_with(&Cell::Uncollapsed(second), Operation::Union, Function::Add);
+    assert!(cell.possibilities_eq(&HashMap::new(), false));
+
+    // Collapsed and collapsed
+    let mut cell = Cell::Collapsed(LandCoastSea::Coast);
+    cell.merge_with(&Cell::Collapsed(LandCoastSea::Coast), Operation::Union, Function::Add);
+    assert_eq!(cell.get_value(), Some(LandCoastSea::Coast));
+    let mut cell = Cell::Collapsed(LandCoastSea::Coast);
+    cell.merge_with(&Cell::Collapsed(LandCoastSea::Land), Operation::Union, Function::Add);
+    assert!(cell.possibilities_eq(&HashMap::new(), false));
+}
+
+#[test]
+fn shrink_after_removals() {
+    let capacity = |cell: &Cell<usize>| match cell {
+        Cell::Uncollapsed(possibilities) => possibilities.capacity(),
+        Cell::Collapsed(_) => 0,
+    };
+
+    let mut cell = Cell::Uncollapsed(HashMap::new());
+    for value in 0..100 {
+        cell.add_possibility(&value);
+    }
+    for value in 2..100 {
+        cell.remove_possibility(&value);
+    }
+    let before = capacity(&cell);
+    cell.shrink();
+    assert!(capacity(&cell) < before);
+    assert_eq!(cell.get_possibilities().len(), 2);
+
+    // Compacting a layout shrinks every cell
+    let mut layout = Grid::new(2, 2);
+    for value in 0..100 {
+        layout.add_possibility(&value);
+    }
+    for value in 1..100 {
+        layout.remove_possibility(&value);
+    }
+    let before = capacity(layout.get_cell(&Coord2D::new(1, 1)).unwrap());
+    layout.compact();
+    assert!(capacity(layout.get_cell(&Coord2D::new(1, 1)).unwrap()) < before);
+}
+
+#[test]
+fn multiply_normalized() {
+    let mut terrain = HashMap::new();
+    terrain.insert(LandCoastSea::Land, 6);
+    terrain.insert(LandCoastSea::Coast, 3);
+    terrain.insert(LandCoastSea::Sea, 1);
+    // The same distribution, reversed and at a different scale
+    let mut climate = HashMap::new();
+    climate.insert(LandCoastSea::Land, 10);
+    climate.insert(LandCoastSea::Coast, 30);
+    climate.insert(LandCoastSea::Sea, 60);
+
+    let mut cell = Cell::Uncollapsed(terrain.clone());
+    cell.merge_cell_possibilities(Operation::Intersection, Function::MultiplyNormalized, &climate);
+    let product = cell.get_possibilities();
+    assert!(product[&LandCoastSea::Coast] > product[&LandCoastSea::Land]);
+    assert_eq!(product[&LandCoastSea::Land], product[&LandCoastSea::Sea]);
+    assert!(product.values().sum::<usize>() <= 1000);
+
+    // Values outside the overlap are dropped and the rest renormalized
+    climate.remove(&LandCoastSea::Sea);
+    let mut cell = Cell::Uncollapsed(terrain);
+    cell.merge_cell_possibilities(Operation::Intersection, Function::MultiplyNormalized, &climate);
+    let mut expected = HashMap::new();
+    expected.insert(LandCoastSea::Land, 400);
+    expected.insert(LandCoastSea::Coast, 600);
+    assert!(cell.possibilities_eq(&expected, true));
+
+    // A weight under one in a thousand stays possible after rounding
+    let mut cell = Cell::Uncollapsed(HashMap::from([(LandCoastSea::Land, 1), (LandCoastSea::Sea, 5000)]));
+    let even = HashMap::from([(LandCoastSea::Land, 1), (LandCoastSea::Sea, 1)]);
+    cell.merge_cell_possibilities(Operation::Intersection, Function::MultiplyNormalized, &even);
+    assert_eq!(cell.get_possibilities()[&LandCoastSea::Land], 1);
+    assert!(cell.entropy().is_finite());
+}
+
+#[test]
+fn probability() {
+    let mut possibilities = HashMap::new();
+    possibilities.insert(LandCoastSea::Land, 7);
+    possibilities.insert(LandCoastSea::Coast, 1);
+    possibilities.insert(LandCoastSea::Sea, 2);
+    let cell = Cell::Uncollapsed(possibilities.clone());
+    let total: f64 = possibilities.keys().map(|value| cell.probability(value)).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+    assert!((cell.probability(&LandCoastSea::Land) - 0.7).abs() < 1e-9);
+
+    let collapsed = Cell::Collapsed(LandCoastSea::Sea);
+    assert_eq!(collapsed.probability(&LandCoastSea::Sea), 1.0);
+    assert_eq!(collapsed.probability(&LandCoastSea::Land), 0.0);
+    assert_eq!(Cell::Uncollapsed(HashMap::new()).probability(&LandCoastSea::Land), 0.0);
+
+    let mut grid = Grid::new(2, 1);
+    grid.add_possibilities(&possibilities);
+    assert_eq!(grid.cell_probability(&Coord2D::new(1, 0), &LandCoastSea::Sea), Some(0.2));
+    assert_eq!(grid.cell_probability(&Coord2D::new(2, 0), &LandCoastSea::Sea), None);
+}
+
+#[test]
+fn float_weights() {
+    let weights = HashMap::from([
+        (LandCoastSea::Land, 0.123457),
+        (LandCoastSea::Coast, 1e-12),
+        (LandCoastSea::Sea, 0.876543),
+    ]);
+    let mut cell = Cell::Uncollapsed(HashMap::new());
+    cell.set_float_weights(&weights);
+    assert_eq!(cell.get_possibilities().get(&LandCoastSea::Coast), Some(&1));
+    let float_error = (cell.probability(&LandCoastSea::Land) - 0.123457).abs();
+    assert!(float_error < 1e-6);
+
+    // Integer weights with two digits of precision are much further off
+    let mut possibilities = HashMap::new();
+    possibilities.insert(LandCoastSea::Land, 12);
+    possibilities.insert(LandCoastSea::Sea, 88);
+    let integer_error = (Cell::Uncollapsed(possibilities).probability(&LandCoastSea::Land) - 0.123457).abs();
+    assert!(float_error * 1000.0 < integer_error);
+
+    let total: f64 = cell.probabilities().values().sum();
+    assert!((total - 1.0).abs() < 1e-9);
+    assert_eq!(
+        Cell::Collapsed(LandCoastSea::Sea).probabilities(),
+        HashMap::from([(LandCoastSea::Sea, 1.0)])
+    );
+
+    // Invalid weights are not added
+    cell.set_float_weights(&HashMap::from([(LandCoastSea::Land, -1.0), (LandCoastSea::Sea, f64::NAN)]));
+    assert!(cell.get_possibilities().is_empty());
+}
+
+#[test]
+fn multiply_and_average() {
+    let mut first = HashMap::new();
+    first.insert(LandCoastSea::Land, 3);
+    first.insert(LandCoastSea::Sea, 5);
+    let mut second = HashMap::new();
+    second.insert(LandCoastSea::Land, 4);
+    second.insert(LandCoastSea::Sea, 4);
+
+    let mut cell = Cell::Uncollapsed(first.clone());
+    cell.merge_cell_possibilities(Operation::Intersection, Function::Multiply, &second);
+    let mut expected = HashMap::new();
+    expected.insert(LandCoastSea::Land, 12);
+    expected.insert(LandCoastSea::Sea, 20);
+    assert!(cell.possibilities_eq(&expected, true));
+
+    // Averages round down, and do not overflow
+    let mut cell = Cell::Uncollapsed(first);
+    cell.merge_cell_possibilities(Operation::Intersection, Function::Average, &second);
+    expected.insert(LandCoastSea::Land, 3);
+    expected.insert(LandCoastSea::Sea, 4);
+    assert!(cell.possibilities_eq(&expected, true));
+    let mut cell = Cell::Uncollapsed(HashMap::from([(LandCoastSea::Land, usize::MAX)]));
+    cell.merge_cell_possibilities(Operation::Intersection, Function::Average, &HashMap::from([(LandCoastSea::Land, usize::MAX)]));
+    assert!(cell.possibilities_eq(&HashMap::from([(LandCoastSea::Land, usize::MAX)]), true));
+}
@@ -1,2 +1,5 @@
+// Not every test uses every shared item.
+#![allow(dead_code)]
+
 mod land_coast_sea;
 pub use land_coast_sea::LandCoastSea;
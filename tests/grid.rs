@@ -0,0 +1,491 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use wave_function_collapse::*;
+
+mod common;
+use common::LandCoastSea;
+
+#[test]
+fn cached_neighbors_match() {
+    let grid: Grid<LandCoastSea> = Grid::new(5, 4);
+    let cached = Grid::<LandCoastSea>::new(5, 4).with_cached_neighbors();
+    for y in 0..4 {
+        for x in 0..5 {
+            let coord = Coord2D::new(x, y);
+            let expected: Vec<_> = grid.neighbors(coord).iter().map(|c| (c.x(), c.y())).collect();
+            let actual: Vec<_> = cached.neighbors(coord).iter().map(|c| (c.x(), c.y())).collect();
+            assert_eq!(expected, actual);
+            assert_eq!(cached.cached_neighbors(coord).unwrap().len(), 8);
+        }
+    }
+    assert!(grid.cached_neighbors(Coord2D::new(0, 0)).is_none());
+    assert!(cached.cached_neighbors(Coord2D::new(5, 0)).is_none());
+
+    // Cached lists are borrowed, and the rest are computed as before
+    let coord = Coord2D::new(2, 1);
+    assert!(matches!(cached.neighbors_ref(coord), Cow::Borrowed(_)));
+    assert!(matches!(cached.neighbor_coords(&coord), Cow::Borrowed(_)));
+    assert!(matches!(grid.neighbors_ref(coord), Cow::Owned(_)));
+    assert!(matches!(cached.neighbors_ref(Coord2D::new(5, 0)), Cow::Owned(_)));
+    for y in 0..5 {
+        assert!(matches!(cached.row_ref(y), Cow::Borrowed(_)) == (y < 4));
+        assert_eq!(cached.row(y), grid.row(y));
+    }
+    for x in 0..6 {
+        assert!(matches!(cached.col_ref(x), Cow::Borrowed(_)) == (x < 5));
+        assert_eq!(cached.col(x), grid.col(x));
+    }
+}
+
+#[test]
+fn cell_entropy() {
+    let mut grid = Grid::new(2, 1);
+    grid.add_possibility(&LandCoastSea::Land);
+    grid.add_possibility(&LandCoastSea::Coast);
+    grid.add_possibility(&LandCoastSea::Sea);
+    grid.collapse(&Coord2D::new(0, 0), LandCoastSea::Land);
+
+    assert_eq!(grid.cell_entropy(&Coord2D::new(0, 0)), Some(0.0));
+    let entropy = grid.cell_entropy(&Coord2D::new(1, 0)).unwrap();
+    assert!((entropy - 3f64.log2()).abs() < 1e-9);
+    assert_eq!(grid.cell_entropy(&Coord2D::new(2, 0)), None);
+}
+
+#[test]
+fn apply_falloff() {
+    let mut grid = Grid::new(9, 9);
+    let mut possibilities = std::collections::HashMap::new();
+    possibilities.insert(LandCoastSea::Land, 100);
+    possibilities.insert(LandCoastSea::Sea, 100);
+    grid.add_possibilities(&possibilities);
+    grid.apply_falloff(Coord2D::new(2, 2), LandCoastSea::Sea, 60, 3);
+
+    let sea_weight = |grid: &Grid<LandCoastSea>, x, y| {
+        grid.get_cell(&Coord2D::new(x, y))
+            .unwrap()
+            .get_possibilities()
+            .get(&LandCoastSea::Sea)
+            .copied()
+    };
+    assert_eq!(sea_weight(&grid, 2, 2), Some(40));
+    assert_eq!(sea_weight(&grid, 3, 1), Some(70));
+    assert_eq!(sea_weight(&grid, 0, 4), Some(80));
+    assert_eq!(sea_weight(&grid, 5, 5), Some(85));
+    assert_eq!(sea_weight(&grid, 6, 2), Some(100));
+    assert_eq!(sea_weight(&grid, 8, 8), Some(100));
+    assert!(sea_weight(&grid, 2, 2) < sea_weight(&grid, 4, 4));
+    assert_eq!(Coord2D::new(1, 7).chebyshev_distance(&Coord2D::new(4, 5)), 3);
+}
+
+#[test]
+fn dimensions() {
+    let grid: Grid<LandCoastSea> = Grid::new(5, 3);
+    assert_eq!(grid.dimensions(), vec![grid.x(), grid.y()]);
+    assert_eq!(grid.dimensions().iter().product::<usize>(), grid.cell_count());
+}
+
+#[test]
+fn coord_index() {
+    let grid: Grid<usize> = Grid::new(4, 3);
+    for (coord, index) in [
+        (Coord2D::new(0, 0), 0),
+        (Coord2D::new(3, 0), 3),
+        (Coord2D::new(0, 2), 8),
+        (Coord2D::new(3, 2), 11),
+    ] {
+        assert_eq!(grid.coord_to_index(coord), Some(index));
+        assert_eq!(grid.index_to_coord(index), Some(coord));
+    }
+    assert_eq!(grid.coord_to_index(Coord2D::new(4, 0)), None);
+    assert_eq!(grid.coord_to_index(Coord2D::new(0, 3)), None);
+    assert_eq!(grid.index_to_coord(12), None);
+}
+
+#[test]
+fn try_new() {
+    let overflow = Grid::<LandCoastSea>::try_new(usize::MAX, usize::MAX);
+    assert!(matches!(overflow, Err(GridError::Overflow { .. })));
+    let empty_row = Grid::<LandCoastSea>::try_new(usize::MAX, 0);
+    assert!(matches!(empty_row, Err(GridError::TooManyCells { .. })));
+    assert!(Grid::<LandCoastSea>::try_with_max_cells(4, 4, 15).is_err());
+
+    let grid = Grid::<LandCoastSea>::try_with_max_cells(4, 4, 16).unwrap();
+    assert_eq!(grid.cell_count(), 16);
+}
+
+#[test]
+fn tiles() {
+    let mut grid = Grid::new(5, 4);
+    grid.collapse_pattern(|coord| match (coord.x() + coord.y()) % 2 {
+        0 => Some(LandCoastSea::Land),
+        _ => Some(LandCoastSea::Sea),
+    });
+    let tiles: Vec<(Coord2D, Tile2D<LandCoastSea, 2, 2>)> = grid.tiles().collect();
+    assert_eq!(tiles.len(), (5 - 1) * (4 - 1));
+    for (coord, tile) in &tiles {
+        assert_eq!(grid.get_tile(*coord), Some(*tile));
+    }
+
+    // Tiles overlapping an uncollapsed cell are skipped
+    grid.get_cell_mut(&Coord2D::new(0, 0)).unwrap().set_possibilities(Default::default());
+    assert_eq!(grid.tiles::<2, 2>().count(), (5 - 1) * (4 - 1) - 1);
+}
+
+#[test]
+fn take_changed_cells() {
+    let mut grid: Grid<LandCoastSea> = Grid::new(3, 3);
+    assert_eq!(grid.take_changed_cells(), None);
+    assert_eq!(grid.take_changed_cells(), Some(Vec::new()));
+
+    grid.collapse(&Coord2D::new(1, 2), LandCoastSea::Land);
+    grid.remove_cell_possibility(&Coord2D::new(0, 0), &LandCoastSea::Sea);
+    grid.remove_cell_possibility(&Coord2D::new(5, 5), &LandCoastSea::Sea);
+    assert_eq!(
+        grid.take_changed_cells(),
+        Some(vec![Coord2D::new(1, 2), Coord2D::new(0, 0)])
+    );
+
+    // Borrowing every cell loses track of the individual changes
+    grid.remove_possibility(&LandCoastSea::Sea);
+    assert_eq!(grid.take_changed_cells(), None);
+}
+
+#[test]
+fn checkpoint_restore() {
+    let mut grid: Grid<LandCoastSea> = Grid::new(3, 3);
+    grid.add_possibility(&LandCoastSea::Land);
+    grid.add_possibility(&LandCoastSea::Sea);
+    grid.take_changed_cells();
+    assert!(grid.checkpoint());
+    grid.collapse(&Coord2D::new(0, 0), LandCoastSea::Land);
+
+    assert!(grid.checkpoint());
+    grid.remove_cell_possibility(&Coord2D::new(1, 1), &LandCoastSea::Sea);
+    grid.remove_possibility(&LandCoastSea::Land);
+    assert!(grid.get_cell(&Coord2D::new(1, 1)).unwrap().get_possibilities().is_empty());
+
+    // Clones do not share the checkpoints
+    let mut clone = grid.clone();
+    clone.restore();
+    assert!(clone.get_cell(&Coord2D::new(1, 1)).unwrap().get_possibilities().is_empty());
+
+    grid.restore();
+    assert_eq!(grid.get_cell(&Coord2D::new(1, 1)).unwrap().get_possibilities().len(), 2);
+    assert_eq!(grid.get_cell(&Coord2D::new(0, 0)).unwrap().get_value(), Some(LandCoastSea::Land));
+    assert_eq!(grid.take_changed_cells(), Some(vec![Coord2D::new(0, 0)]));
+
+    grid.restore();
+    assert!(!grid.get_cell(&Coord2D::new(0, 0)).unwrap().is_collapsed());
+    assert_eq!(grid.take_changed_cells(), Some(Vec::new()));
+
+    // Without a checkpoint, nothing is undone
+    grid.collapse(&Coord2D::new(2, 2), LandCoastSea::Sea);
+    grid.restore();
+    assert!(grid.get_cell(&Coord2D::new(2, 2)).unwrap().is_collapsed());
+}
+
+#[test]
+fn tile_edges() {
+    use LandCoastSea::*;
+    let left: Tile2D<LandCoastSea, 3, 2> = Tile2D::new([
+        [Land, Coast, Sea],
+        [Land, Land, Coast],
+    ]);
+    let right: Tile2D<LandCoastSea, 3, 2> = Tile2D::new([
+        [Sea, Sea, Sea],
+        [Coast, Sea, Sea],
+    ]);
+    assert_eq!(left.edge(Direction::Up), vec![Land, Coast, Sea]);
+    assert_eq!(left.edge(Direction::Left), vec![Land, Land]);
+    assert_eq!(left.edge(Direction::DownRight), vec![Coast]);
+
+    // Compatible to the right, but not below
+    assert_eq!(left.edge(Direction::Right), right.edge(Direction::Left));
+    assert_ne!(left.edge(Direction::Down), right.edge(Direction::Up));
+}
+
+#[test]
+fn tile_from_slice() {
+    use LandCoastSea::*;
+    let tile: Tile2D<LandCoastSea> = Tile2D::from_slice(&[Land, Coast, Sea, Land]).unwrap();
+    assert_eq!(tile, Tile2D::new([[Land, Coast], [Sea, Land]]));
+
+    let wide = Tile2D::<LandCoastSea, 3, 1>::from_slice(&[Land, Coast, Sea]).unwrap();
+    assert_eq!(wide.get(2, 0), Some(&Sea));
+
+    assert_eq!(Tile2D::<LandCoastSea>::from_slice(&[Land, Coast, Sea]), None);
+    assert_eq!(Tile2D::<LandCoastSea>::from_slice(&[Land; 5]), None);
+}
+
+#[test]
+fn tile_set() {
+    let mut tile: Tile2D<LandCoastSea> = Tile2D::new([[LandCoastSea::Sea; 2]; 2]);
+    assert!(tile.set(1, 0, LandCoastSea::Land));
+    assert_eq!(tile.get(1, 0), Some(&LandCoastSea::Land));
+    assert_eq!(tile.get(0, 1), Some(&LandCoastSea::Sea));
+
+    *tile.get_mut(0, 1).unwrap() = LandCoastSea::Coast;
+    assert_eq!(tile.get(0, 1), Some(&LandCoastSea::Coast));
+    tile[(1, 1)] = LandCoastSea::Land;
+    assert_eq!(tile[(1, 1)], LandCoastSea::Land);
+
+    assert!(!tile.set(2, 0, LandCoastSea::Land));
+    assert!(tile.get_mut(0, 2).is_none());
+}
+
+#[test]
+fn collapse_pattern() {
+    let mut grid = Grid::new(4, 3);
+    grid.add_possibility(&LandCoastSea::Coast);
+    grid.collapse_pattern(|coord| match (coord.x() + coord.y()) % 2 {
+        _ if coord.y() == 2 => None,
+        0 => Some(LandCoastSea::Land),
+        _ => Some(LandCoastSea::Sea),
+    });
+    for y in 0..3 {
+        for x in 0..4 {
+            let cell = grid.get_cell(&Coord2D::new(x, y)).unwrap();
+            let expected = match (x + y) % 2 {
+                _ if y == 2 => None,
+                0 => Some(LandCoastSea::Land),
+                _ => Some(LandCoastSea::Sea),
+            };
+            assert_eq!(cell.get_value(), expected);
+        }
+    }
+}
+
+#[test]
+fn collapse_returning() {
+    let mut grid: Grid<LandCoastSea> = Grid::new(3, 3);
+    grid.add_possibility_count(&LandCoastSea::Land, 3);
+    grid.add_possibility(&LandCoastSea::Sea);
+    let coord = Coord2D::new(1, 1);
+    let original = grid.get_cell(&coord).unwrap().get_possibilities();
+
+    let removed = grid.collapse_returning(&coord, LandCoastSea::Land).unwrap();
+    assert_eq!(grid.get_cell(&coord).unwrap().get_value(), Some(LandCoastSea::Land));
+    assert_eq!(grid.collapse_returning(&coord, LandCoastSea::Sea), None);
+    assert_eq!(grid.get_cell(&coord).unwrap().get_value(), Some(LandCoastSea::Land));
+    assert_eq!(grid.collapse_returning(&Coord2D::new(3, 0), LandCoastSea::Sea), None);
+
+    grid.get_cell_mut(&coord).unwrap().set_possibilities(removed);
+    assert!(grid.get_cell(&coord).unwrap().possibilities_eq(&original, true));
+}
+
+#[test]
+fn max_entropy() {
+    let mut grid: Grid<LandCoastSea> = Grid::new(4, 3);
+    grid.add_possibility(&LandCoastSea::Land);
+    grid.add_possibility(&LandCoastSea::Coast);
+    grid.add_possibility(&LandCoastSea::Sea);
+    let max = grid.max_entropy();
+    assert!((max - 12.0 * 3f64.log2()).abs() < 1e-9);
+    assert!((grid.total_entropy() - max).abs() < 1e-9);
+
+    // Uneven weights and collapses lower the entropy below the maximum
+    grid.add_cell_possibility(&Coord2D::new(0, 0), &LandCoastSea::Land);
+    grid.collapse(&Coord2D::new(1, 0), LandCoastSea::Sea);
+    assert!(grid.total_entropy() < grid.max_entropy());
+    assert!(grid.max_entropy() < max);
+
+    // Neither marks any cells as changed
+    grid.take_changed_cells();
+    grid.total_entropy();
+    grid.max_entropy();
+    assert_eq!(grid.take_changed_cells(), Some(Vec::new()));
+}
+
+#[test]
+fn possibility_layers() {
+    let mut grid: Grid<LandCoastSea> = Grid::new(3, 2);
+    grid.add_possibility(&LandCoastSea::Land);
+    grid.add_possibility(&LandCoastSea::Sea);
+    grid.remove_cell_possibility(&Coord2D::new(1, 0), &LandCoastSea::Sea);
+    grid.collapse(&Coord2D::new(2, 1), LandCoastSea::Coast);
+
+    let layers = grid.possibility_layers();
+    assert_eq!(layers.len(), 3);
+    let sea = &layers[&LandCoastSea::Sea];
+    let coast = &layers[&LandCoastSea::Coast];
+    for y in 0..2 {
+        for x in 0..3 {
+            let coord = Coord2D::new(x, y);
+            let expected = (x, y) != (1, 0) && (x, y) != (2, 1);
+            assert_eq!(sea.get_cell(&coord).unwrap().get_value(), Some(expected));
+            assert_eq!(coast.get_cell(&coord).unwrap().get_value(), Some((x, y) == (2, 1)));
+        }
+    }
+}
+
+#[test]
+fn candidates_by_entropy() {
+    let mut grid: Grid<LandCoastSea> = Grid::new(3, 3);
+    grid.add_possibility(&LandCoastSea::Land);
+    grid.add_possibility(&LandCoastSea::Coast);
+    grid.add_possibility(&LandCoastSea::Sea);
+    grid.remove_cell_possibility(&Coord2D::new(2, 2), &LandCoastSea::Sea);
+    grid.remove_cell_possibility(&Coord2D::new(0, 1), &LandCoastSea::Sea);
+    grid.remove_cell_possibility(&Coord2D::new(0, 1), &LandCoastSea::Coast);
+    grid.collapse(&Coord2D::new(1, 1), LandCoastSea::Land);
+
+    // Reading the entropies does not mark any cells as changed
+    grid.take_changed_cells();
+    let candidates = grid.candidates_by_entropy();
+    assert_eq!(grid.take_changed_cells(), Some(Vec::new()));
+    assert_eq!(candidates.len(), 8);
+    assert!(candidates.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    assert!(candidates.iter().all(|(coord, _)| *coord != Coord2D::new(1, 1)));
+    assert_eq!(candidates[0].0, Coord2D::new(0, 1));
+    assert_eq!(candidates[1].0, Coord2D::new(2, 2));
+}
+
+#[test]
+fn merge_from() {
+    let mut base: Grid<LandCoastSea> = Grid::new(3, 2);
+    base.add_possibility_count(&LandCoastSea::Land, 2);
+    base.add_possibility(&LandCoastSea::Coast);
+    base.collapse(&Coord2D::new(1, 1), LandCoastSea::Land);
+    // A larger detail grid is clipped to the base
+    let mut detail: Grid<LandCoastSea> = Grid::new(4, 2);
+    detail.add_possibility_count(&LandCoastSea::Sea, 3);
+
+    base.merge_from(&detail, Operation::Union, Function::Add);
+    let mut expected = std::collections::HashMap::new();
+    expected.insert(LandCoastSea::Land, 2);
+    expected.insert(LandCoastSea::Coast, 1);
+    expected.insert(LandCoastSea::Sea, 3);
+    for y in 0..2 {
+        for x in 0..3 {
+            let cell = base.get_cell(&Coord2D::new(x, y)).unwrap();
+            if (x, y) == (1, 1) {
+                // The detail does not allow the collapsed land
+                assert!(cell.possibilities_eq(&Default::default(), false));
+            } else {
+                assert!(cell.possibilities_eq(&expected, true));
+            }
+        }
+    }
+}
+
+#[test]
+fn thickness_constraint() {
+    // Two land masses joined by a bridge of the given width
+    let islands = |bridge: usize| {
+        let mut grid = Grid::new(8, 4);
+        grid.collapse_pattern(|coord| match (coord.x(), coord.y()) {
+            (0..=1, _) | (6..=7, _) => Some(LandCoastSea::Land),
+            (_, y) if y < bridge => Some(LandCoastSea::Land),
+            _ => Some(LandCoastSea::Sea),
+        });
+        grid
+    };
+    let mut constraint = ThicknessConstraint::new();
+    constraint.set_values(&[LandCoastSea::Land]);
+
+    let thin = islands(1);
+    assert_eq!(
+        constraint.violations(&thin),
+        (2..6).map(|x| Coord2D::new(x, 0)).collect::<Vec<_>>()
+    );
+    assert!(constraint.is_satisfied(&islands(2)));
+
+    // A lone cell is only accepted when singletons are allowed
+    let mut speck = islands(2);
+    speck.collapse(&Coord2D::new(4, 3), LandCoastSea::Land);
+    assert!(!constraint.is_satisfied(&speck));
+    constraint.set_allow_singletons(true);
+    assert!(constraint.is_satisfied(&speck));
+    assert!(!constraint.is_satisfied(&thin));
+}
+
+#[test]
+fn cow_grid() {
+    let mut grid = Grid::new(3, 2);
+    grid.add_possibility(&LandCoastSea::Land);
+    grid.add_possibility(&LandCoastSea::Sea);
+    let mut original = CowGrid::from_grid(&grid);
+    assert_eq!(original.dimensions(), vec![3, 2]);
+
+    // A clone shares every cell until one is changed
+    let mut clone = original.clone();
+    let changed = Coord2D::new(2, 1);
+    assert!(clone.is_shared(&changed));
+    clone.collapse(&changed, LandCoastSea::Sea);
+    assert!(!clone.is_shared(&changed));
+    assert!(clone.is_shared(&Coord2D::new(0, 0)));
+    assert!(!original.get_cell(&changed).unwrap().is_collapsed());
+    assert_eq!(clone.get_cell(&changed).unwrap().get_value(), Some(LandCoastSea::Sea));
+    assert_eq!(clone.take_changed_cells(), None);
+    clone.remove_cell_possibility(&Coord2D::new(1, 1), &LandCoastSea::Land);
+    assert_eq!(clone.take_changed_cells(), Some(vec![Coord2D::new(1, 1)]));
+
+    // Scanning the candidates leaves the collapsed cell shared
+    let mut shared = clone.clone();
+    assert_eq!(shared.candidates().count(), 5);
+    assert!(shared.is_shared(&changed));
+    assert!(original.get_cell(&Coord2D::new(3, 0)).is_none());
+
+    let converted = clone.to_grid();
+    assert_eq!(converted.get_cell(&changed).unwrap().get_value(), Some(LandCoastSea::Sea));
+    assert_eq!(
+        converted.get_cell(&Coord2D::new(1, 1)).unwrap().get_possibilities(),
+        HashMap::from([(LandCoastSea::Sea, 1)])
+    );
+    assert_eq!(original.cells().count(), 6);
+}
+
+#[test]
+fn connected_regions() {
+    // Two land regions that only touch diagonally, separated by sea
+    let mut grid = Grid::new(4, 3);
+    grid.collapse_pattern(|coord| match (coord.x(), coord.y()) {
+        (0..=1, 0) | (0, 1) | (2, 1) => Some(LandCoastSea::Land),
+        (3, 2) => None,
+        _ => Some(LandCoastSea::Sea),
+    });
+
+    let regions = grid.connected_regions();
+    let land: Vec<_> = regions
+        .iter()
+        .filter(|(value, _)| *value == LandCoastSea::Land)
+        .map(|(_, region)| region.clone())
+        .collect();
+    assert_eq!(
+        land,
+        vec![
+            [(0, 0), (1, 0), (0, 1)].map(|(x, y)| Coord2D::new(x, y)).into(),
+            [Coord2D::new(2, 1)].into(),
+        ]
+    );
+
+    // The uncollapsed corner splits the sea into two regions
+    let sea = regions.iter().filter(|(value, _)| *value == LandCoastSea::Sea).count();
+    assert_eq!(sea, 2);
+    let cells: usize = regions.iter().map(|(_, region)| region.len()).sum();
+    assert_eq!(cells, 11);
+}
+
+#[test]
+fn add_possibilities_set() {
+    let values = HashSet::from([LandCoastSea::Land, LandCoastSea::Coast, LandCoastSea::Sea]);
+    let ones: HashMap<_, _> = values.iter().map(|value| (*value, 1)).collect();
+    let mut grid = Grid::new(2, 1);
+    grid.add_possibilities_set(&values);
+    for x in 0..2 {
+        assert!(grid.get_cell(&Coord2D::new(x, 0)).unwrap().possibilities_eq(&ones, true));
+    }
+    let entropy = grid.cell_entropy(&Coord2D::new(1, 0)).unwrap();
+    assert!((entropy - 3f64.log2()).abs() < 1e-9);
+
+    // Adding the same values again increases each weight by one
+    let land = HashSet::from([LandCoastSea::Land]);
+    grid.add_cell_possibilities_set(&Coord2D::new(0, 0), &land);
+    grid.add_cells_possibilities_set(vec![Coord2D::new(0, 0), Coord2D::new(5, 0)], &land);
+    let weights = grid.get_cell(&Coord2D::new(0, 0)).unwrap().get_possibilities();
+    assert_eq!(weights.get(&LandCoastSea::Land), Some(&3));
+    assert_eq!(weights.get(&LandCoastSea::Sea), Some(&1));
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use wave_function_collapse::*;
+
+mod common;
+use common::LandCoastSea;
+
+pub struct GridNDTest<const D: usize> {
+    layout: GridND<LandCoastSea, D>,
+}
+
+impl<const D: usize> GridNDTest<D> {
+    pub fn new(dimensions: [usize; D]) -> Self {
+        let mut layout = GridND::new(dimensions);
+
+        let mut possibilities = HashMap::new();
+        possibilities.insert(LandCoastSea::Coast, 5);
+        possibilities.insert(LandCoastSea::Sea, 100);
+        possibilities.insert(LandCoastSea::Land, 100);
+        layout.add_possibilities(&possibilities);
+
+        Self { layout }
+    }
+}
+
+impl<const D: usize> Wavefunction for GridNDTest<D> {
+    type V = LandCoastSea;
+
+    type L = GridND<Self::V, D>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        value: Self::V,
+    ) {
+        // Simple rules, land cannot be directly next to sea.
+        match value {
+            LandCoastSea::Land => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Sea);
+            }
+            LandCoastSea::Coast => {}
+            LandCoastSea::Sea => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Land)
+            }
+        }
+    }
+}
+
+fn assert_land_not_next_to_sea<const D: usize>(layout: &GridND<LandCoastSea, D>) {
+    let mut layout = layout.clone();
+    let cells: Vec<_> = layout.cells().map(|(coord, cell)| (coord, cell.clone())).collect();
+    for (coord, cell) in cells {
+        assert!(cell.is_collapsed());
+        if cell.get_value() != Some(LandCoastSea::Land) {
+            continue;
+        }
+        for neighbor in layout.neighbors(coord) {
+            if let Some(neighbor) = layout.get_cell(&neighbor) {
+                assert_ne!(neighbor.get_value(), Some(LandCoastSea::Sea));
+            }
+        }
+    }
+}
+
+#[test]
+fn grid_nd_one_dimension() {
+    let grid: GridND<LandCoastSea, 1> = GridND::new([20]);
+    assert_eq!(grid.cell_count(), 20);
+    assert_eq!(grid.neighbors(CoordND::new([5])).len(), 2);
+
+    let mut solver = Solver::new(GridNDTest::new([20]));
+    solver.collapse_initial(CoordND::new([0]), LandCoastSea::Land);
+    solver.collapse_initial(CoordND::new([19]), LandCoastSea::Sea);
+    let output = solver.solve().expect("land and sea should always be solvable");
+    assert_land_not_next_to_sea(&output);
+}
+
+#[test]
+fn grid_nd_three_dimensions() {
+    let grid: GridND<LandCoastSea, 3> = GridND::new([4, 4, 4]);
+    assert_eq!(grid.cell_count(), 64);
+    assert_eq!(Layout::dimensions(&grid), vec![4, 4, 4]);
+    assert_eq!(grid.neighbors(CoordND::new([1, 1, 1])).len(), 26);
+    assert_eq!(grid.orthogonal_neighbors(CoordND::new([1, 1, 1])).len(), 6);
+
+    let mut solver = Solver::new(GridNDTest::new([4, 4, 4]));
+    solver.collapse_initial(CoordND::new([0, 0, 0]), LandCoastSea::Land);
+    solver.collapse_initial(CoordND::new([3, 3, 3]), LandCoastSea::Sea);
+    let output = solver.solve().expect("land and sea should always be solvable");
+    assert_land_not_next_to_sea(&output);
+}
+
+#[test]
+fn grid_3d() {
+    let coord = Coord3D::new([1, 2, 3]);
+    assert_eq!((coord.x(), coord.y(), coord.z()), (1, 2, 3));
+    assert_eq!(coord.up(), Coord3D::new([1, 1, 3]));
+    assert_eq!(coord.down(), Coord3D::new([1, 3, 3]));
+    assert_eq!(coord.left(), Coord3D::new([0, 2, 3]));
+    assert_eq!(coord.right(), Coord3D::new([2, 2, 3]));
+    assert_eq!(coord.backward(), Coord3D::new([1, 2, 2]));
+    assert_eq!(coord.forward(), Coord3D::new([1, 2, 4]));
+
+    let grid: Grid3D<LandCoastSea> = Grid3D::new([3, 2, 4]);
+    let layer = grid.layer(2);
+    assert_eq!(layer.len(), 6);
+    assert_eq!(layer[0], Coord3D::new([0, 0, 2]));
+    assert_eq!(layer[5], Coord3D::new([2, 1, 2]));
+    assert!(layer.iter().all(|coord| grid.get_cell(coord).is_some()));
+    assert!(grid.layer(4).iter().all(|coord| grid.get_cell(coord).is_none()));
+
+    let mut solver = Solver::new(GridNDTest::new([4, 4, 4]));
+    solver.collapse_initial(Coord3D::new([0, 0, 0]).forward(), LandCoastSea::Land);
+    solver.collapse_initial(Coord3D::new([3, 3, 3]).up(), LandCoastSea::Sea);
+    let output: Grid3D<LandCoastSea> = solver.solve().expect("land and sea should always be solvable");
+    assert_land_not_next_to_sea(&output);
+    assert_eq!(output.get_cell(&Coord3D::new([0, 0, 1])).unwrap().get_value(), Some(LandCoastSea::Land));
+}
+
+#[test]
+fn grid_nd_take_changed_cells() {
+    let mut grid: GridND<LandCoastSea, 2> = GridND::new([3, 3]);
+    assert_eq!(grid.take_changed_cells(), None);
+    assert_eq!(grid.take_changed_cells(), Some(Vec::new()));
+
+    grid.collapse(&CoordND::new([1, 2]), LandCoastSea::Land);
+    grid.remove_cell_possibility(&CoordND::new([0, 0]), &LandCoastSea::Sea);
+    grid.remove_cell_possibility(&CoordND::new([5, 5]), &LandCoastSea::Sea);
+    assert_eq!(
+        grid.take_changed_cells(),
+        Some(vec![CoordND::new([1, 2]), CoordND::new([0, 0])])
+    );
+
+    // Borrowing every cell loses track of the individual changes
+    grid.remove_possibility(&LandCoastSea::Sea);
+    assert_eq!(grid.take_changed_cells(), None);
+}
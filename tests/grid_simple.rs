@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use wave_function_collapse::*;
 
@@ -7,6 +10,9 @@ use common::LandCoastSea;
 
 pub struct GridTest {
     layout: Grid<LandCoastSea>,
+    deterministic: bool,
+    /// The number of times collapse was called.
+    calls: Rc<std::cell::Cell<usize>>,
 }
 
 impl GridTest {
@@ -19,7 +25,7 @@ impl GridTest {
         possibilities.insert(LandCoastSea::Land, 100);
         layout.add_possibilities(&possibilities);
 
-        Self { layout }
+        Self { layout, deterministic: false, calls: Rc::default() }
     }
 
     pub fn get_layout_mut(&mut self) -> &mut Grid<LandCoastSea> {
@@ -42,6 +48,7 @@ impl Wavefunction for GridTest {
         coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
         value: Self::V,
     ) {
+        self.calls.set(self.calls.get() + 1);
         // Simple rules, land cannot be directly next to sea.
         // There must be a coast inbetween
         match value {
@@ -56,6 +63,11 @@ impl Wavefunction for GridTest {
             }
         }
     }
+
+    // The rules only touch the neighbors of the collapsed cell
+    fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
 }
 
 #[test]
@@ -76,3 +88,674 @@ fn grid_land_coast_sea() {
     println!("Backtracks: {}", solver.get_backtrack_count());
 
 }
+
+#[test]
+fn grid_land_coast_sea_greedy() {
+    let wavefunction = GridTest::new(50, 20);
+
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_initial(Coord2D::new(0,0), LandCoastSea::Land);
+    solver.collapse_initial(Coord2D::new(25, 10), LandCoastSea::Sea);
+
+    for _ in 0..5 {
+        let mut layout = solver.solve_greedy();
+        println!("Greedy solution:\n{}", layout);
+
+        assert!(layout.cells().all(|(_, cell)| cell.is_collapsed()));
+        assert_eq!(solver.get_backtrack_count(), 0);
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_bias_field() {
+    let wavefunction = GridTest::new(30, 20);
+
+    let mut solver = Solver::new(wavefunction);
+    solver.set_bias_field(|coord: Coord2D| {
+        // Favor coasts everywhere so that neither land nor sea can flood the
+        // whole grid
+        let mut factors = HashMap::new();
+        factors.insert(LandCoastSea::Coast, 10.0);
+        if coord.y() < 10 {
+            factors.insert(LandCoastSea::Land, 10.0);
+            factors.insert(LandCoastSea::Sea, 0.1);
+        } else {
+            factors.insert(LandCoastSea::Land, 0.1);
+            factors.insert(LandCoastSea::Sea, 10.0);
+        }
+        factors
+    });
+    let mut layout = solver.solve().expect("land and sea should always be solvable");
+    println!("Solution:\n{}", layout);
+
+    let mut top_land = 0;
+    let mut bottom_land = 0;
+    for (coord, cell) in layout.cells() {
+        if cell.get_value() == Some(LandCoastSea::Land) {
+            if coord.y() < 10 {
+                top_land += 1;
+            } else {
+                bottom_land += 1;
+            }
+        }
+    }
+    assert!(top_land > bottom_land);
+}
+
+#[test]
+fn grid_land_coast_sea_bias_field_overflow() {
+    let wavefunction = GridTest::new(10, 10);
+
+    let mut solver = Solver::new(wavefunction);
+    solver.set_seed(3);
+    solver.set_bias_field(|coord: Coord2D| {
+        // Infinite factors are ignored, and a huge finite one must not
+        // overflow the total weight
+        let mut factors = HashMap::new();
+        factors.insert(LandCoastSea::Land, f64::INFINITY);
+        factors.insert(LandCoastSea::Coast, f64::NAN);
+        if coord.x() < 5 {
+            factors.insert(LandCoastSea::Sea, f64::MAX);
+        }
+        factors
+    });
+    let mut layout = solver.solve().expect("land and sea should always be solvable");
+    assert!(layout.cells().all(|(_, cell)| cell.is_collapsed()));
+}
+
+#[test]
+fn grid_land_coast_sea_degenerate() {
+    // An empty grid is vacuously solved
+    for (x, y) in [(0, 0), (0, 5), (5, 0)] {
+        let mut solver = Solver::new(GridTest::new(x, y));
+        assert!(solver.validate_setup().is_ok());
+        let layout = solver.solve().expect("an empty grid should be solved");
+        assert_eq!(layout.cell_count(), 0);
+        assert_eq!(solver.get_backtrack_count(), 0);
+        assert!(solver.last_solution_path().is_empty());
+        assert!(solver.is_already_determined());
+        assert_eq!(solver.count_solutions(10), 1);
+    }
+
+    let mut layout = Grid::new(1, 1);
+    layout.add_possibility(&LandCoastSea::Coast);
+    let mut solver = Solver::new(GridTest { layout, deterministic: false, calls: Rc::default() });
+    let layout = solver.solve().expect("a single possibility should be a solution");
+    assert_eq!(layout.get_cell(&Coord2D::new(0, 0)).unwrap().get_value(), Some(LandCoastSea::Coast));
+
+    for (x, y) in [(12, 1), (1, 12)] {
+        let mut solver = Solver::new(GridTest::new(x, y));
+        solver.collapse_initial(Coord2D::new(0, 0), LandCoastSea::Land);
+        solver.collapse_initial(Coord2D::new(x - 1, y - 1), LandCoastSea::Sea);
+        let mut layout = solver.solve().expect("a single row should be solvable");
+        assert!(layout.cells().all(|(_, cell)| cell.is_collapsed()));
+        assert!(GridTest::new(x, y).is_valid(&layout));
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_border() {
+    let wavefunction = GridTest::new(20, 10);
+
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_border(LandCoastSea::Sea);
+
+    // The ring inside the border can no longer be land
+    let initial = solver.get_initial_state();
+    for x in 1..19 {
+        for y in [1, 8] {
+            let possibilities = initial.get_cell(&Coord2D::new(x, y)).unwrap().get_possibilities();
+            assert!(!possibilities.contains_key(&LandCoastSea::Land));
+        }
+    }
+
+    let layout = solver.solve().expect("land and sea should always be solvable");
+    println!("Solution:\n{}", layout);
+    for coord in layout.border() {
+        assert_eq!(layout.get_cell(&coord).unwrap().get_value(), Some(LandCoastSea::Sea));
+    }
+
+    let mut grid: Grid<LandCoastSea> = Grid::new(4, 3);
+    grid.set_border(LandCoastSea::Sea);
+    assert_eq!(grid.border().len(), 10);
+    assert!(!grid.get_cell(&Coord2D::new(1, 1)).unwrap().is_collapsed());
+}
+
+#[test]
+fn grid_land_coast_sea_with_seed() {
+    let solve = |seed| {
+        let mut solver = Solver::with_seed(GridTest::new(30, 15), seed);
+        solver.collapse_initial(Coord2D::new(0, 0), LandCoastSea::Land);
+        solver.collapse_initial(Coord2D::new(20, 10), LandCoastSea::Sea);
+        solver.solve().expect("grid should be solvable").to_string()
+    };
+    let output = solve(11);
+    for _ in 0..3 {
+        assert_eq!(solve(11), output);
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_entropy_floor() {
+    let mut solver = Solver::with_seed(GridTest::new(20, 10), 4);
+    solver.set_entropy_floor(0.5);
+    let mut partial = solver.solve().expect("grid should be solvable");
+    let uncollapsed: Vec<_> = partial
+        .cells()
+        .filter(|(_, cell)| !cell.is_collapsed())
+        .map(|(_, cell)| cell.entropy())
+        .collect();
+    assert!(!uncollapsed.is_empty());
+    assert!(uncollapsed.iter().all(|entropy| *entropy < 0.5));
+
+    let mut layout = solver.finalize(partial).expect("nearly determined cells should be solvable");
+    assert!(layout.cells().all(|(_, cell)| cell.is_collapsed()));
+    assert!(GridTest::new(20, 10).is_valid(&layout));
+}
+
+#[test]
+fn grid_land_coast_sea_recipe() {
+    let mut solver = Solver::new(GridTest::new(20, 10));
+    solver.set_seed(42);
+    solver.set_selection_strategy(SelectionStrategy::MinimumRemainingValues);
+    solver.collapse_initial(Coord2D::new(0, 0), LandCoastSea::Land);
+    solver.collapse_initial(Coord2D::new(10, 5), LandCoastSea::Sea);
+    let output = solver.solve().expect("grid should be solvable");
+
+    let recipe = solver.export_recipe();
+    assert_eq!(recipe.seed, Some(42));
+    assert_eq!(recipe.collapses.len(), 2);
+
+    let mut replayed = Solver::from_recipe(GridTest::new(20, 10), recipe);
+    let replayed_output = replayed.solve().expect("grid should be solvable");
+    assert_eq!(output.to_string(), replayed_output.to_string());
+}
+
+#[test]
+fn grid_land_coast_sea_recipe_settings() {
+    let configure = |solver: &mut Solver<GridTest>| {
+        solver.set_seed(7);
+        solver.set_coherence_weight(0.5);
+        solver.set_value_importance(HashMap::from([(LandCoastSea::Coast, 2.0)]));
+        solver.set_candidate_cache(false);
+        solver.set_batch_collapse(true);
+        solver.set_entropy_floor(0.1);
+        solver.set_max_backtracks(500);
+        solver.set_backtrack_budget_per_cell(50);
+        solver.set_compact_layout(true);
+        solver.set_solve_region(Coord2D::new(2, 1), 15, 8);
+        let allowed = HashSet::from([LandCoastSea::Land, LandCoastSea::Coast]);
+        solver.restrict_initial(Coord2D::new(3, 3), &allowed);
+        solver.collapse_initial(Coord2D::new(12, 6), LandCoastSea::Sea);
+    };
+
+    for scanline in [false, true] {
+        let mut solver = Solver::new(GridTest::new(20, 10));
+        configure(&mut solver);
+        solver.set_scanline_order(scanline);
+        let output = solver.solve().expect("grid should be solvable");
+
+        let recipe = solver.export_recipe();
+        assert_eq!(recipe.restrictions.len(), 1);
+        assert_eq!(recipe.solve_region.as_ref().map(HashSet::len), Some(15 * 8));
+        assert_eq!(recipe.scanline_order, scanline);
+
+        let mut replayed = Solver::from_recipe(GridTest::new(20, 10), recipe.clone());
+        assert_eq!(replayed.export_recipe(), recipe);
+        let replayed_output = replayed.solve().expect("grid should be solvable");
+        // The cells outside the region are left uncollapsed, and their
+        // possibilities are not printed in a stable order.
+        for (coord, cell) in output.cells_ref() {
+            let replayed_cell = replayed_output.get_cell(&coord).unwrap();
+            assert_eq!(cell.get_value(), replayed_cell.get_value());
+            assert_eq!(cell.get_possibilities(), replayed_cell.get_possibilities());
+        }
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_restrict_region() {
+    let mut wavefunction = GridTest::new(20, 10);
+    let layout = wavefunction.get_layout_mut();
+    let border = layout.border();
+    let allowed = HashSet::from([LandCoastSea::Sea, LandCoastSea::Coast]);
+    layout.restrict_region(border, &allowed);
+
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_initial(Coord2D::new(10, 5), LandCoastSea::Land);
+    let layout = solver.solve().expect("interior land should be solvable");
+    println!("Solution:\n{}", layout);
+
+    for coord in layout.border() {
+        let value = layout.get_cell(&coord).unwrap().get_value();
+        assert!(allowed.contains(&value.unwrap()));
+    }
+    assert_eq!(
+        layout.get_cell(&Coord2D::new(10, 5)).unwrap().get_value(),
+        Some(LandCoastSea::Land)
+    );
+}
+
+/// The land and sea rules of [GridTest], declared with a [RuleTable].
+struct RuleGrid {
+    layout: Grid<LandCoastSea>,
+    rules: RuleTable<LandCoastSea>,
+}
+
+impl RuleGrid {
+    fn new(x: usize, y: usize) -> Self {
+        let mut layout = Grid::new(x, y);
+        let mut possibilities = HashMap::new();
+        possibilities.insert(LandCoastSea::Coast, 5);
+        possibilities.insert(LandCoastSea::Sea, 100);
+        possibilities.insert(LandCoastSea::Land, 100);
+        layout.add_possibilities(&possibilities);
+
+        let mut rules = RuleTable::new();
+        for direction in [
+            Direction::UpLeft,
+            Direction::Up,
+            Direction::UpRight,
+            Direction::Left,
+            Direction::Right,
+            Direction::DownLeft,
+            Direction::Down,
+            Direction::DownRight,
+        ] {
+            rules.add_rule(LandCoastSea::Land, direction, &[LandCoastSea::Sea]);
+            rules.add_rule(LandCoastSea::Sea, direction, &[LandCoastSea::Land]);
+        }
+        Self { layout, rules }
+    }
+}
+
+impl Wavefunction for RuleGrid {
+    type V = LandCoastSea;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        value: Self::V,
+    ) {
+        self.rules.apply(layout, coord, value);
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_rule_table() {
+    let wavefunction = RuleGrid::new(30, 20);
+    assert!(wavefunction
+        .rules
+        .get_forbidden(&LandCoastSea::Coast, Direction::Up)
+        .is_none());
+
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_initial(Coord2D::new(0, 0), LandCoastSea::Land);
+    solver.collapse_initial(Coord2D::new(15, 10), LandCoastSea::Sea);
+    let layout = solver.solve().expect("land and sea should always be solvable");
+    println!("Solution:\n{}", layout);
+
+    for y in 0..20 {
+        for x in 0..30 {
+            let coord = Coord2D::new(x, y);
+            if layout.get_cell(&coord).unwrap().get_value() != Some(LandCoastSea::Land) {
+                continue;
+            }
+            for neighbor in layout.neighbors(coord) {
+                let value = layout.get_cell(&neighbor).and_then(|cell| cell.get_value());
+                assert_ne!(value, Some(LandCoastSea::Sea));
+            }
+        }
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_collapse_memo() {
+    let solve = |memo: bool| {
+        let mut wavefunction = GridTest::new(20, 10);
+        wavefunction.deterministic = true;
+        let calls = wavefunction.calls.clone();
+        let mut solver = Solver::new(wavefunction);
+        solver.set_collapse_memo(memo);
+        let mut layouts = Vec::new();
+        let mut counts = Vec::new();
+        // The second solve replays the collapses memoized by the first
+        for _ in 0..2 {
+            solver.set_seed(5);
+            calls.set(0);
+            let mut layout = solver.solve().expect("land and sea should always be solvable");
+            let values: Vec<_> = layout.cells().map(|(_, cell)| cell.get_value()).collect();
+            layouts.push(values);
+            counts.push(calls.get());
+        }
+        (layouts, counts)
+    };
+
+    let (memoized, counts) = solve(true);
+    assert_eq!(memoized[0], memoized[1]);
+    // Every collapse of the second solve was replayed from the memo
+    assert!(counts[0] > 0);
+    assert_eq!(counts[1], 0);
+    let (plain, counts) = solve(false);
+    assert_eq!(memoized, plain);
+    assert_eq!(counts[0], counts[1]);
+}
+
+#[test]
+fn grid_land_coast_sea_dynamic_constraint() {
+    let wavefunction = GridTest::new(20, 10);
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_initial(Coord2D::new(0, 0), LandCoastSea::Land);
+    solver.add_dynamic_constraint(Box::new(|layout: &mut Grid<LandCoastSea>| {
+        layout.remove_possibility(&LandCoastSea::Coast);
+    }));
+    let mut layout = solver.solve().expect("an all land layout should be possible");
+    println!("Solution:\n{}", layout);
+
+    // Without coast, land can never give way to sea
+    assert!(layout.cells().all(|(_, cell)| cell.get_value() == Some(LandCoastSea::Land)));
+}
+
+#[test]
+fn grid_land_coast_sea_scanline_order() {
+    let wavefunction = GridTest::new(8, 5);
+    let mut solver = Solver::new(wavefunction);
+    solver.set_scanline_order(true);
+    let (tx, rx) = std::sync::mpsc::channel();
+    assert!(solver.solve_streaming(tx).is_some());
+
+    // Coast is always possible, so the solve never backtracks
+    let order: Vec<_> = rx
+        .into_iter()
+        .map(|event| match event {
+            SolveEvent::Collapse(coord, _) => (coord.x(), coord.y()),
+            SolveEvent::Undo(coord) => panic!("unexpected undo at {}", coord),
+        })
+        .collect();
+    let scanline: Vec<_> = (0..5).flat_map(|y| (0..8).map(move |x| (x, y))).collect();
+    assert_eq!(order, scanline);
+}
+
+#[test]
+fn grid_land_coast_sea_validate_setup() {
+    let mut wavefunction = GridTest::new(4, 3);
+    assert_eq!(Solver::new(GridTest::new(4, 3)).validate_setup(), Ok(()));
+
+    // Forgetting the possibilities leaves every cell empty
+    *wavefunction.get_layout_mut() = Grid::new(4, 3);
+    let solver = Solver::new(wavefunction);
+    let error = solver.validate_setup().unwrap_err();
+    assert_eq!(error, SetupError::NoPossibilities(Coord2D::new(0, 0)));
+    assert_eq!(
+        error.to_string(),
+        "cell (0, 0) has no possibilities; did you call add_possibilities?"
+    );
+}
+
+#[test]
+fn grid_land_coast_sea_solve_region() {
+    let wavefunction = GridTest::new(8, 8);
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_border(LandCoastSea::Sea);
+    solver.set_solve_region(Coord2D::new(2, 2), 4, 4);
+    let layout = solver.solve().expect("land and sea should always be solvable");
+    println!("Solution:\n{}", layout);
+
+    for y in 0..8 {
+        for x in 0..8 {
+            let cell = layout.get_cell(&Coord2D::new(x, y)).unwrap();
+            let inside = (2..6).contains(&x) && (2..6).contains(&y);
+            let border = x == 0 || y == 0 || x == 7 || y == 7;
+            match cell.get_value() {
+                Some(value) if border => assert_eq!(value, LandCoastSea::Sea),
+                Some(_) => assert!(inside),
+                None => assert!(!inside),
+            }
+        }
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_restrict_initial() {
+    let wavefunction = GridTest::new(8, 8);
+    let mut solver = Solver::new(wavefunction);
+    let coord = Coord2D::new(3, 3);
+    let allowed = HashSet::from([LandCoastSea::Land, LandCoastSea::Coast]);
+    solver.restrict_initial(coord, &allowed);
+    assert!(solver.get_initial_state().get_cell(&coord).unwrap().possibilities_eq(
+        &HashMap::from([(LandCoastSea::Land, 1), (LandCoastSea::Coast, 1)]),
+        false
+    ));
+
+    for _ in 0..20 {
+        let layout = solver.solve().expect("land and sea should always be solvable");
+        let value = layout.get_cell(&coord).unwrap().get_value().unwrap();
+        assert!(allowed.contains(&value));
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_value_feasibility() {
+    let wavefunction = GridTest::new(5, 5);
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_border(LandCoastSea::Sea);
+
+    // Land needs coast between it and the sea, so only the center can be land
+    let land = solver.value_feasibility(LandCoastSea::Land);
+    println!("Land:\n{}", land);
+    for y in 0..5 {
+        for x in 0..5 {
+            let feasible = land.get_cell(&Coord2D::new(x, y)).unwrap().get_value().unwrap();
+            assert_eq!(feasible, x == 2 && y == 2);
+        }
+    }
+
+    let coast = solver.value_feasibility(LandCoastSea::Coast);
+    for y in 0..5 {
+        for x in 0..5 {
+            let feasible = coast.get_cell(&Coord2D::new(x, y)).unwrap().get_value().unwrap();
+            assert_eq!(feasible, (1..4).contains(&x) && (1..4).contains(&y));
+        }
+    }
+    assert!(!solver.get_initial_state().get_cell(&Coord2D::new(2, 2)).unwrap().is_collapsed());
+}
+
+#[test]
+fn grid_land_coast_sea_solve_toward() {
+    // A sketch of land next to sea, which needs coast in between
+    let mut target = Grid::new(6, 6);
+    target.collapse_pattern(|coord| {
+        Some(if coord.x() < 3 { LandCoastSea::Land } else { LandCoastSea::Sea })
+    });
+
+    let wavefunction = GridTest::new(6, 6);
+    let mut solver = Solver::new(wavefunction);
+    let layout = solver.solve_toward(&target).expect("land and sea should always be solvable");
+    println!("Solution:\n{}", layout);
+
+    // Only the cells along the shore can deviate from the sketch
+    for y in 0..6 {
+        for x in 0..6 {
+            let coord = Coord2D::new(x, y);
+            let value = layout.get_cell(&coord).unwrap().get_value();
+            if x != 2 && x != 3 {
+                assert_eq!(value, target.get_cell(&coord).unwrap().get_value());
+            }
+        }
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_tiebreak_noise() {
+    // Every cell starts with the same entropy, so the noise picks the first
+    let center = Coord2D::new(3, 3);
+    for seed in 0..4 {
+        let wavefunction = GridTest::new(7, 7);
+        let mut solver = Solver::new(wavefunction);
+        solver.set_seed(seed);
+        solver.set_tiebreak_noise(move |coord| {
+            let dx = coord.x() as f64 - center.x() as f64;
+            let dy = coord.y() as f64 - center.y() as f64;
+            (dx * dx + dy * dy).sqrt()
+        });
+        solver.solve().expect("land and sea should always be solvable");
+        assert_eq!(solver.last_solution_path()[0].0, center);
+    }
+}
+
+// Placing land resets the cell to its right, even if it was collapsed
+struct Resetting {
+    layout: Grid<LandCoastSea>,
+}
+
+impl Wavefunction for Resetting {
+    type V = LandCoastSea;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(&mut self, layout: &mut Self::L, coord: Coord2D, value: Self::V) {
+        if value == LandCoastSea::Land {
+            if let Some(cell) = layout.get_cell_mut(&coord.right()) {
+                cell.set_possibilities(HashMap::from([(LandCoastSea::Land, 1), (LandCoastSea::Sea, 1)]));
+            }
+        }
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_locked_cells() {
+    let wavefunction = GridTest::new(6, 6);
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_initial(Coord2D::new(1, 1), LandCoastSea::Land);
+    solver.collapse_initial(Coord2D::new(4, 4), LandCoastSea::Sea);
+    let mut locked = solver.locked_cells();
+    locked.sort_by_key(|coord| (coord.y(), coord.x()));
+    assert_eq!(locked, vec![Coord2D::new(1, 1), Coord2D::new(4, 4)]);
+    let layout = solver.solve().expect("land and sea should always be solvable");
+    assert_eq!(layout.get_cell(&Coord2D::new(1, 1)).unwrap().get_value(), Some(LandCoastSea::Land));
+    assert_eq!(layout.get_cell(&Coord2D::new(4, 4)).unwrap().get_value(), Some(LandCoastSea::Sea));
+
+    // Land next to the locked sea would reset it, so it is a contradiction
+    let mut layout = Grid::new(3, 1);
+    layout.add_possibility(&LandCoastSea::Land);
+    layout.add_possibility(&LandCoastSea::Sea);
+    let mut solver = Solver::new(Resetting { layout });
+    let sea = Coord2D::new(2, 0);
+    solver.collapse_initial(sea, LandCoastSea::Sea);
+    for _ in 0..10 {
+        let layout = solver.solve().expect("sea next to sea should be possible");
+        assert_eq!(layout.get_cell(&sea).unwrap().get_value(), Some(LandCoastSea::Sea));
+        assert_eq!(layout.get_cell(&Coord2D::new(1, 0)).unwrap().get_value(), Some(LandCoastSea::Sea));
+    }
+}
+
+struct FirstChooser;
+
+impl<V> Chooser<V> for FirstChooser {
+    fn choose(&mut self, items: &[(V, usize)]) -> Option<usize> {
+        (!items.is_empty()).then_some(0)
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_chooser() {
+    // Every cell tries the same value first, and a grid of one value is
+    // always valid, so the order in which cells are collapsed does not matter
+    let mut solutions = Vec::new();
+    for seed in 0..3 {
+        let mut solver = Solver::new(GridTest::new(8, 8));
+        solver.set_seed(seed);
+        solver.set_chooser(FirstChooser);
+        let mut layout = solver.solve().expect("land and sea should always be solvable");
+        let values: Vec<_> = layout.cells().map(|(_, cell)| cell.get_value().unwrap()).collect();
+        assert!(values.iter().all(|value| *value == values[0]));
+        solutions.push(values);
+    }
+    assert!(solutions.iter().all(|values| *values == solutions[0]));
+
+    // Land and sea share the largest weight, so coast is never chosen
+    let mut solver = Solver::new(GridTest::new(8, 8));
+    solver.set_chooser(MaxWeightChooser);
+    let mut layout = solver.solve().expect("land and sea should always be solvable");
+    assert!(layout.cells().all(|(_, cell)| cell.get_value() != Some(LandCoastSea::Coast)));
+}
+
+#[test]
+fn grid_land_coast_sea_value_importance() {
+    // Only one cell may still become coast, which gives it the highest
+    // entropy of all cells
+    let landmark = Coord2D::new(5, 5);
+    let first_collapse = |importance: HashMap<LandCoastSea, f64>| {
+        let mut wavefunction = GridTest::new(8, 8);
+        let layout = wavefunction.get_layout_mut();
+        let others: Vec<_> = (0..64)
+            .map(|index| Coord2D::new(index % 8, index / 8))
+            .filter(|coord| *coord != landmark)
+            .collect();
+        layout.remove_cells_possibility(others, &LandCoastSea::Coast);
+        let mut solver = Solver::new(wavefunction);
+        solver.set_value_importance(importance);
+        solver.solve().expect("land and sea should always be solvable");
+        solver.last_solution_path()[0].0
+    };
+    for _ in 0..5 {
+        assert_ne!(first_collapse(HashMap::new()), landmark);
+        assert_eq!(first_collapse(HashMap::from([(LandCoastSea::Coast, 10.0)])), landmark);
+    }
+}
+
+#[test]
+fn grid_land_coast_sea_explain_collapse() {
+    let mut solver = Solver::new(GridTest::new(5, 5));
+    let sea = Coord2D::new(0, 2);
+    let removed = solver.explain_collapse(sea, LandCoastSea::Sea);
+    let mut neighbors = GridTest::new(5, 5).get_layout_mut().neighbors(sea);
+    neighbors.retain(|coord| coord.x() < 5 && coord.y() < 5);
+    assert_eq!(removed.len(), neighbors.len());
+    for neighbor in neighbors {
+        assert_eq!(removed[&neighbor], HashSet::from([LandCoastSea::Land]));
+    }
+
+    // The initial state is left as it was
+    let mut initial = solver.get_initial_state().clone();
+    assert!(initial.cells().all(|(_, cell)| cell.get_possibilities().len() == 3));
+}
+
+#[test]
+fn rule_table_validate() {
+    let mut rules = RuleGrid::new(1, 1).rules;
+    let universe = HashSet::from([LandCoastSea::Land, LandCoastSea::Coast, LandCoastSea::Sea]);
+    assert!(rules.validate(&universe).is_empty());
+
+    // Coast may not be below anything, so nothing may be above it
+    for value in universe.iter() {
+        rules.add_rule(*value, Direction::Down, &[LandCoastSea::Coast]);
+    }
+    // Coast forbids land to its left, and land forbids land to its right
+    rules.add_rule(LandCoastSea::Coast, Direction::Left, &[LandCoastSea::Land]);
+    rules.add_rule(LandCoastSea::Land, Direction::Right, &[LandCoastSea::Land]);
+    let mut conflicts = rules.validate(&universe);
+    conflicts.sort_by_key(|conflict| format!("{:?}", conflict.value));
+    assert_eq!(
+        conflicts,
+        vec![
+            RuleConflict {
+                value: LandCoastSea::Coast,
+                directions: vec![Direction::Up],
+            },
+            RuleConflict {
+                value: LandCoastSea::Land,
+                directions: vec![Direction::Right],
+            },
+        ]
+    );
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use wave_function_collapse::*;
+
+mod common;
+use common::LandCoastSea;
+
+pub struct HexTest {
+    layout: HexGrid<LandCoastSea>,
+}
+
+impl HexTest {
+    pub fn new(radius: usize) -> Self {
+        let mut layout = HexGrid::new(radius);
+
+        let mut possibilities = HashMap::new();
+        possibilities.insert(LandCoastSea::Coast, 5);
+        possibilities.insert(LandCoastSea::Sea, 100);
+        possibilities.insert(LandCoastSea::Land, 100);
+        layout.add_possibilities(&possibilities);
+
+        Self { layout }
+    }
+}
+
+impl Wavefunction for HexTest {
+    type V = LandCoastSea;
+
+    type L = HexGrid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(&mut self, layout: &mut Self::L, coord: HexCoord, value: Self::V) {
+        // Simple rules, land cannot be directly next to sea.
+        match value {
+            LandCoastSea::Land => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Sea);
+            }
+            LandCoastSea::Coast => {}
+            LandCoastSea::Sea => {
+                let coords = layout.neighbors(coord);
+                layout.remove_cells_possibility(coords, &LandCoastSea::Land)
+            }
+        }
+    }
+}
+
+#[test]
+fn hex_grid_coords() {
+    let mut grid: HexGrid<LandCoastSea> = HexGrid::new(2);
+    assert_eq!(grid.cell_count(), 19);
+    assert_eq!(HexGrid::<LandCoastSea>::new(0).cell_count(), 1);
+
+    let center = HexCoord::new(0, 0);
+    let neighbors = grid.neighbors(center);
+    assert_eq!(neighbors.len(), 6);
+    assert!(neighbors.iter().all(|neighbor| neighbor.distance(&center) == 1));
+
+    assert!(grid.contains(&HexCoord::new(2, -2)));
+    assert!(grid.contains(&HexCoord::new(-1, -1)));
+    assert!(!grid.contains(&HexCoord::new(2, 1)));
+    assert!(grid.get_cell(&HexCoord::new(-3, 0)).is_none());
+
+    // Every cell is visited once, and can be looked up by its coordinate
+    grid.add_possibility(&LandCoastSea::Sea);
+    assert!(grid.collapse(&HexCoord::new(1, -2), LandCoastSea::Sea));
+    let coords: Vec<_> = grid.cells().map(|(coord, _)| coord).collect();
+    assert_eq!(coords.len(), 19);
+    assert!(coords.iter().all(|coord| grid.contains(coord)));
+    let collapsed: Vec<_> = coords
+        .iter()
+        .filter(|coord| grid.get_cell(coord).unwrap().is_collapsed())
+        .collect();
+    assert_eq!(collapsed, vec![&HexCoord::new(1, -2)]);
+}
+
+#[test]
+fn hex_grid_land_sea() {
+    let mut solver = Solver::new(HexTest::new(6));
+    solver.collapse_initial(HexCoord::new(0, 0), LandCoastSea::Land);
+    solver.collapse_initial(HexCoord::new(6, -6), LandCoastSea::Sea);
+    solver.set_seed(3);
+    let mut layout = solver.solve().expect("hex map should be solvable");
+
+    let cells: Vec<_> = layout.cells().map(|(coord, cell)| (coord, cell.clone())).collect();
+    assert_eq!(cells.len(), 127);
+    for (coord, cell) in cells {
+        assert!(cell.is_collapsed());
+        if cell.get_value() != Some(LandCoastSea::Land) {
+            continue;
+        }
+        for neighbor in layout.neighbors(coord) {
+            if let Some(neighbor) = layout.get_cell(&neighbor) {
+                assert_ne!(neighbor.get_value(), Some(LandCoastSea::Sea));
+            }
+        }
+    }
+    assert_eq!(layout.get_cell(&HexCoord::new(0, 0)).unwrap().get_value(), Some(LandCoastSea::Land));
+}
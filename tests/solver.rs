@@ -0,0 +1,633 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use wave_function_collapse::*;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum Choice {
+    Good,
+    Bad,
+}
+
+/// A row of cells where a bad cell leaves no possibilities for the cell to its
+/// right. Bad is far more likely to be chosen by weight.
+struct BadNeighbor {
+    layout: Grid<Choice>,
+}
+
+impl BadNeighbor {
+    fn new(x: usize) -> Self {
+        let mut layout = Grid::new(x, 1);
+        let mut possibilities = HashMap::new();
+        possibilities.insert(Choice::Good, 1);
+        possibilities.insert(Choice::Bad, 1000);
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl Wavefunction for BadNeighbor {
+    type V = Choice;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        value: Self::V,
+    ) {
+        if value == Choice::Bad {
+            layout.remove_cell_possibility(&coord.right(), &Choice::Good);
+            layout.remove_cell_possibility(&coord.right(), &Choice::Bad);
+        }
+    }
+}
+
+#[test]
+fn least_constraining_value_order() {
+    let mut solver = Solver::new(BadNeighbor::new(10));
+    assert!(solver.solve().is_some());
+    let random_backtracks = solver.get_backtrack_count();
+
+    solver.set_value_order(ValueOrder::LeastConstraining);
+    assert!(solver.solve().is_some());
+    let least_constraining_backtracks = solver.get_backtrack_count();
+
+    println!("Weighted random backtracks: {}", random_backtracks);
+    println!("Least constraining backtracks: {}", least_constraining_backtracks);
+    assert_eq!(least_constraining_backtracks, 0);
+    assert!(random_backtracks > least_constraining_backtracks);
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum Marker {
+    Trap,
+    Left,
+    Right,
+    Zero,
+    One,
+}
+
+/// A row whose first cell may never be a trap, which is only discovered once
+/// every other cell is collapsed. The trap has the highest weight, and the
+/// first cell has a lower entropy, but more possibilities, than the others.
+struct LateTrap {
+    layout: Grid<Marker>,
+}
+
+impl LateTrap {
+    fn new(x: usize) -> Self {
+        let mut layout = Grid::new(x, 1);
+        let mut possibilities = HashMap::new();
+        possibilities.insert(Marker::Zero, 1);
+        possibilities.insert(Marker::One, 1);
+        layout.add_possibilities(&possibilities);
+
+        let mut first = HashMap::new();
+        first.insert(Marker::Trap, 1_000_000_000);
+        first.insert(Marker::Left, 1);
+        first.insert(Marker::Right, 1);
+        layout
+            .get_cell_mut(&Coord2D::new(0, 0))
+            .unwrap()
+            .set_possibilities(first);
+        Self { layout }
+    }
+}
+
+impl Wavefunction for LateTrap {
+    type V = Marker;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        _coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        _value: Self::V,
+    ) {
+        let first = Coord2D::new(0, 0);
+        let rest_collapsed = layout
+            .row(0)
+            .iter()
+            .skip(1)
+            .all(|coord| layout.get_cell(coord).unwrap().is_collapsed());
+        if !rest_collapsed {
+            return;
+        }
+        if layout.get_cell(&first).unwrap().get_value() == Some(Marker::Trap) {
+            layout.clear_cell(&first);
+        } else {
+            layout.remove_cell_possibility(&first, &Marker::Trap);
+        }
+    }
+}
+
+#[test]
+fn adaptive_strategy() {
+    let mut solver = Solver::new(LateTrap::new(13));
+    solver.set_adaptive_backtrack_budget(500);
+
+    // Minimum entropy collapses the trap first and runs out of budget
+    let output = solver.solve_adaptive();
+    assert!(output.is_some());
+    assert_eq!(
+        solver.get_adaptive_strategy(),
+        Some(SelectionStrategy::MinimumRemainingValues)
+    );
+
+    // An unsolvable problem is detected without trying every strategy
+    let mut solver = Solver::new(BadNeighbor::new(3));
+    solver.collapse_initial(Coord2D::new(1, 0), Choice::Bad);
+    assert!(solver.solve_adaptive().is_none());
+    assert_eq!(solver.get_adaptive_strategy(), None);
+}
+
+#[test]
+fn cancel_solve() {
+    // Trapped this way, the search would take far too long to complete
+    let mut solver = Solver::new(LateTrap::new(40));
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let flag = cancel.clone();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        flag.store(true, Ordering::Relaxed);
+    });
+
+    let start = Instant::now();
+    let output = solver.solve_cancellable(cancel);
+    canceller.join().unwrap();
+
+    assert!(output.is_none());
+    assert_eq!(solver.get_stop_reason(), Some(StopReason::Cancelled));
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum Coin {
+    Heads,
+    Tails,
+}
+
+/// Unconstrained cells that are three times as likely to be heads.
+struct Coins {
+    layout: Grid<Coin>,
+}
+
+impl Coins {
+    fn new(x: usize, y: usize) -> Self {
+        let mut layout = Grid::new(x, y);
+        let mut possibilities = HashMap::new();
+        possibilities.insert(Coin::Heads, 3);
+        possibilities.insert(Coin::Tails, 1);
+        layout.add_possibilities(&possibilities);
+        Self { layout }
+    }
+}
+
+impl Wavefunction for Coins {
+    type V = Coin;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        _layout: &mut Self::L,
+        _coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        _value: Self::V,
+    ) {
+    }
+}
+
+#[test]
+fn collapse_rarest() {
+    let heads = |layout: &mut Grid<Coin>| {
+        layout.cells().filter(|(_, cell)| cell.get_value() == Some(Coin::Heads)).count()
+    };
+
+    let mut solver = Solver::new(Coins::new(4, 4));
+    // Ties go to the more likely value
+    assert_eq!(solver.collapse_rarest(Coord2D::new(0, 0)), Some(Coin::Heads));
+    assert_eq!(solver.collapse_rarest(Coord2D::new(0, 0)), None);
+    for y in 0..4 {
+        for x in 0..4 {
+            solver.collapse_rarest(Coord2D::new(x, y));
+        }
+    }
+    let mut rarest = solver.get_initial_state().clone();
+    assert_eq!(heads(&mut rarest), 8);
+
+    // Weighted random choices favor heads
+    let mut random = Solver::with_seed(Coins::new(4, 4), 1).solve().unwrap();
+    assert!(heads(&mut random) > 8);
+}
+
+#[test]
+fn iterative_deepening() {
+    // The trap is committed to before it is discovered, until the horizon
+    // covers the whole row
+    let mut solver = Solver::new(LateTrap::new(6));
+    let mut layout = solver.solve_iterative_deepening(2).expect("the trap can be avoided");
+    assert!(layout.cells().all(|(_, cell)| cell.is_collapsed()));
+    assert_ne!(layout.get_cell(&Coord2D::new(0, 0)).unwrap().get_value(), Some(Marker::Trap));
+    assert!(solver.get_backtrack_count() > 0);
+    assert_eq!(solver.last_solution_path().len(), 6);
+
+    let mut impossible = Solver::new(BadNeighbor::new(4));
+    impossible.collapse_initial(Coord2D::new(1, 0), Choice::Bad);
+    assert!(impossible.solve_iterative_deepening(1).is_none());
+}
+
+static BATCH_STEPS: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn batch_collapse() {
+    // Every cell of the coins ties, so a single step collapses all of them
+    let mut solver = Solver::new(Coins::new(4, 4));
+    solver.set_on_tile_placement(|_| {
+        BATCH_STEPS.fetch_add(1, Ordering::Relaxed);
+    });
+    solver.set_batch_collapse(true);
+    let mut layout = solver.solve().expect("coins should always be solvable");
+    assert!(layout.cells().all(|(_, cell)| cell.is_collapsed()));
+    assert_eq!(BATCH_STEPS.swap(0, Ordering::Relaxed), 1);
+    assert_eq!(solver.last_solution_path().len(), 16);
+
+    solver.set_batch_collapse(false);
+    assert!(solver.solve().is_some());
+    assert_eq!(BATCH_STEPS.load(Ordering::Relaxed), 16);
+
+    // The trap is only found after the batch of the other cells
+    let mut solver = Solver::new(LateTrap::new(6));
+    solver.set_batch_collapse(true);
+    let layout = solver.solve().expect("the trap should be avoided");
+    assert_ne!(layout.get_cell(&Coord2D::new(0, 0)).unwrap().get_value(), Some(Marker::Trap));
+    assert!(solver.get_backtrack_count() > 0);
+}
+
+#[test]
+fn on_step() {
+    let steps = Rc::new(RefCell::new(Vec::new()));
+    let recorded = steps.clone();
+    let mut solver = Solver::with_seed(LateTrap::new(5), 1);
+    solver.set_on_step(move |layout: &Grid<Marker>, coord: &Coord2D| {
+        assert!(layout.get_cell(coord).unwrap().is_collapsed());
+        recorded.borrow_mut().push(*coord);
+    });
+    assert!(solver.solve().is_some());
+
+    // The branches through the trap are included, and the trap is the first
+    // cell, so the solution is found by the last collapses alone
+    let steps = steps.borrow();
+    let path = solver.last_solution_path();
+    assert!(steps.len() > path.len());
+    assert!(steps.ends_with(&path.iter().map(|(coord, _)| *coord).collect::<Vec<_>>()));
+}
+
+#[test]
+fn top_solutions_ordered() {
+    let mut solver = Solver::new(Coins::new(2, 1));
+    let solutions = solver.top_solutions(10);
+    assert_eq!(solutions.len(), 4);
+
+    let likelihoods: Vec<_> = solutions.iter().map(|(_, likelihood)| *likelihood).collect();
+    let expected = [9.0 / 16.0, 3.0 / 16.0, 3.0 / 16.0, 1.0 / 16.0];
+    for (likelihood, expected) in likelihoods.iter().zip(expected) {
+        assert!((likelihood - expected).abs() < 1e-9);
+    }
+
+    let (best, _) = &solutions[0];
+    assert!(best
+        .row(0)
+        .iter()
+        .all(|coord| best.get_cell(coord).unwrap().get_value() == Some(Coin::Heads)));
+
+    assert_eq!(solver.top_solutions(1).len(), 1);
+}
+
+#[test]
+fn is_reproducible() {
+    assert!(!Solver::new(Coins::new(4, 4)).is_reproducible());
+
+    let mut solver = Solver::with_seed(Coins::new(4, 4), 7);
+    assert!(solver.is_reproducible());
+    let first = format!("{:?}", solver.solve().unwrap());
+    solver.set_seed(7);
+    assert_eq!(format!("{:?}", solver.solve().unwrap()), first);
+
+    solver.set_diversity_memory(true);
+    assert!(!solver.is_reproducible());
+    solver.solve();
+    solver.set_diversity_memory(false);
+    assert!(!solver.is_reproducible());
+    solver.clear_diversity_memory();
+    assert!(solver.is_reproducible());
+
+    solver.set_chooser(MaxWeightChooser);
+    assert!(!solver.is_reproducible());
+}
+
+#[test]
+fn seeded_cell_order() {
+    let collapse_order = |seed| {
+        let mut solver = Solver::new(Coins::new(4, 4));
+        solver.set_seed(seed);
+        let (tx, rx) = mpsc::channel();
+        assert!(solver.solve_streaming(tx).is_some());
+        rx.into_iter()
+            .map(|event| match event {
+                SolveEvent::Collapse(coord, _) => coord,
+                SolveEvent::Undo(coord) => panic!("unexpected undo at {}", coord),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Every cell always has the same entropy, so each choice is a tie
+    let order = collapse_order(7);
+    assert_eq!(order.len(), 16);
+    assert_eq!(order, collapse_order(7));
+}
+
+/// A row split into two halves, where neighboring cells within a half must
+/// differ. Cells in different halves never affect each other.
+struct SplitRow {
+    layout: Grid<Coin>,
+}
+
+impl SplitRow {
+    const HALF: usize = 4;
+
+    fn new() -> Self {
+        let mut layout = Grid::new(Self::HALF * 2, 1);
+        layout.add_possibility(&Coin::Heads);
+        layout.add_possibility(&Coin::Tails);
+        Self { layout }
+    }
+
+    fn same_half(first: &Coord2D, second: &Coord2D) -> bool {
+        first.x() / Self::HALF == second.x() / Self::HALF
+    }
+}
+
+impl Wavefunction for SplitRow {
+    type V = Coin;
+    type L = Grid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(
+        &mut self,
+        layout: &mut Self::L,
+        coord: <<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+        value: Self::V,
+    ) {
+        for neighbor in self.coupled_cells(layout, &coord).unwrap() {
+            layout.remove_cell_possibility(&neighbor, &value);
+        }
+    }
+
+    fn coupled_cells(
+        &self,
+        _layout: &Self::L,
+        coord: &<<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate,
+    ) -> Option<Vec<<<Self as Wavefunction>::L as Layout<Self::V>>::Coordinate>> {
+        Some(
+            [coord.left(), coord.right()]
+                .into_iter()
+                .filter(|neighbor| Self::same_half(coord, neighbor))
+                .collect(),
+        )
+    }
+}
+
+#[test]
+fn partition_solving() {
+    let mut solver = Solver::new(SplitRow::new());
+    solver.set_selection_strategy(SelectionStrategy::Random);
+    solver.set_partition_solving(true);
+    let (tx, rx) = mpsc::channel();
+    let solution = solver.solve_streaming(tx).expect("split row should be solvable");
+
+    let row = solution.row(0);
+    for pair in row.windows(2) {
+        if SplitRow::same_half(&pair[0], &pair[1]) {
+            assert_ne!(
+                solution.get_cell(&pair[0]).unwrap().get_value(),
+                solution.get_cell(&pair[1]).unwrap().get_value()
+            );
+        }
+    }
+
+    // Each half is solved completely before the other is started
+    let mut order = Vec::new();
+    for event in rx {
+        match event {
+            SolveEvent::Collapse(coord, _) => order.push(coord),
+            SolveEvent::Undo(coord) => assert_eq!(order.pop(), Some(coord)),
+        }
+    }
+    assert_eq!(order.len(), SplitRow::HALF * 2);
+    let switches = order
+        .windows(2)
+        .filter(|pair| !SplitRow::same_half(&pair[0], &pair[1]))
+        .count();
+    assert_eq!(switches, 1);
+}
+
+#[test]
+fn count_solutions() {
+    let mut solver = Solver::new(Coins::new(2, 2));
+    assert_eq!(solver.count_solutions(1000), 16);
+    assert_eq!(solver.count_solutions(5), 5);
+    assert_eq!(solver.count_solutions(0), 0);
+
+    // Only the alternating halves are solutions
+    let mut solver = Solver::new(SplitRow::new());
+    assert_eq!(solver.count_solutions(1000), 4);
+}
+
+#[test]
+fn diversity_memory() {
+    let distinct_outputs = |diversity| {
+        let mut solver = Solver::new(Coins::new(2, 2));
+        solver.set_seed(3);
+        solver.set_diversity_memory(diversity);
+        let outputs: HashSet<_> = (0..16)
+            .map(|_| format!("{:?}", solver.solve().unwrap()))
+            .collect();
+        outputs.len()
+    };
+
+    let without = distinct_outputs(false);
+    let with = distinct_outputs(true);
+    println!("Distinct without memory: {}, with memory: {}", without, with);
+    assert!(with > without);
+
+    let mut solver = Solver::new(Coins::new(2, 2));
+    solver.set_diversity_memory(true);
+    solver.solve();
+    solver.clear_diversity_memory();
+    assert!(solver.solve().is_some());
+}
+
+#[test]
+fn backtrack_budget_per_cell() {
+    let mut small = Solver::new(LateTrap::new(4));
+    let mut large = Solver::new(LateTrap::new(8));
+    assert_eq!(small.get_backtrack_limit(), None);
+
+    small.set_backtrack_budget_per_cell(3);
+    large.set_backtrack_budget_per_cell(3);
+    assert_eq!(small.get_backtrack_limit(), Some(12));
+    assert_eq!(large.get_backtrack_limit(), Some(24));
+
+    // Trapped this way, the search needs far more backtracks than allowed
+    let mut trapped = Solver::new(LateTrap::new(20));
+    trapped.set_backtrack_budget_per_cell(5);
+    assert!(trapped.solve().is_none());
+    assert_eq!(trapped.get_stop_reason(), Some(StopReason::BacktrackLimit));
+    assert_eq!(trapped.get_backtrack_count(), 100);
+}
+
+#[test]
+fn max_backtracks() {
+    let mut trapped = Solver::new(LateTrap::new(20));
+    trapped.set_max_backtracks(50);
+    assert_eq!(trapped.get_backtrack_limit(), Some(50));
+    assert!(trapped.solve().is_none());
+    assert!(trapped.last_solve_exhausted());
+    assert_eq!(trapped.get_backtrack_count(), 50);
+
+    // The per cell budget of 20 backtracks is stricter
+    trapped.set_backtrack_budget_per_cell(1);
+    assert_eq!(trapped.get_backtrack_limit(), Some(20));
+    assert!(trapped.solve().is_none());
+    assert_eq!(trapped.get_backtrack_count(), 20);
+
+    // A locked trap is a contradiction that the search proves
+    let mut impossible = Solver::new(LateTrap::new(4));
+    impossible.set_max_backtracks(1000);
+    impossible.collapse_initial(Coord2D::new(0, 0), Marker::Trap);
+    assert!(impossible.solve().is_none());
+    assert!(!impossible.last_solve_exhausted());
+
+    let mut solvable = Solver::new(LateTrap::new(4));
+    solvable.set_max_backtracks(1000);
+    assert!(solvable.solve().is_some());
+    assert!(!solvable.last_solve_exhausted());
+}
+
+#[test]
+fn try_solve() {
+    let mut solvable = Solver::new(LateTrap::new(4));
+    assert!(solvable.try_solve().is_ok());
+
+    // Only the trapped first cell is ever left without possibilities
+    let mut impossible = Solver::new(LateTrap::new(4));
+    impossible.collapse_initial(Coord2D::new(0, 0), Marker::Trap);
+    let backtracks = match impossible.try_solve() {
+        Err(SolveError::Contradiction { coord, backtracks }) => {
+            assert_eq!(coord, Coord2D::new(0, 0));
+            backtracks
+        }
+        other => panic!("expected a contradiction, got {:?}", other.map(|_| ())),
+    };
+    assert_eq!(backtracks, impossible.get_backtrack_count());
+
+    let mut trapped = Solver::new(LateTrap::new(20));
+    trapped.set_max_backtracks(10);
+    assert_eq!(trapped.try_solve().err(), Some(SolveError::Exhausted { backtracks: 10 }));
+}
+
+#[test]
+fn coherence_weight() {
+    // Counts the collapses that started a new region, away from every cell
+    // collapsed before them.
+    let new_regions = |weight| {
+        let mut solver = Solver::new(Coins::new(8, 8));
+        solver.set_selection_strategy(SelectionStrategy::Random);
+        solver.set_coherence_weight(weight);
+        let solution = solver.solve().unwrap();
+
+        let mut collapsed = HashSet::new();
+        let mut regions = 0;
+        for (coord, _) in solver.last_solution_path() {
+            let touching = solution
+                .neighbors(coord)
+                .iter()
+                .any(|neighbor| collapsed.contains(neighbor));
+            if !touching {
+                regions += 1;
+            }
+            collapsed.insert(coord);
+        }
+        regions
+    };
+
+    assert!(new_regions(0.0) > 1);
+    assert_eq!(new_regions(1.0), 1);
+}
+
+#[test]
+fn cancel_parallel_attempts() {
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    // None of these attempts could finish in a reasonable time
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let mut solver = Solver::new(LateTrap::new(40));
+                let output = solver.solve_cancellable(cancel);
+                (output.is_some(), solver.get_stop_reason())
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut solver = Solver::new(Coins::new(4, 4));
+    let output = solver.solve_cancellable(cancel.clone());
+    assert!(output.is_some());
+    cancel.store(true, Ordering::Relaxed);
+
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), (false, Some(StopReason::Cancelled)));
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn solve_until_stable() {
+    // A full solve would have to backtrack through every other cell
+    let mut solver = Solver::new(LateTrap::new(40));
+    let mut layout = solver.solve_until_stable();
+    assert_eq!(layout.get_cell(&Coord2D::new(0, 0)).unwrap().get_value(), Some(Marker::Trap));
+
+    // Only the last cell to be chosen is left, since either value springs the trap
+    let uncollapsed: Vec<_> = layout.candidates().map(|(_, cell)| cell.get_possibilities()).collect();
+    assert_eq!(uncollapsed.len(), 1);
+    assert_eq!(uncollapsed[0].len(), 2);
+    assert_eq!(solver.get_backtrack_count(), 0);
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+
+use wave_function_collapse::*;
+
+mod common;
+use common::LandCoastSea;
+
+fn tile(value: LandCoastSea) -> Tile2D<LandCoastSea> {
+    Tile2D::new([[value; 2]; 2])
+}
+
+#[test]
+fn symmetric_adjacency() {
+    let land = tile(LandCoastSea::Land);
+    let sea = tile(LandCoastSea::Sea);
+
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.add_adjacency(land, Direction::Left, sea);
+    wavefunction.add_adjacency(land, Direction::Left, sea);
+    assert!(wavefunction.get_adjacencies(&sea, Direction::Right).is_none());
+
+    wavefunction.set_enforce_symmetric_adjacency(true);
+    let reciprocal = wavefunction
+        .get_adjacencies(&sea, Direction::Right)
+        .expect("reciprocal rule should be added");
+    assert_eq!(reciprocal.get(&land), Some(&2));
+
+    // Rules added after enabling also gain their reciprocal
+    wavefunction.add_adjacency(sea, Direction::Up, land);
+    let reciprocal = wavefunction
+        .get_adjacencies(&land, Direction::Down)
+        .expect("reciprocal rule should be added");
+    assert_eq!(reciprocal.get(&sea), Some(&1));
+}
+
+fn banded_material() -> Grid<LandCoastSea> {
+    let mut material = Grid::new(8, 8);
+    for y in 0..8 {
+        let value = match y {
+            0..=2 => LandCoastSea::Sea,
+            3..=4 => LandCoastSea::Coast,
+            _ => LandCoastSea::Land,
+        };
+        for coord in material.row(y) {
+            material.collapse(&coord, value);
+        }
+    }
+    material
+}
+
+#[test]
+fn learn_from_solution_keeps_rules() {
+    let material = banded_material();
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.learn(&material);
+
+    let directions = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+    let mut original = Vec::new();
+    for x in 0..material.x() {
+        for y in 0..material.y() {
+            let Some(tile) = material.get_tile(Coord2D::new(x, y)) else {
+                continue;
+            };
+            for direction in directions {
+                if let Some(adjacencies) = wavefunction.get_adjacencies(&tile, direction) {
+                    original.push((tile, direction, adjacencies.clone()));
+                }
+            }
+        }
+    }
+    assert!(!original.is_empty());
+
+    let mut solver = Solver::new(wavefunction);
+    let solved = solver.solve().expect("banded material should be solvable");
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.learn(&material);
+    wavefunction.learn_from_solution(&solved, false);
+
+    for (tile, direction, adjacencies) in original {
+        let learned = wavefunction.get_adjacencies(&tile, direction).unwrap();
+        for (adjacent, weight) in adjacencies {
+            assert!(learned.get(&adjacent).copied().unwrap_or(0) >= weight);
+        }
+    }
+}
+
+#[test]
+fn tile_frequencies() {
+    let land = tile(LandCoastSea::Land);
+    let sea = tile(LandCoastSea::Sea);
+
+    // Every tile may be next to every other tile. The adjacency weights are at
+    // least the frequencies, so they do not reduce them.
+    let mut wavefunction = Standard2D::new(20, 20);
+    for first in [land, sea] {
+        for second in [land, sea] {
+            for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                for _ in 0..3 {
+                    wavefunction.add_adjacency(first, direction, second);
+                }
+            }
+        }
+    }
+
+    let mut freqs = HashMap::new();
+    freqs.insert(land, 3);
+    freqs.insert(sea, 1);
+    wavefunction.set_tile_frequencies(freqs);
+
+    let mut solver = Solver::new(wavefunction);
+    let mut solved = solver.solve().expect("unconstrained tiles should be solvable");
+    let land_count = solved
+        .cells()
+        .filter(|(_, cell)| cell.get_value() == Some(land))
+        .count();
+
+    // 300 land tiles are expected out of 400
+    println!("Land tiles: {}", land_count);
+    assert!(land_count > 250 && land_count < 350);
+}
+
+#[test]
+fn isolated_tiles() {
+    let land = tile(LandCoastSea::Land);
+    let coast = tile(LandCoastSea::Coast);
+    let sea = tile(LandCoastSea::Sea);
+
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.set_enforce_symmetric_adjacency(true);
+    for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+        wavefunction.add_adjacency(land, direction, sea);
+    }
+    // Coast may only be to the right of land
+    wavefunction.add_adjacency(land, Direction::Right, coast);
+
+    let right = wavefunction.compatibility_matrix(Direction::Right);
+    assert_eq!(right.get(&land), Some(&HashSet::from([sea, coast])));
+    assert_eq!(right.get(&sea), Some(&HashSet::from([land])));
+    assert_eq!(right.get(&coast), None);
+
+    assert_eq!(wavefunction.find_isolated_tiles(), vec![coast]);
+}
+
+#[test]
+fn pattern_histogram() {
+    let mut material = Grid::new(4, 4);
+    for y in 0..4 {
+        for x in 0..4 {
+            let value = if (x + y) % 2 == 0 { LandCoastSea::Land } else { LandCoastSea::Sea };
+            material.collapse(&Coord2D::new(x, y), value);
+        }
+    }
+
+    let histogram = Standard2D::pattern_histogram::<2, 2>(&material);
+    assert_eq!(histogram.len(), 2);
+    let land_first = Tile2D::new([
+        [LandCoastSea::Land, LandCoastSea::Sea],
+        [LandCoastSea::Sea, LandCoastSea::Land],
+    ]);
+    let sea_first = Tile2D::new([
+        [LandCoastSea::Sea, LandCoastSea::Land],
+        [LandCoastSea::Land, LandCoastSea::Sea],
+    ]);
+    assert_eq!(histogram.get(&land_first), Some(&5));
+    assert_eq!(histogram.get(&sea_first), Some(&4));
+    assert_eq!(Standard2D::pattern_histogram::<3, 3>(&material).values().sum::<usize>(), 4);
+}
+
+#[test]
+fn seeded_collapse_sequence() {
+    let collapse_sequence = || {
+        // Each wavefunction has its own randomly seeded HashMaps
+        let mut wavefunction = Standard2D::new(4, 4);
+        wavefunction.learn(&banded_material());
+        let mut solver = Solver::new(wavefunction);
+        solver.set_seed(9);
+        let (tx, rx) = std::sync::mpsc::channel();
+        solver.solve_streaming(tx).expect("banded material should be solvable");
+        rx.into_iter().collect::<Vec<_>>()
+    };
+
+    let sequence = collapse_sequence();
+    assert!(!sequence.is_empty());
+    assert_eq!(sequence, collapse_sequence());
+}
+
+
+#[test]
+fn edge_matching() {
+    use LandCoastSea::*;
+    let land = tile(Land);
+    let sea = tile(Sea);
+    let shore = Tile2D::new([[Land, Sea], [Land, Sea]]);
+
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.learn_edge_matching(&[land, sea, shore]);
+
+    let right = wavefunction.compatibility_matrix(Direction::Right);
+    assert_eq!(right.get(&land), Some(&HashSet::from([land, shore])));
+    assert_eq!(right.get(&shore), Some(&HashSet::from([sea])));
+    assert_eq!(right.get(&sea), Some(&HashSet::from([sea])));
+    let left = wavefunction.compatibility_matrix(Direction::Left);
+    assert_eq!(left.get(&sea), Some(&HashSet::from([sea, shore])));
+    let down = wavefunction.compatibility_matrix(Direction::Down);
+    assert_eq!(down.get(&shore), Some(&HashSet::from([shore])));
+
+    let mut solver = Solver::new(wavefunction);
+    assert!(solver.solve().is_some());
+}
+
+#[test]
+fn recommended_max_size() {
+    let tiles = [tile(LandCoastSea::Land), tile(LandCoastSea::Coast), tile(LandCoastSea::Sea)];
+    let connected = |links: usize| {
+        let mut wavefunction = Standard2D::new(4, 4);
+        for (index, tile) in tiles.iter().enumerate() {
+            for offset in 0..links {
+                let adjacent = tiles[(index + offset) % tiles.len()];
+                for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    wavefunction.add_adjacency(*tile, direction, adjacent);
+                }
+            }
+        }
+        wavefunction.recommended_max_size()
+    };
+
+    assert_eq!(Standard2D::<LandCoastSea>::new(4, 4).recommended_max_size(), 0);
+    assert!(connected(2) > connected(1));
+    assert_eq!(connected(3), usize::MAX);
+}
+
+#[test]
+fn display_detiled() {
+    let mut wavefunction = Standard2D::new(3, 3);
+    wavefunction.learn(&banded_material());
+    let mut solver = Solver::new(wavefunction);
+    let mut solution = solver.solve().expect("banded material should be solvable");
+
+    let display = solution.display_detiled();
+    assert_eq!(display.lines().count(), 6);
+    assert_eq!(display, solution.detile().to_string());
+}
+
+#[test]
+fn verify_detiled() {
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.learn(&banded_material());
+    let mut solver = Solver::new(wavefunction);
+    let mut solution = solver.solve().expect("banded material should be solvable");
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.learn(&banded_material());
+    assert!(wavefunction.verify_detiled(&solution));
+
+    // The material never has land and sea in one tile
+    let corner = Coord2D::new(1, 1);
+    let original = solution.get_cell(&corner).unwrap().clone();
+    let mixed = Tile2D::new([
+        [LandCoastSea::Land, LandCoastSea::Sea],
+        [LandCoastSea::Sea, LandCoastSea::Land],
+    ]);
+    *solution.get_cell_mut(&corner).unwrap() = Cell::Collapsed(mixed);
+    assert!(!wavefunction.verify_detiled(&solution));
+    *solution.get_cell_mut(&corner).unwrap() = original;
+    assert!(wavefunction.verify_detiled(&solution));
+
+    // An uncollapsed cell cannot be checked
+    solution.clear_cell(&Coord2D::new(2, 2));
+    assert!(!wavefunction.verify_detiled(&solution));
+}
+
+fn brute_force_sat(cnf: &CnfFormula<Tile2D<LandCoastSea>>) -> Option<Vec<bool>> {
+    let count = cnf.variable_count();
+    (0..1u32 << count)
+        .map(|bits| (0..count).map(|index| bits & (1 << index) != 0).collect::<Vec<_>>())
+        .find(|assignment| cnf.is_satisfied_by(assignment))
+}
+
+fn checkerboard(x: usize, y: usize) -> Standard2D<LandCoastSea> {
+    let land = tile(LandCoastSea::Land);
+    let sea = tile(LandCoastSea::Sea);
+
+    // Neighbors must differ, so the grid is a checkerboard
+    let mut wavefunction = Standard2D::new(x, y);
+    for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+        wavefunction.add_adjacency(land, direction, sea);
+        wavefunction.add_adjacency(sea, direction, land);
+    }
+    wavefunction.set_tile_frequencies(HashMap::from([(land, 1), (sea, 1)]));
+    wavefunction
+}
+
+#[test]
+fn to_cnf() {
+    let land = tile(LandCoastSea::Land);
+    let mut wavefunction = checkerboard(2, 2);
+    let cnf = wavefunction.to_cnf(wavefunction.get_initial_state());
+    assert_eq!(cnf.variable_count(), 8);
+    assert!(cnf.to_dimacs().starts_with(&format!("p cnf 8 {}\n", cnf.clauses().len())));
+    let model = brute_force_sat(&cnf).expect("checkerboard should be satisfiable");
+    assert!(wavefunction.is_valid(&cnf.decode(&model)));
+    assert!(Solver::new(wavefunction).solve().is_some());
+
+    // Two land tiles next to each other make both unsatisfiable
+    let wavefunction = checkerboard(2, 2);
+    let mut grid = wavefunction.get_initial_state().clone();
+    grid.collapse(&Coord2D::new(0, 0), land);
+    grid.collapse(&Coord2D::new(1, 0), land);
+    assert!(brute_force_sat(&wavefunction.to_cnf(&grid)).is_none());
+
+    let mut solver = Solver::new(wavefunction);
+    solver.collapse_initial(Coord2D::new(0, 0), land);
+    solver.collapse_initial(Coord2D::new(1, 0), land);
+    assert!(solver.solve().is_none());
+}
+
+#[test]
+fn adjacency_stats() {
+    use LandCoastSea::*;
+    // Columns of land, land, sea, sea, sea
+    let columns = [Land, Land, Sea, Sea, Sea];
+    let mut material = Grid::new(5, 2);
+    for y in 0..2 {
+        for (x, value) in columns.iter().enumerate() {
+            material.collapse(&Coord2D::new(x, y), *value);
+        }
+    }
+    let mut wavefunction = Standard2D::new(4, 4);
+    wavefunction.learn(&material);
+
+    // The tiles at x = 0, 1, 2, 3 are land, shore, sea and sea, so:
+    // land: right {sea}
+    // shore: right {sea}
+    // sea: left {land, shore}
+    let stats = wavefunction.adjacency_stats();
+    assert_eq!(stats.tile_count, 3);
+    assert_eq!(stats.rule_count, 4);
+    assert_eq!(stats.average_neighbors, 4.0 / 12.0);
+    assert_eq!(stats.min_neighbors, 0);
+    assert_eq!(stats.max_neighbors, 2);
+
+    let empty = Standard2D::<LandCoastSea>::new(4, 4).adjacency_stats();
+    assert_eq!((empty.tile_count, empty.rule_count, empty.max_neighbors), (0, 0, 0));
+    assert_eq!(empty.average_neighbors, 0.0);
+}
+
+#[test]
+fn partition_solving() {
+    use std::sync::mpsc;
+
+    // A collapsed column splits the grid into two independent halves
+    let mut solver = Solver::new(checkerboard(5, 2));
+    solver.set_selection_strategy(SelectionStrategy::Random);
+    solver.set_partition_solving(true);
+    solver.collapse_initial(Coord2D::new(2, 0), tile(LandCoastSea::Land));
+    solver.collapse_initial(Coord2D::new(2, 1), tile(LandCoastSea::Sea));
+    let (tx, rx) = mpsc::channel();
+    let mut solution = solver.solve_streaming(tx).expect("checkerboard should be solvable");
+    assert!(solution.cells().all(|(_, cell)| cell.is_collapsed()));
+
+    let mut order = Vec::new();
+    for event in rx {
+        match event {
+            SolveEvent::Collapse(coord, _) => order.push(coord),
+            SolveEvent::Undo(coord) => assert_eq!(order.pop(), Some(coord)),
+        }
+    }
+    assert_eq!(order.len(), 8);
+    let switches = order
+        .windows(2)
+        .filter(|pair| (pair[0].x() < 2) != (pair[1].x() < 2))
+        .count();
+    assert_eq!(switches, 1);
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::mpsc};
 
 use wave_function_collapse::*;
 
@@ -30,6 +30,12 @@ impl Sudoku {
     }
 }
 
+impl Default for Sudoku {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Wavefunction for Sudoku {
     type V = usize;
     type L = Grid<Self::V>;
@@ -58,7 +64,7 @@ impl Wavefunction for Sudoku {
         } else if coord.y() < 6 {
             [3, 4, 5]
         } else {
-            [7, 8, 9]
+            [6, 7, 8]
         };
 
         // Which subgrid cols
@@ -67,7 +73,7 @@ impl Wavefunction for Sudoku {
         } else if coord.x() < 6 {
             [3, 4, 5]
         } else {
-            [7, 8, 9]
+            [6, 7, 8]
         };
 
         let mut subgrid = Vec::new();
@@ -103,11 +109,7 @@ impl Wavefunction for Sudoku {
     }
 }
 
-#[test]
-fn sudoku_easy() {
-    let wavefunction = Sudoku::new();
-
-    let mut solver = Solver::new(wavefunction);
+fn collapse_easy(solver: &mut Solver<Sudoku>) {
     solver.collapse_initial(Coord2D::new(3, 0), 4);
     solver.collapse_initial(Coord2D::new(5, 0), 9);
     solver.collapse_initial(Coord2D::new(6, 0), 8);
@@ -154,6 +156,14 @@ fn sudoku_easy() {
     solver.collapse_initial(Coord2D::new(4, 8), 7);
     solver.collapse_initial(Coord2D::new(5, 8), 3);
     solver.collapse_initial(Coord2D::new(7, 8), 5);
+}
+
+#[test]
+fn sudoku_easy() {
+    let wavefunction = Sudoku::new();
+
+    let mut solver = Solver::new(wavefunction);
+    collapse_easy(&mut solver);
 
     println!("Initial State:");
     solver.print_layout();
@@ -172,13 +182,12 @@ fn sudoku_easy() {
 
     assert!(output.is_some());
     // TODO: Assert that this is exactly equal to the correct output
-}
 
-#[test]
-fn sudoku_hard() {
-    let wavefunction = Sudoku::new();
+    // A cap of 2 is enough to show that the solution is unique
+    assert_eq!(solver.count_solutions(2), 1);
+}
 
-    let mut solver = Solver::new(wavefunction);
+fn collapse_hard(solver: &mut Solver<Sudoku>) {
     solver.collapse_initial(Coord2D::new(1, 0), 3);
     solver.collapse_initial(Coord2D::new(3, 0), 8);
     solver.collapse_initial(Coord2D::new(5, 0), 2);
@@ -213,6 +222,14 @@ fn sudoku_hard() {
     solver.collapse_initial(Coord2D::new(3, 8), 9);
     solver.collapse_initial(Coord2D::new(5, 8), 3);
     solver.collapse_initial(Coord2D::new(7, 8), 7);
+}
+
+#[test]
+fn sudoku_hard() {
+    let wavefunction = Sudoku::new();
+
+    let mut solver = Solver::new(wavefunction);
+    collapse_hard(&mut solver);
 
     println!("Initial State:");
     solver.print_layout();
@@ -270,3 +287,312 @@ fn sudoku_invalid() {
 
     assert!(output.is_none());
 }
+
+#[test]
+fn sudoku_is_valid() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_easy(&mut solver);
+    let mut solution = solver.solve().expect("easy sudoku should be solvable");
+    assert!(Sudoku::new().is_valid(&solution));
+
+    // Swapping two values in a row breaks their columns
+    let first = Coord2D::new(0, 0);
+    let second = Coord2D::new(1, 0);
+    let first_value = solution.get_cell(&first).unwrap().get_value().unwrap();
+    let second_value = solution.get_cell(&second).unwrap().get_value().unwrap();
+    solution.collapse(&first, second_value);
+    solution.collapse(&second, first_value);
+    assert!(!Sudoku::new().is_valid(&solution));
+}
+
+#[test]
+fn sudoku_streaming() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_hard(&mut solver);
+
+    let (tx, rx) = mpsc::channel();
+    let mut solution = solver.solve_streaming(tx).expect("hard sudoku should be solvable");
+
+    let mut replayed = HashMap::new();
+    let mut undos = 0;
+    for event in rx {
+        match event {
+            SolveEvent::Collapse(coord, value) => {
+                assert!(replayed.insert(coord, value).is_none());
+            }
+            SolveEvent::Undo(coord) => {
+                assert!(replayed.remove(&coord).is_some());
+                undos += 1;
+            }
+        }
+    }
+    println!("Undo events: {}", undos);
+
+    // Every cell the solver collapsed was replayed, the rest were givens
+    let initial = solver.get_initial_state().clone();
+    for (coord, cell) in solution.cells() {
+        let value = cell.get_value().unwrap();
+        match replayed.get(&coord) {
+            Some(replayed) => assert_eq!(*replayed, value),
+            None => assert!(initial.get_cell(&coord).unwrap().is_collapsed()),
+        }
+    }
+}
+
+#[test]
+fn sudoku_already_determined() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_easy(&mut solver);
+    let solution = solver.solve().expect("easy sudoku should be solvable");
+
+    let missing = Coord2D::new(4, 4);
+    let mut solver = Solver::new(Sudoku::new());
+    for y in 0..9 {
+        for x in 0..9 {
+            let coord = Coord2D::new(x, y);
+            if coord != missing {
+                let value = solution.get_cell(&coord).unwrap().get_value().unwrap();
+                solver.collapse_initial(coord, value);
+            }
+        }
+    }
+    assert!(solver.is_already_determined());
+    assert!(!solver.get_initial_state().get_cell(&missing).unwrap().is_collapsed());
+
+    assert!(!Solver::new(Sudoku::new()).is_already_determined());
+}
+
+#[test]
+fn sudoku_count_solutions() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_easy(&mut solver);
+    assert_eq!(solver.count_solutions(1000), 1);
+}
+
+#[test]
+fn sudoku_solution_path() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_hard(&mut solver);
+    let solution = solver.solve().expect("hard sudoku should be solvable");
+
+    let path = solver.last_solution_path();
+    let mut replayed = solver.get_initial_state().clone();
+    let uncollapsed = replayed.candidates().count();
+    assert_eq!(path.len(), uncollapsed);
+    for (coord, value) in path {
+        assert!(replayed.collapse(&coord, value));
+    }
+    assert_eq!(replayed.to_string(), solution.to_string());
+}
+
+#[test]
+fn sudoku_candidate_cache() {
+    for enabled in [true, false] {
+        let mut solver = Solver::new(Sudoku::new());
+        solver.set_candidate_cache(enabled);
+        collapse_hard(&mut solver);
+        let mut solution = solver.solve().expect("hard sudoku should be solvable");
+        assert!(Sudoku::new().is_valid(&solution));
+        assert_eq!(solution.candidates().count(), 0);
+    }
+}
+
+#[test]
+fn sudoku_diagnose_givens() {
+    let mut solver = Solver::new(Sudoku::new());
+    let givens = [
+        ((0, 0), 1),
+        ((2, 0), 4),
+        ((0, 1), 2),
+        ((2, 1), 5),
+        ((0, 2), 3),
+        ((2, 2), 6),
+        ((1, 3), 7),
+        ((1, 4), 8),
+        ((1, 5), 9),
+    ];
+    for ((x, y), value) in givens {
+        solver.collapse_initial(Coord2D::new(x, y), value);
+    }
+
+    // The middle column of the first box can only hold 7, 8 and 9, but the
+    // column below already contains them
+    let removed: Vec<_> = solver
+        .diagnose_givens()
+        .into_iter()
+        .map(|(coord, value)| ((coord.x(), coord.y()), value))
+        .collect();
+    assert_eq!(removed, vec![((1, 3), 7), ((1, 4), 8), ((1, 5), 9)]);
+
+    // The givens are restored afterwards
+    assert!(solver.solve().is_none());
+}
+
+#[test]
+fn sudoku_collapse_initial_batch() {
+    let mut single = Solver::new(Sudoku::new());
+    collapse_easy(&mut single);
+    let mut expected = single.get_initial_state().clone();
+    let givens: Vec<_> = expected
+        .cells()
+        .filter_map(|(coord, cell)| cell.get_value().map(|value| (coord, value)))
+        .collect();
+
+    let mut batch = Solver::new(Sudoku::new());
+    batch.collapse_initial_batch(&givens);
+    let actual = batch.get_initial_state();
+    for (coord, cell) in expected.cells() {
+        let batch_cell = actual.get_cell(&coord).unwrap();
+        assert_eq!(batch_cell.get_value(), cell.get_value());
+        assert_eq!(batch_cell.get_possibilities(), cell.get_possibilities());
+    }
+    assert!(batch.solve().is_some());
+}
+
+#[test]
+fn sudoku_estimate_difficulty() {
+    let mut easy = Solver::new(Sudoku::new());
+    collapse_easy(&mut easy);
+    let mut hard = Solver::new(Sudoku::new());
+    collapse_hard(&mut hard);
+
+    let easy = easy.estimate_difficulty();
+    let hard = hard.estimate_difficulty();
+    println!("Easy: {easy:?}, hard: {hard:?}");
+    assert!(easy < hard);
+    assert_ne!(hard, Difficulty::Unsolvable);
+}
+
+#[test]
+fn sudoku_resolve_after_change() {
+    let mut solver = Solver::new(Sudoku::new());
+    solver.set_seed(7);
+    solver.collapse_initial(Coord2D::new(0, 0), 1);
+    solver.collapse_initial(Coord2D::new(4, 4), 5);
+    let previous = solver.solve().expect("sparse sudoku should be solvable");
+
+    let changed = Coord2D::new(0, 0);
+    let solution = solver
+        .resolve_after_change(changed, 2)
+        .expect("changed sudoku should be solvable");
+    assert!(Sudoku::new().is_valid(&solution));
+    assert_eq!(solution.get_cell(&changed).unwrap().get_value(), Some(2));
+    assert_eq!(solution.get_cell(&Coord2D::new(4, 4)).unwrap().get_value(), Some(5));
+    assert_eq!(solver.get_initial_state().get_cell(&changed).unwrap().get_value(), Some(2));
+
+    // A fresh solve would share few values with the previous solution
+    let mut kept = 0;
+    for y in 0..9 {
+        for x in 0..9 {
+            let coord = Coord2D::new(x, y);
+            let old = previous.get_cell(&coord).unwrap().get_value();
+            let new = solution.get_cell(&coord).unwrap().get_value();
+            if old == new {
+                kept += 1;
+            }
+        }
+    }
+    println!("Kept {} of 81 cells", kept);
+    assert!(kept > 40);
+}
+
+#[test]
+fn sudoku_hidden_singles() {
+    let mut groups = Vec::new();
+    for index in 0..9 {
+        groups.push((0..9).map(|x| Coord2D::new(x, index)).collect());
+        groups.push((0..9).map(|y| Coord2D::new(index, y)).collect());
+        let (box_x, box_y) = (index % 3 * 3, index / 3 * 3);
+        groups.push((0..9).map(|cell| Coord2D::new(box_x + cell % 3, box_y + cell / 3)).collect());
+    }
+
+    // The 1s leave the top left cell as the only place for a 1 in the first row
+    let mut solver = Solver::new(Sudoku::new());
+    solver.collapse_initial(Coord2D::new(4, 1), 1);
+    solver.collapse_initial(Coord2D::new(7, 2), 1);
+    solver.collapse_initial(Coord2D::new(1, 3), 1);
+    solver.collapse_initial(Coord2D::new(2, 6), 1);
+    let corner = Coord2D::new(0, 0);
+    assert_eq!(solver.get_initial_state().get_cell(&corner).unwrap().get_possibilities().len(), 9);
+
+    assert!(solver.propagate_hidden_singles(&groups) >= 1);
+    assert_eq!(solver.get_initial_state().get_cell(&corner).unwrap().get_value(), Some(1));
+    assert_eq!(solver.propagate_hidden_singles(&groups), 0);
+    assert!(solver.solve().is_some());
+}
+
+#[test]
+fn sudoku_on_backtrack() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_hard(&mut solver);
+    solver.set_seed(3);
+    let backtracks = Rc::new(RefCell::new(Vec::new()));
+    let recorded = backtracks.clone();
+    solver.set_on_backtrack(move |depth, coord| recorded.borrow_mut().push((depth, *coord)));
+
+    solver.solve().expect("hard sudoku should be solvable");
+    let backtracks = backtracks.borrow();
+    println!("Backtracks: {:?}", backtracks);
+    assert!(!backtracks.is_empty());
+    assert_eq!(backtracks.len(), solver.get_backtrack_count() as usize);
+    // The givens are not part of the search
+    assert!(backtracks.iter().all(|(depth, _)| *depth < 81 - 26));
+}
+
+#[test]
+fn sudoku_entropy_trace() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_hard(&mut solver);
+    solver.set_record_entropy(true);
+    solver.solve().expect("hard sudoku should be solvable");
+
+    let trace = solver.entropy_trace();
+    assert_eq!(trace.len(), solver.last_solution_path().len());
+    assert!(trace.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert_eq!(trace.last(), Some(&0.0));
+}
+
+#[test]
+fn sudoku_solve_with_confidence() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_hard(&mut solver);
+    let (_, confidence) = solver.solve_with_confidence().expect("hard sudoku should be solvable");
+    assert_eq!(confidence.len(), 81);
+
+    let givens: Vec<_> = solver
+        .get_initial_state()
+        .clone()
+        .cells()
+        .filter(|(_, cell)| cell.is_collapsed())
+        .map(|(coord, _)| coord)
+        .collect();
+    for coord in &givens {
+        assert_eq!(confidence[coord], 1.0);
+    }
+
+    // Some cells are forced by propagation, while others are free choices
+    let path = solver.last_solution_path();
+    let (forced, chosen): (Vec<f64>, Vec<f64>) =
+        path.iter().map(|(coord, _)| confidence[coord]).partition(|c| *c == 1.0);
+    assert!(!forced.is_empty());
+    assert!(!chosen.is_empty());
+    assert!(chosen.iter().all(|c| *c > 0.0 && *c <= 0.5));
+}
+
+#[test]
+fn sudoku_iterative_deepening() {
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_easy(&mut solver);
+    let expected = solver.solve().expect("easy sudoku should be solvable").to_string();
+    for increment in [1, 5, 81] {
+        let solution = solver.solve_iterative_deepening(increment).expect("easy sudoku should be solvable");
+        assert_eq!(solution.to_string(), expected);
+    }
+
+    // The hard sudoku cannot be solved without backtracking across rounds
+    let mut solver = Solver::new(Sudoku::new());
+    collapse_hard(&mut solver);
+    let expected = solver.solve().expect("hard sudoku should be solvable").to_string();
+    let solution = solver.solve_iterative_deepening(10).expect("hard sudoku should be solvable");
+    assert_eq!(solution.to_string(), expected);
+}
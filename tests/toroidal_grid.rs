@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use wave_function_collapse::*;
+
+mod common;
+use common::LandCoastSea;
+
+pub struct ToroidalTest {
+    layout: ToroidalGrid<LandCoastSea>,
+}
+
+impl ToroidalTest {
+    pub fn new(x: usize, y: usize) -> Self {
+        let mut layout = ToroidalGrid::new(x, y);
+
+        let mut possibilities = HashMap::new();
+        possibilities.insert(LandCoastSea::Coast, 5);
+        possibilities.insert(LandCoastSea::Sea, 100);
+        possibilities.insert(LandCoastSea::Land, 100);
+        layout.add_possibilities(&possibilities);
+
+        Self { layout }
+    }
+}
+
+impl Wavefunction for ToroidalTest {
+    type V = LandCoastSea;
+
+    type L = ToroidalGrid<Self::V>;
+
+    fn get_initial_state(&self) -> &Self::L {
+        &self.layout
+    }
+
+    fn collapse(&mut self, layout: &mut Self::L, coord: Coord2D, value: Self::V) {
+        // Simple rules, land cannot be directly next to sea.
+        match value {
+            LandCoastSea::Land => {
+                let coords = coord.neighbors();
+                layout.remove_cells_possibility(coords, &LandCoastSea::Sea);
+            }
+            LandCoastSea::Coast => {}
+            LandCoastSea::Sea => {
+                let coords = coord.neighbors();
+                layout.remove_cells_possibility(coords, &LandCoastSea::Land)
+            }
+        }
+    }
+}
+
+#[test]
+fn toroidal_grid_wrap() {
+    let grid: ToroidalGrid<LandCoastSea> = ToroidalGrid::new(4, 3);
+    assert_eq!(grid.wrap(Coord2D::new(4, 3)), Some(Coord2D::new(0, 0)));
+    assert_eq!(grid.wrap(Coord2D::new(usize::MAX, 1)), Some(Coord2D::new(3, 1)));
+    assert_eq!(grid.wrap(Coord2D::new(2, 7)), Some(Coord2D::new(2, 1)));
+    assert!(grid.get_cell(&Coord2D::new(9, usize::MAX)).is_some());
+    assert_eq!(ToroidalGrid::<LandCoastSea>::new(0, 3).wrap(Coord2D::new(0, 0)), None);
+
+    let neighbors = grid.neighbors(Coord2D::new(0, 0));
+    assert_eq!(neighbors.len(), 8);
+    assert!(neighbors.contains(&Coord2D::new(3, 2)));
+    assert!(neighbors.contains(&Coord2D::new(1, 1)));
+    assert!(neighbors.iter().all(|coord| coord.x() < 4 && coord.y() < 3));
+}
+
+#[test]
+fn toroidal_grid_seam() {
+    let mut solver = Solver::new(ToroidalTest::new(8, 6));
+    solver.collapse_initial(Coord2D::new(0, 0), LandCoastSea::Land);
+
+    // The opposite corner neighbors the collapsed cell across both seams
+    let corner = solver.get_initial_state().get_cell(&Coord2D::new(7, 5)).unwrap();
+    assert!(!corner.get_possibilities().contains_key(&LandCoastSea::Sea));
+
+    solver.collapse_initial(Coord2D::new(4, 3), LandCoastSea::Sea);
+    solver.set_seed(2);
+    let layout = solver.solve().expect("toroidal grid should be solvable");
+    let mut grid = layout.grid().clone();
+    let cells: Vec<_> = grid.cells().map(|(coord, cell)| (coord, cell.clone())).collect();
+    for (coord, cell) in cells {
+        assert!(cell.is_collapsed());
+        if cell.get_value() != Some(LandCoastSea::Land) {
+            continue;
+        }
+        for neighbor in layout.neighbors(coord) {
+            assert_ne!(layout.get_cell(&neighbor).unwrap().get_value(), Some(LandCoastSea::Sea));
+        }
+    }
+}